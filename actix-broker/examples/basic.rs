@@ -60,14 +60,16 @@ impl Handler<MessageOne> for ActorThree {
 
 #[derive(Clone, Debug, Message)]
 #[rtype(result = "()")]
+#[allow(dead_code)]
 struct MessageOne(String);
 
 #[derive(Clone, Debug, Message)]
 #[rtype(result = "()")]
+#[allow(dead_code)]
 struct MessageTwo(u8);
 
 fn main() {
-    let _ = System::new().block_on(async {
+    System::new().block_on(async {
         ActorTwo.start();
         ActorThree.start();
         ActorOne.start();
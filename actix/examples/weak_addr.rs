@@ -12,6 +12,7 @@ use std::time::{Duration, Instant};
 
 #[derive(Message, Debug)]
 #[rtype(result = "()")]
+#[allow(dead_code)]
 pub struct TimePing(Instant);
 
 #[derive(Message, Debug)]
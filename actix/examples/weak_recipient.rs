@@ -12,13 +12,14 @@ use std::time::{Duration, Instant};
 
 #[derive(Message, Debug)]
 #[rtype(result = "()")]
+#[allow(dead_code)]
 pub struct TimePing(Instant);
 
 #[derive(Message, Debug)]
 #[rtype(result = "()")]
 pub struct RegisterForTime(pub WeakRecipient<TimePing>);
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct TimeService {
     clients: Vec<WeakRecipient<TimePing>>,
 }
@@ -79,14 +80,6 @@ impl Handler<RegisterForTime> for TimeService {
 impl Supervised for TimeService {}
 impl SystemService for TimeService {}
 
-impl Default for TimeService {
-    fn default() -> Self {
-        TimeService {
-            clients: Default::default(),
-        }
-    }
-}
-
 #[derive(Debug, Default)]
 pub struct ClientA;
 
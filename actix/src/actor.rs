@@ -1,17 +1,21 @@
+use std::future::Future;
 use std::time::Duration;
 
 use actix_rt::ArbiterHandle;
 use futures_core::stream::Stream;
+use futures_util::stream::StreamExt;
 use log::error;
 
 use crate::address::{channel, Addr};
 use crate::context::Context;
-use crate::contextitems::{ActorDelayedMessageItem, ActorMessageItem, ActorMessageStreamItem};
-use crate::fut::{ActorFuture, ActorStreamExt};
-use crate::handler::{Handler, Message};
+use crate::contextitems::{
+    ActorDelayedMessageItem, ActorJoinItem, ActorMessageItem, ActorMessageStreamItem,
+};
+use crate::fut::{wrap_future, ActorFuture, ActorFutureExt, ActorStreamExt};
+use crate::handler::{Handler, Message, MessageResponse};
 use crate::mailbox::DEFAULT_CAPACITY;
 use crate::stream::StreamHandler;
-use crate::utils::{IntervalFunc, TimerFunc};
+use crate::utils::{output_channel, IntervalFunc, OutputSender, OutputStream, TimerFunc};
 
 /// Actors are objects which encapsulate state and behavior.
 ///
@@ -53,6 +57,7 @@ use crate::utils::{IntervalFunc, TimerFunc};
 /// * `Context::stop` gets called by actor itself
 /// * all addresses to the actor get dropped
 /// * no evented objects are registered in its context.
+/// * the actor's arbiter shuts down, e.g. because the `System` stops.
 ///
 /// An actor can return from the `stopping` state to the `running`
 /// state by creating a new address or adding an evented object, like
@@ -64,6 +69,13 @@ use crate::utils::{IntervalFunc, TimerFunc};
 /// method. If an actor does not return back to a `running` state,
 /// all unprocessed messages get dropped.
 ///
+/// When the arbiter shuts down, the context future gets dropped. The
+/// context then stops the actor and polls it one last time, so that
+/// `Actor::stopping` and `Actor::stopped` still get called and can flush
+/// state. This is best effort: spawned futures are not polled any more,
+/// and if `stopping` returns `Running::Continue`, `stopped` does not get
+/// called.
+///
 /// ## Stopped
 ///
 /// If an actor does not modify execution context while in stopping
@@ -77,6 +89,16 @@ pub trait Actor: Sized + Unpin + 'static {
     /// Called when an actor gets polled the first time.
     fn started(&mut self, ctx: &mut Self::Context) {}
 
+    /// Decides whether `started` gets called when the actor gets polled
+    /// the first time.
+    ///
+    /// An actor that resumes from a persisted state can return `false` to
+    /// skip its startup side effects and go straight to processing messages.
+    /// By default this method returns `true`.
+    fn should_start(&self) -> bool {
+        true
+    }
+
     /// Called after an actor is in `Actor::Stopping` state.
     ///
     /// There can be several reasons for stopping:
@@ -137,6 +159,50 @@ pub trait Actor: Sized + Unpin + 'static {
         Self::default().start()
     }
 
+    /// Start a new asynchronous actor with a stream registered to its
+    /// context, returning its address.
+    ///
+    /// `f` builds the stream and may read the actor's state while doing so.
+    /// Stream items are handled by the actor's [`StreamHandler`], in the same
+    /// way as with [`AsyncContext::add_stream`]. Streams of `Result<I, E>`
+    /// are handled by implementing `StreamHandler<Result<I, E>>`.
+    fn start_with_stream<F, S>(self, f: F) -> Addr<Self>
+    where
+        Self: Actor<Context = Context<Self>> + StreamHandler<S::Item>,
+        F: FnOnce(&Self) -> S,
+        S: Stream + 'static,
+    {
+        let mut ctx = Context::new();
+        let stream = f(&self);
+        ctx.add_stream(stream);
+        ctx.run(self)
+    }
+
+    /// Start a new asynchronous actor which handles `items` as a stream,
+    /// returning its address.
+    ///
+    /// Items are handled by the actor's `StreamHandler<Result<T, E>>` in
+    /// order, after which `StreamHandler::finished` is called. If
+    /// `stop_on_error` is `true`, the stream ends after the first `Err`
+    /// item, otherwise errors are handled like any other item. This is handy
+    /// for batch processing and deterministic tests.
+    fn start_with_items<T, E>(self, items: Vec<Result<T, E>>, stop_on_error: bool) -> Addr<Self>
+    where
+        Self: Actor<Context = Context<Self>> + StreamHandler<Result<T, E>>,
+        T: 'static,
+        E: 'static,
+    {
+        let mut failed = false;
+        let items = items.into_iter().take_while(move |item| {
+            let take = !failed;
+            failed = stop_on_error && item.is_err();
+            take
+        });
+        let mut ctx = Context::new();
+        ctx.add_stream(futures_util::stream::iter(items));
+        ctx.run(self)
+    }
+
     /// Start new actor in arbiter's thread.
     fn start_in_arbiter<F>(wrk: &ArbiterHandle, f: F) -> Addr<Self>
     where
@@ -191,6 +257,69 @@ pub trait Actor: Sized + Unpin + 'static {
         let act = f(&mut ctx);
         ctx.run(act)
     }
+
+    /// Start a new asynchronous actor which is only constructed by `f`
+    /// once the first message is sent to it.
+    ///
+    /// Its context is created along with the actor, until then only the
+    /// mailbox exists. See [`Context::run_lazy`] for details.
+    fn create_lazy<F>(f: F) -> Addr<Self>
+    where
+        Self: Actor<Context = Context<Self>>,
+        F: FnOnce(&mut Context<Self>) -> Self + 'static,
+    {
+        Context::create_lazy(f)
+    }
+
+    /// Construct and start a new asynchronous actor that emits a stream of
+    /// outputs over its lifetime.
+    ///
+    /// This is similar to `create` method, except the closure also receives
+    /// an [`OutputSender`], whose items are yielded by the returned
+    /// [`OutputStream`]. The stream ends once the actor stops and drops the
+    /// sender, which suits long-lived transformers with no single terminal
+    /// value, e.g. a decoder.
+    ///
+    /// ```
+    /// # use actix::prelude::*;
+    /// use actix::utils::OutputSender;
+    /// use futures_util::stream::StreamExt;
+    ///
+    /// struct Doubler(OutputSender<usize>);
+    ///
+    /// impl Actor for Doubler {
+    ///     type Context = Context<Self>;
+    /// }
+    ///
+    /// #[derive(Message)]
+    /// #[rtype(result = "()")]
+    /// struct Num(usize);
+    ///
+    /// impl Handler<Num> for Doubler {
+    ///     type Result = ();
+    ///
+    ///     fn handle(&mut self, msg: Num, _: &mut Context<Self>) {
+    ///         let _ = self.0.send(msg.0 * 2);
+    ///     }
+    /// }
+    ///
+    /// fn main() {
+    ///     System::new().block_on(async {
+    ///         let (addr, mut output) = Doubler::create_with_output(|_, tx| Doubler(tx));
+    ///         addr.do_send(Num(21));
+    ///         assert_eq!(output.next().await, Some(42));
+    ///     });
+    /// }
+    /// ```
+    fn create_with_output<F, T>(f: F) -> (Addr<Self>, OutputStream<T>)
+    where
+        Self: Actor<Context = Context<Self>>,
+        F: FnOnce(&mut Context<Self>, OutputSender<T>) -> Self,
+    {
+        let (tx, rx) = output_channel();
+        let addr = Self::create(move |ctx| f(ctx, tx));
+        (addr, rx)
+    }
 }
 
 #[allow(unused_variables)]
@@ -280,6 +409,11 @@ where
     ///
     /// All futures spawned into an actor's context are cancelled
     /// during the actor's stopping stage.
+    ///
+    /// A future spawned while the context polls its spawned futures, e.g.
+    /// from within another spawned future, is not polled in the same pass.
+    /// It is polled in the next pass of the same wake-up, after pending
+    /// wait futures and stashed messages scheduled for redelivery.
     fn spawn<F>(&mut self, fut: F) -> SpawnHandle
     where
         F: ActorFuture<A, Output = ()> + 'static;
@@ -351,6 +485,81 @@ where
         <A as StreamHandler<S::Item>>::add_stream(fut, self)
     }
 
+    /// Registers a stream that is re-created every time it finishes.
+    ///
+    /// `mk` is called to construct a fresh stream whenever the previous one
+    /// ends, optionally after waiting `delay`. This suits sources that
+    /// naturally end but should be re-subscribed, like re-issuing a query.
+    /// `StreamHandler::reconnected` is called for every new stream and
+    /// `StreamHandler::finished` is never called; cancel the returned handle
+    /// to stop the repetition.
+    fn add_stream_repeating<F, S>(&mut self, mk: F, delay: Option<Duration>) -> SpawnHandle
+    where
+        F: FnMut() -> S + 'static,
+        S: Stream + 'static,
+        A: StreamHandler<S::Item>,
+    {
+        <A as StreamHandler<S::Item>>::add_stream_repeating(mk, delay, self)
+    }
+
+    /// Registers a stream that is re-established every time it finishes.
+    ///
+    /// `mk` returns a future which (re)connects and resolves to the stream.
+    /// It is called again, optionally after `delay`, whenever the stream
+    /// ends, and `StreamHandler::reconnected` is called once the new stream
+    /// is in place. Cancel the returned handle to stop reconnecting.
+    fn add_stream_reconnecting<F, Fut, S>(
+        &mut self,
+        mk: F,
+        delay: Option<Duration>,
+    ) -> SpawnHandle
+    where
+        F: FnMut() -> Fut + 'static,
+        Fut: Future<Output = S> + 'static,
+        S: Stream + 'static,
+        A: StreamHandler<S::Item>,
+    {
+        <A as StreamHandler<S::Item>>::add_stream_reconnecting(mk, delay, self)
+    }
+
+    /// Registers a stream whose items are handled in time-based batches.
+    ///
+    /// Items are buffered and handed to `StreamHandler<Vec<S::Item>>` once
+    /// per `window`, for aggregation like emitting a summary of samples
+    /// every second. Empty windows are skipped and the final partial batch
+    /// is handled when the stream ends.
+    fn add_stream_windowed<S>(&mut self, stream: S, window: Duration) -> SpawnHandle
+    where
+        S: Stream + 'static,
+        A: StreamHandler<Vec<S::Item>>,
+    {
+        <A as StreamHandler<Vec<S::Item>>>::add_stream_windowed(stream, window, self)
+    }
+
+    /// Registers a stream whose first item is handled by `f`.
+    ///
+    /// `f` gets the first item, or `None` if the stream ended right away, and
+    /// can configure the actor from it. The rest of the stream is then added
+    /// with [`add_stream`](Self::add_stream). This suits handshake-then-data
+    /// protocols. The returned handle only cancels waiting for the first item.
+    fn add_stream_after_first<S, F>(&mut self, stream: S, f: F) -> SpawnHandle
+    where
+        S: Stream + 'static,
+        F: FnOnce(&mut A, &mut A::Context, Option<S::Item>) + 'static,
+        A: StreamHandler<S::Item>,
+    {
+        let first = Box::pin(stream).into_future();
+        self.spawn(
+            wrap_future(first).map(|(item, rest), act, ctx: &mut A::Context| {
+                let done = item.is_none();
+                f(act, ctx, item);
+                if !done {
+                    ctx.add_stream(rest);
+                }
+            }),
+        )
+    }
+
     /// Registers a stream with the context, ignoring errors.
     ///
     /// This method is similar to `add_stream` but it skips stream
@@ -450,6 +659,20 @@ where
         self.spawn(TimerFunc::new(dur, f))
     }
 
+    /// Executes a closure on the next poll of the context.
+    ///
+    /// Unlike a zero [`run_later`](Self::run_later), no timer is involved:
+    /// the context is woken right away, and the closure runs after other
+    /// futures and the mailbox had their turn. An actor that buffers work
+    /// internally can drain it in chunks this way, without waiting for the
+    /// next external event.
+    fn run_soon<F>(&mut self, f: F) -> SpawnHandle
+    where
+        F: FnOnce(&mut A, &mut A::Context) + 'static,
+    {
+        self.spawn(wrap_future(actix_rt::task::yield_now()).map(move |_, act, ctx| f(act, ctx)))
+    }
+
     /// Spawns a job to execute the given closure periodically, at a
     /// specified fixed interval.
     fn run_interval<F>(&mut self, dur: Duration, f: F) -> SpawnHandle
@@ -458,12 +681,61 @@ where
     {
         self.spawn(IntervalFunc::new(dur, f).finish())
     }
+
+    /// Spawns a future that is dropped if it does not complete within `ttl`.
+    ///
+    /// When the lifetime expires, the future is dropped and `on_expired` is
+    /// called. This suits fire-and-forget work like speculative prefetching,
+    /// which should cancel itself when slow instead of piling up.
+    fn spawn_with_ttl<F, T>(&mut self, fut: F, ttl: Duration, on_expired: T) -> SpawnHandle
+    where
+        F: ActorFuture<A, Output = ()> + 'static,
+        T: FnOnce(&mut A, &mut A::Context) + 'static,
+    {
+        self.spawn(fut.timeout(ttl).map(move |res, act, ctx| {
+            if res.is_err() {
+                on_expired(act, ctx);
+            }
+        }))
+    }
+
+    /// Spawns a group of futures and calls `f` once all of them completed.
+    ///
+    /// `f` receives the outputs of the futures in the order they completed.
+    /// The group is driven as a single spawned item, so the returned handle
+    /// cancels all of its futures. This is a context-aware `join_all`.
+    fn spawn_join<I, F>(&mut self, futs: I, f: F) -> SpawnHandle
+    where
+        I: IntoIterator,
+        I::Item: ActorFuture<A> + 'static,
+        F: FnOnce(&mut A, &mut A::Context, Vec<<I::Item as ActorFuture<A>>::Output>) + 'static,
+    {
+        self.spawn(ActorJoinItem::new(futs, f))
+    }
+
+    /// Spawns a plain future whose success value is discarded.
+    ///
+    /// Only an error is delivered to the actor, through its `Handler<E>`
+    /// implementation. This suits background work such as writes that need no
+    /// confirmation, but whose failure must be noticed.
+    fn spawn_discard<F, E>(&mut self, fut: F) -> SpawnHandle
+    where
+        F: Future<Output = Result<(), E>> + 'static,
+        E: Message + 'static,
+        A: Handler<E>,
+    {
+        self.spawn(wrap_future(fut).map(|res, act, ctx| {
+            if let Err(err) = res {
+                <A as Handler<E>>::handle(act, err, ctx).handle(ctx, None);
+            }
+        }))
+    }
 }
 
 /// A handle to a spawned future.
 ///
 /// Can be used to cancel the future.
-#[derive(Eq, PartialEq, Debug, Copy, Clone, Hash)]
+#[derive(Eq, PartialEq, Ord, PartialOrd, Debug, Copy, Clone, Hash, Default)]
 pub struct SpawnHandle(usize);
 
 impl SpawnHandle {
@@ -476,9 +748,3 @@ impl SpawnHandle {
         self.0
     }
 }
-
-impl Default for SpawnHandle {
-    fn default() -> SpawnHandle {
-        SpawnHandle(0)
-    }
-}
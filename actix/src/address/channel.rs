@@ -20,6 +20,7 @@ use parking_lot::Mutex;
 use tokio::sync::oneshot::{channel as oneshot_channel, Receiver as OneshotReceiver};
 
 use crate::actor::Actor;
+use crate::clock::Instant;
 use crate::handler::{Handler, Message};
 
 use super::envelope::{Envelope, ToEnvelope};
@@ -37,6 +38,12 @@ where
 
     fn send(&self, msg: M) -> Result<OneshotReceiver<M::Result>, SendError<M>>;
 
+    fn send_until(
+        &self,
+        msg: M,
+        deadline: Instant,
+    ) -> Result<OneshotReceiver<M::Result>, SendError<M>>;
+
     fn boxed(&self) -> Box<dyn Sender<M> + Sync>;
 
     fn hash(&self) -> usize;
@@ -62,6 +69,14 @@ where
         (**self).send(msg)
     }
 
+    fn send_until(
+        &self,
+        msg: M,
+        deadline: Instant,
+    ) -> Result<OneshotReceiver<M::Result>, SendError<M>> {
+        (**self).send_until(msg, deadline)
+    }
+
     fn boxed(&self) -> Box<dyn Sender<M> + Sync> {
         (**self).boxed()
     }
@@ -84,6 +99,8 @@ where
     ///
     /// Returns [`None`] if the actor has since been dropped.
     fn upgrade(&self) -> Option<Box<dyn Sender<M> + Sync>>;
+
+    #[allow(dead_code)]
     fn boxed(&self) -> Box<dyn WeakSender<M> + Sync>;
 }
 
@@ -134,8 +151,6 @@ impl<A: Actor> fmt::Debug for WeakAddressSender<A> {
     }
 }
 
-trait AssertKinds: Send + Sync + Clone {}
-
 /// The receiving end of a channel which implements the `Stream` trait.
 ///
 /// This is a concrete implementation of a stream which can be used to represent
@@ -289,6 +304,37 @@ impl<A: Actor> AddressSender<A> {
         A::Context: ToEnvelope<A, M>,
         M::Result: Send,
         M: Message + Send,
+    {
+        self.send_envelope(msg, |env| env)
+    }
+
+    /// Like `send`, the message is skipped if it is not handled before
+    /// `deadline`.
+    pub fn send_until<M>(
+        &self,
+        msg: M,
+        deadline: Instant,
+    ) -> Result<OneshotReceiver<M::Result>, SendError<M>>
+    where
+        A: Handler<M>,
+        A::Context: ToEnvelope<A, M>,
+        M::Result: Send,
+        M: Message + Send + 'static,
+    {
+        self.send_envelope(msg, |env| env.with_deadline::<M>(deadline))
+    }
+
+    fn send_envelope<M, F>(
+        &self,
+        msg: M,
+        wrap: F,
+    ) -> Result<OneshotReceiver<M::Result>, SendError<M>>
+    where
+        A: Handler<M>,
+        A::Context: ToEnvelope<A, M>,
+        M::Result: Send,
+        M: Message + Send,
+        F: FnOnce(Envelope<A>) -> Envelope<A>,
     {
         // If the sender is currently blocked, reject the message
         if !self.poll_unparked(false, None).is_ready() {
@@ -317,7 +363,7 @@ impl<A: Actor> AddressSender<A> {
             self.park();
         }
         let (tx, rx) = oneshot_channel();
-        let env = <A::Context as ToEnvelope<A, M>>::pack(msg, Some(tx));
+        let env = wrap(<A::Context as ToEnvelope<A, M>>::pack(msg, Some(tx)));
         self.queue_push_and_signal(env);
         Ok(rx)
     }
@@ -432,7 +478,11 @@ impl<A: Actor> AddressSender<A> {
         self.maybe_parked.store(state.is_open, Relaxed);
     }
 
-    fn poll_unparked(&self, do_park: bool, cx: Option<&mut task::Context<'_>>) -> Poll<()> {
+    pub(crate) fn poll_unparked(
+        &self,
+        do_park: bool,
+        cx: Option<&mut task::Context<'_>>,
+    ) -> Poll<()> {
         // First check the `maybe_parked` variable. This avoids acquiring the
         // lock in most cases
         if self.maybe_parked.load(Relaxed) {
@@ -479,6 +529,13 @@ where
     fn send(&self, msg: M) -> Result<OneshotReceiver<M::Result>, SendError<M>> {
         self.send(msg)
     }
+    fn send_until(
+        &self,
+        msg: M,
+        deadline: Instant,
+    ) -> Result<OneshotReceiver<M::Result>, SendError<M>> {
+        self.send_until(msg, deadline)
+    }
     fn boxed(&self) -> Box<dyn Sender<M> + Sync> {
         Box::new(self.clone())
     }
@@ -562,10 +619,7 @@ impl<A: Actor> WeakAddressSender<A> {
     ///
     /// Returns [`None`] if the actor has since been dropped.
     pub fn upgrade(&self) -> Option<AddressSender<A>> {
-        match Weak::upgrade(&self.inner) {
-            Some(inner) => Some(AddressSenderProducer { inner }.sender()),
-            None => None,
-        }
+        Weak::upgrade(&self.inner).map(|inner| AddressSenderProducer { inner }.sender())
     }
 }
 
@@ -577,7 +631,7 @@ where
     M: Message + Send + 'static,
 {
     fn upgrade(&self) -> Option<Box<dyn Sender<M> + Sync>> {
-        if let Some(inner) = WeakAddressSender::upgrade(&self) {
+        if let Some(inner) = WeakAddressSender::upgrade(self) {
             Some(Box::new(inner))
         } else {
             None
@@ -605,6 +659,11 @@ impl<A: Actor> AddressSenderProducer<A> {
         self.inner.buffer.load(Relaxed)
     }
 
+    /// Number of messages waiting in the channel
+    pub fn queued(&self) -> usize {
+        decode_state(self.inner.state.load(SeqCst)).num_messages
+    }
+
     /// Set channel capacity
     ///
     /// This method wakes up all waiting senders if new capacity is greater
@@ -1,6 +1,9 @@
+use std::any::Any;
+
 use tokio::sync::oneshot::Sender;
 
 use crate::actor::{Actor, AsyncContext};
+use crate::clock::Instant;
 use crate::context::Context;
 use crate::handler::{Handler, Message, MessageResponse};
 
@@ -17,6 +20,16 @@ where
 pub trait EnvelopeProxy<A: Actor> {
     /// handle message within new actor and context
     fn handle(&mut self, act: &mut A, ctx: &mut A::Context);
+
+    /// message carried by the envelope, if it can be inspected
+    fn message(&self) -> Option<&dyn Any> {
+        None
+    }
+
+    /// take the message out of the envelope without handling it
+    fn take_message(&mut self) -> Option<Box<dyn Any>> {
+        None
+    }
 }
 
 impl<A, M> ToEnvelope<A, M> for Context<A>
@@ -46,12 +59,44 @@ impl<A: Actor> Envelope<A> {
     pub fn with_proxy(proxy: Box<dyn EnvelopeProxy<A> + Send>) -> Self {
         Envelope(proxy)
     }
+
+    /// Wraps the envelope so the message is passed to `Handler::expired`
+    /// once `deadline` passed.
+    pub(crate) fn with_deadline<M>(self, deadline: Instant) -> Self
+    where
+        A: Handler<M>,
+        M: Message + 'static,
+    {
+        fn expire<A, M>(env: &mut Envelope<A>, act: &mut A, ctx: &mut A::Context)
+        where
+            A: Handler<M>,
+            M: Message + 'static,
+        {
+            if let Some(Ok(msg)) = env.take_message().map(|msg| msg.downcast::<M>()) {
+                <A as Handler<M>>::expired(act, *msg, ctx)
+            }
+        }
+
+        Envelope(Box::new(DeadlineEnvelopeProxy {
+            env: self,
+            deadline,
+            expire: expire::<A, M>,
+        }))
+    }
 }
 
 impl<A: Actor> EnvelopeProxy<A> for Envelope<A> {
     fn handle(&mut self, act: &mut A, ctx: &mut <A as Actor>::Context) {
         self.0.handle(act, ctx)
     }
+
+    fn message(&self) -> Option<&dyn Any> {
+        self.0.message()
+    }
+
+    fn take_message(&mut self) -> Option<Box<dyn Any>> {
+        self.0.take_message()
+    }
 }
 
 pub struct SyncEnvelopeProxy<M>
@@ -81,4 +126,43 @@ where
             fut.handle(ctx, tx)
         }
     }
+
+    fn message(&self) -> Option<&dyn Any> {
+        let msg: &dyn Any = self.msg.as_ref()?;
+        Some(msg)
+    }
+
+    fn take_message(&mut self) -> Option<Box<dyn Any>> {
+        // the sender is answered with a timeout
+        self.tx.take();
+        let msg: Box<dyn Any> = Box::new(self.msg.take()?);
+        Some(msg)
+    }
+}
+
+type Expire<A> = fn(&mut Envelope<A>, &mut A, &mut <A as Actor>::Context);
+
+/// Envelope of a message that must be handled before a deadline.
+struct DeadlineEnvelopeProxy<A: Actor> {
+    env: Envelope<A>,
+    deadline: Instant,
+    expire: Expire<A>,
+}
+
+impl<A: Actor> EnvelopeProxy<A> for DeadlineEnvelopeProxy<A> {
+    fn handle(&mut self, act: &mut A, ctx: &mut <A as Actor>::Context) {
+        if Instant::now() < self.deadline {
+            self.env.handle(act, ctx)
+        } else {
+            (self.expire)(&mut self.env, act, ctx)
+        }
+    }
+
+    fn message(&self) -> Option<&dyn Any> {
+        self.env.message()
+    }
+
+    fn take_message(&mut self) -> Option<Box<dyn Any>> {
+        self.env.take_message()
+    }
 }
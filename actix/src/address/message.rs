@@ -6,7 +6,7 @@ use std::time::Duration;
 use pin_project_lite::pin_project;
 use tokio::sync::oneshot;
 
-use crate::clock::Sleep;
+use crate::clock::{sleep_until, Instant, Sleep};
 use crate::handler::Message;
 
 use super::channel::{AddressSender, Sender};
@@ -30,6 +30,7 @@ pin_project! {
         info: Option<(S, M)>,
         #[pin]
         timeout: Option<Sleep>,
+        deadline: Option<Instant>,
     }
 }
 
@@ -44,9 +45,17 @@ where
             rx,
             info,
             timeout: None,
+            deadline: None,
         }
     }
 
+    /// Skip the message unless it is handled before `deadline`.
+    pub(crate) fn deadline(mut self, deadline: Instant) -> Self {
+        self.timeout = Some(sleep_until(deadline));
+        self.deadline = Some(deadline);
+        self
+    }
+
     #[cfg(test)]
     pub(crate) fn rx_is_some(&self) -> bool {
         self.rx.is_some()
@@ -71,7 +80,11 @@ where
         let this = self.project();
 
         if let Some((sender, msg)) = this.info.take() {
-            match sender.send(msg) {
+            let res = match *this.deadline {
+                Some(deadline) => sender.send_until(msg, deadline),
+                None => sender.send(msg),
+            };
+            match res {
                 Ok(rx) => *this.rx = Some(rx),
                 Err(SendError::Full(msg)) => {
                     *this.info = Some((sender, msg));
@@ -83,7 +96,12 @@ where
 
         match this.rx {
             Some(rx) => match Pin::new(rx).poll(cx) {
-                Poll::Ready(res) => Poll::Ready(res.map_err(|_| MailboxError::Closed)),
+                Poll::Ready(Ok(res)) => Poll::Ready(Ok(res)),
+                // the actor skipped the expired message
+                Poll::Ready(Err(_)) if matches!(this.deadline, Some(at) if Instant::now() >= *at) => {
+                    Poll::Ready(Err(MailboxError::Timeout))
+                }
+                Poll::Ready(Err(_)) => Poll::Ready(Err(MailboxError::Closed)),
                 Poll::Pending => match this.timeout.as_pin_mut() {
                     Some(timeout) => timeout.poll(cx).map(|_| Err(MailboxError::Timeout)),
                     None => Poll::Pending,
@@ -5,12 +5,15 @@ pub(crate) mod channel;
 mod envelope;
 mod message;
 mod queue;
+mod sink;
 
 use crate::actor::Actor;
+use crate::clock::Instant;
 use crate::handler::{Handler, Message};
 
 pub use self::envelope::{Envelope, EnvelopeProxy, ToEnvelope};
 pub use self::message::{RecipientRequest, Request};
+pub use self::sink::AddrSink;
 
 pub(crate) use self::channel::{AddressReceiver, AddressSenderProducer};
 use self::channel::{AddressSender, Sender, WeakAddressSender, WeakSender};
@@ -138,12 +141,52 @@ impl<A: Actor> Addr<A> {
         }
     }
 
+    /// Sends an asynchronous message which is skipped if it is not handled
+    /// before `deadline`.
+    ///
+    /// A backlogged actor does not waste work on requests whose callers have
+    /// already given up: once the deadline has passed, the message is passed
+    /// to [`Handler::expired`] instead of [`Handler::handle`], and the returned
+    /// future resolves to [`MailboxError::Timeout`].
+    pub fn send_with_deadline<M>(&self, msg: M, deadline: Instant) -> Request<A, M>
+    where
+        M: Message + Send + 'static,
+        M::Result: Send,
+        A: Handler<M>,
+        A::Context: ToEnvelope<A, M>,
+    {
+        let req = match self.tx.send_until(msg, deadline) {
+            Ok(rx) => Request::new(Some(rx), None),
+            Err(SendError::Full(msg)) => Request::new(None, Some((self.tx.clone(), msg))),
+            Err(SendError::Closed(_)) => Request::new(None, None),
+        };
+        req.deadline(deadline)
+    }
+
+    /// Turns the address into a `Sink` of messages of type `M`.
+    ///
+    /// Items sent to the sink are delivered to the actor's mailbox, results
+    /// of the handler are discarded. The sink is ready while the mailbox has
+    /// capacity, so a `stream.forward(addr.into_sink())` pipeline slows down
+    /// to the pace at which the actor handles messages. Flushing does not
+    /// wait for queued messages to be handled. The sink fails with
+    /// [`MailboxError::Closed`] once the actor has stopped.
+    pub fn into_sink<M>(self) -> AddrSink<A, M>
+    where
+        A: Handler<M>,
+        A::Context: ToEnvelope<A, M>,
+        M: Message + Send + 'static,
+        M::Result: Send,
+    {
+        AddrSink::new(self.tx)
+    }
+
     /// Returns the `Recipient` for a specific message type.
-    pub fn recipient<M: 'static>(self) -> Recipient<M>
+    pub fn recipient<M>(self) -> Recipient<M>
     where
         A: Handler<M>,
         A::Context: ToEnvelope<A, M>,
-        M: Message + Send,
+        M: Message + Send + 'static,
         M::Result: Send,
     {
         self.into()
@@ -203,11 +246,11 @@ impl<A: Actor> WeakAddr<A> {
         }
     }
 
-    pub fn recipient<M: 'static>(self) -> WeakRecipient<M>
+    pub fn recipient<M>(self) -> WeakRecipient<M>
     where
         A: Handler<M>,
         A::Context: ToEnvelope<A, M>,
-        M: Message + Send,
+        M: Message + Send + 'static,
         M::Result: Send,
     {
         self.into()
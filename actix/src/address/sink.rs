@@ -0,0 +1,72 @@
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures_sink::Sink;
+
+use crate::actor::Actor;
+use crate::handler::{Handler, Message};
+
+use super::channel::AddressSender;
+use super::{MailboxError, SendError, ToEnvelope};
+
+/// A `Sink` which delivers items to an actor's mailbox.
+///
+/// Created with [`Addr::into_sink`](super::Addr::into_sink).
+///
+/// # Panics
+///
+/// `start_send` panics when it is called while the mailbox is full, without
+/// `poll_ready` having returned `Poll::Ready` first.
+pub struct AddrSink<A: Actor, M> {
+    tx: AddressSender<A>,
+    _msg: PhantomData<fn(M)>,
+}
+
+impl<A: Actor, M> AddrSink<A, M> {
+    pub(crate) fn new(tx: AddressSender<A>) -> Self {
+        AddrSink {
+            tx,
+            _msg: PhantomData,
+        }
+    }
+}
+
+impl<A: Actor, M> Unpin for AddrSink<A, M> {}
+
+impl<A, M> Sink<M> for AddrSink<A, M>
+where
+    A: Handler<M>,
+    A::Context: ToEnvelope<A, M>,
+    M: Message + Send + 'static,
+    M::Result: Send,
+{
+    type Error = MailboxError;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        if !self.tx.connected() {
+            return Poll::Ready(Err(MailboxError::Closed));
+        }
+        self.tx.poll_unparked(true, Some(cx)).map(Ok)
+    }
+
+    fn start_send(self: Pin<&mut Self>, msg: M) -> Result<(), Self::Error> {
+        match self.tx.try_send(msg, true) {
+            Ok(()) => Ok(()),
+            // the sender is only parked with a full mailbox, when `poll_ready`
+            // was not waited on
+            Err(SendError::Full(_)) => {
+                panic!("start_send called on AddrSink without readiness")
+            }
+            Err(SendError::Closed(_)) => Err(MailboxError::Closed),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+}
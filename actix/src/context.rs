@@ -1,10 +1,43 @@
+use std::any::Any;
+use std::cell::{Cell, RefCell};
 use std::fmt;
+use std::future::Future;
+use std::rc::Rc;
+use std::task::{Poll, Waker};
+use std::time::Duration;
+
+use actix_rt::task::JoinHandle;
+use futures_core::stream::Stream;
+use futures_util::future::{self, poll_fn};
+use log::error;
+use tokio::io::{AsyncRead, AsyncWrite, WriteHalf};
+use tokio_util::codec::{Decoder, Encoder, FramedRead};
 
 use crate::actor::{Actor, ActorContext, ActorState, AsyncContext, SpawnHandle};
-use crate::address::{Addr, AddressReceiver};
-use crate::contextimpl::{AsyncContextParts, ContextFut, ContextParts};
-use crate::fut::ActorFuture;
+use crate::address::{Addr, AddressReceiver, Envelope, EnvelopeProxy};
+use crate::clock::Instant;
+#[cfg(feature = "metrics")]
+use crate::contextimpl::MetricsSink;
+use crate::contextimpl::{
+    AsyncContextParts, Callback, ContextFut, ContextParts, InboxStat, ItemInfo, ItemScheduler,
+    LatencyStats, MailboxPriority, PendingItems, ResumeHandle, StashOverflow, Stashed,
+};
+use crate::contextitems::{ActorDelayedMessageItem, ActorOrderedFuturesItem, Inbox};
+use crate::fut::{wrap_future, ActorFuture, ActorFutureExt, ActorStreamExt};
+use crate::handler::{Handler, Message, MessageResponse};
+use crate::io::{FramedWrite, WriteHandler};
 use crate::mailbox::Mailbox;
+use crate::stream::{ActorStream, StreamHandler};
+use crate::utils::{
+    inbox_channel, output_channel, CircuitBreaker, InboxSender, IntervalFunc, OutputSender,
+    OutputStream, ShutdownCoordinator, TimerFunc,
+};
+
+/// Sender of `Context::error_stream`, kept with the context-local values.
+struct ErrorSink<E>(OutputSender<E>);
+
+/// Sender of `Context::results`, kept with the context-local values.
+struct ResultSink<T>(OutputSender<T>);
 
 /// An actor execution context.
 pub struct Context<A>
@@ -76,6 +109,42 @@ where
     fn address(&self) -> Addr<A> {
         self.parts.address()
     }
+
+    fn spawn_with_ttl<F, T>(&mut self, fut: F, ttl: Duration, on_expired: T) -> SpawnHandle
+    where
+        F: ActorFuture<A, Output = ()> + 'static,
+        T: FnOnce(&mut A, &mut A::Context) + 'static,
+    {
+        self.parts.spawn_with_ttl(fut, ttl, Box::new(on_expired))
+    }
+
+    fn notify_later<M>(&mut self, msg: M, after: Duration) -> SpawnHandle
+    where
+        A: Handler<M>,
+        M: Message + 'static,
+    {
+        if self.state() == ActorState::Stopped {
+            error!("Context::notify_later called for stopped actor.");
+            SpawnHandle::default()
+        } else {
+            self.parts
+                .spawn_timer(ActorDelayedMessageItem::new(msg, after))
+        }
+    }
+
+    fn run_later<F>(&mut self, dur: Duration, f: F) -> SpawnHandle
+    where
+        F: FnOnce(&mut A, &mut A::Context) + 'static,
+    {
+        self.parts.spawn_timer(TimerFunc::new(dur, f))
+    }
+
+    fn run_interval<F>(&mut self, dur: Duration, f: F) -> SpawnHandle
+    where
+        F: FnMut(&mut A, &mut A::Context) + 'static,
+    {
+        self.parts.spawn_timer(IntervalFunc::new(dur, f).finish())
+    }
 }
 
 impl<A> Context<A>
@@ -132,7 +201,10 @@ where
 
     #[inline]
     pub fn with_receiver(rx: AddressReceiver<A>) -> Self {
-        let mb = Mailbox::new(rx);
+        Self::with_mailbox(Mailbox::new(rx))
+    }
+
+    fn with_mailbox(mb: Mailbox<A>) -> Self {
         Self {
             parts: ContextParts::new(mb.sender_producer()),
             mb: Some(mb),
@@ -147,8 +219,134 @@ where
         addr
     }
 
+    /// Starts the actor like [`run`](Self::run), but calls
+    /// [`Actor::started`] before returning.
+    ///
+    /// Side effects of `started`, e.g. binding a socket, have therefore
+    /// happened when the address is returned, instead of on the first poll of
+    /// the spawned future. `started` runs inside this call and must not
+    /// block. If the context has outstanding futures added with
+    /// [`prepare`](Self::prepare), the actor is started once they resolve,
+    /// as with `run`.
+    pub fn run_eager(self, act: A) -> Addr<A> {
+        let mut fut = self.into_future(act);
+        fut.start_eagerly();
+        let addr = fut.address();
+        actix_rt::spawn(fut);
+        addr
+    }
+
+    /// Starts an actor which is only constructed once its first message
+    /// arrives.
+    ///
+    /// The address is returned right away, but `f` is not called and the
+    /// actor does not start until a message is sent to it. The message is
+    /// then handled first, right after [`Actor::started`]. If all addresses
+    /// are dropped before any message is sent, the actor is never
+    /// constructed. This keeps large fan-outs of mostly idle actors cheap,
+    /// e.g. one actor per user of a presence system.
+    ///
+    /// Only the actor is lazy, the context has already been created and can
+    /// be configured before this call. [`Actor::create_lazy`] also defers
+    /// the creation of the context, only the mailbox exists up front.
+    pub fn run_lazy<F>(mut self, f: F) -> Addr<A>
+    where
+        F: FnOnce(&mut Self) -> A + 'static,
+    {
+        let addr = self.address();
+        actix_rt::spawn(async move {
+            let mb = self.mb.as_mut().expect("Context is already running");
+            if let Some(first) = poll_fn(|cx| mb.poll_envelope(cx)).await {
+                self.start_lazy(first, f).await;
+            }
+        });
+        addr
+    }
+
+    /// Like [`run_lazy`](Self::run_lazy), but the context is only created
+    /// along with the actor.
+    pub(crate) fn create_lazy<F>(f: F) -> Addr<A>
+    where
+        F: FnOnce(&mut Self) -> A + 'static,
+    {
+        let mut mb = Mailbox::default();
+        let addr = Addr::new(mb.sender_producer().sender());
+        actix_rt::spawn(async move {
+            if let Some(first) = poll_fn(|cx| mb.poll_envelope(cx)).await {
+                Self::with_mailbox(mb).start_lazy(first, f).await;
+            }
+        });
+        addr
+    }
+
+    /// Constructs the actor, its first message is handled right after
+    /// `Actor::started`.
+    fn start_lazy<F>(mut self, mut first: Envelope<A>, f: F) -> ContextFut<A, Self>
+    where
+        F: FnOnce(&mut Self) -> A,
+    {
+        let act = f(&mut self);
+        self.wait(
+            wrap_future(future::ready(()))
+                .map(move |_, act, ctx: &mut Self| first.handle(act, ctx)),
+        );
+        self.into_future(act)
+    }
+
+    /// Starts the actor like [`run`](Self::run), and also returns a handle
+    /// that resolves once the actor has stopped.
+    ///
+    /// The handle resolves to an error if the actor panicked. This allows a
+    /// coordinator to start several actors and await all of them.
+    pub fn run_with_handle(self, act: A) -> (Addr<A>, JoinHandle<()>) {
+        let fut = self.into_future(act);
+        let addr = fut.address();
+        (addr, actix_rt::spawn(fut))
+    }
+
+    /// Turns the context into a future driving `act`, without spawning it.
+    ///
+    /// The future does not need a running [`System`](crate::System) unless
+    /// the actor uses timers or spawns tasks on the runtime. Handler logic
+    /// can therefore be unit tested by polling the future by hand:
+    ///
+    /// ```
+    /// # use std::{future::Future, pin::Pin, task};
+    /// use actix::prelude::*;
+    ///
+    /// struct Add(usize);
+    ///
+    /// impl Message for Add {
+    ///     type Result = ();
+    /// }
+    ///
+    /// struct Sum(usize);
+    ///
+    /// impl Actor for Sum {
+    ///     type Context = Context<Self>;
+    /// }
+    ///
+    /// impl Handler<Add> for Sum {
+    ///     type Result = ();
+    ///
+    ///     fn handle(&mut self, msg: Add, _: &mut Self::Context) {
+    ///         self.0 += msg.0;
+    ///     }
+    /// }
+    ///
+    /// let ctx = Context::new();
+    /// let addr = ctx.address();
+    /// addr.do_send(Add(1));
+    /// addr.do_send(Add(2));
+    ///
+    /// let mut fut = ctx.into_future(Sum(0));
+    /// let waker = futures_util::task::noop_waker();
+    /// let mut cx = task::Context::from_waker(&waker);
+    /// assert!(Pin::new(&mut fut).poll(&mut cx).is_pending());
+    /// assert_eq!(fut.actor().0, 3);
+    /// ```
     pub fn into_future(mut self, act: A) -> ContextFut<A, Self> {
-        let mb = self.mb.take().unwrap();
+        let mb = self.mb.take().expect("Context is already running");
         ContextFut::new(self, act, mb)
     }
 
@@ -160,6 +358,697 @@ where
         self.parts.curr_handle()
     }
 
+    /// Spawns a future into the context and attaches `label` to its handle.
+    ///
+    /// The label is available through [`label_of`](Self::label_of) until the
+    /// future completes or is cancelled, which makes it possible to tell
+    /// which tasks are outstanding, e.g. `subscription:user42`.
+    pub fn spawn_labeled<F>(&mut self, label: impl Into<String>, fut: F) -> SpawnHandle
+    where
+        F: ActorFuture<A, Output = ()> + 'static,
+    {
+        self.parts.spawn_labeled(label.into(), fut)
+    }
+
+    /// Spawns the future returned by `mk`, unless `breaker` is open.
+    ///
+    /// Returns `None` without calling `mk` if the breaker rejects the
+    /// attempt. Otherwise the outcome of the future is recorded with the
+    /// breaker, an `Err` counts as a failure. The error is not passed on,
+    /// handle it in the future if needed. See [`CircuitBreaker`] for the
+    /// state transitions.
+    pub fn spawn_with_breaker<F, Fut, E>(
+        &mut self,
+        mk: F,
+        breaker: &CircuitBreaker,
+    ) -> Option<SpawnHandle>
+    where
+        F: FnOnce() -> Fut,
+        Fut: ActorFuture<A, Output = Result<(), E>> + 'static,
+    {
+        let attempt = breaker.attempt()?;
+        let fut = mk().map(move |res, _, _| attempt.finish(res.is_ok()));
+        Some(self.spawn(fut))
+    }
+
+    /// Starts a child actor whose lifetime is tied to this actor.
+    ///
+    /// The child runs as a future spawned into this context instead of a
+    /// separate task. It is stopped, calling `stopping` and `stopped` on it,
+    /// when this actor stops or restarts, or early when the returned handle
+    /// is cancelled with [`cancel_future`](AsyncContext::cancel_future).
+    /// Whether the child is still running can be checked with
+    /// [`Addr::connected`]. A running child keeps this actor alive, like any
+    /// spawned future.
+    pub fn spawn_child<B>(&mut self, ctx: Context<B>, act: B) -> (SpawnHandle, Addr<B>)
+    where
+        B: Actor<Context = Context<B>>,
+    {
+        let fut = ctx.into_future(act);
+        let addr = fut.address();
+        (self.spawn(wrap_future(fut)), addr)
+    }
+
+    /// Spawns a future that counts toward the limit set with
+    /// [`set_max_concurrent`](Self::set_max_concurrent).
+    ///
+    /// While the limit is reached, the future is queued and starts once an
+    /// earlier limited future completes or is cancelled, in the order the
+    /// futures were spawned. This bounds the concurrency of e.g. outbound
+    /// requests spawned per message. Futures spawned with
+    /// [`spawn`](AsyncContext::spawn) do not count toward the limit.
+    pub fn spawn_limited<F>(&mut self, fut: F) -> SpawnHandle
+    where
+        F: ActorFuture<A, Output = ()> + 'static,
+    {
+        self.parts.spawn_limited(fut)
+    }
+
+    /// Registers the stream `fut` resolves to, once it resolves.
+    ///
+    /// Until then the future counts toward the limit set with
+    /// [`set_max_pending_streams`](Self::set_max_pending_streams), e.g. to
+    /// bound the number of connections being opened at once. Once resolved,
+    /// the stream is added with [`add_stream`](AsyncContext::add_stream) and
+    /// frees its slot, so the next queued future starts while the stream is
+    /// still running. Returns the handle of the pending future, cancelling it
+    /// drops the future before its stream is added.
+    pub fn add_fut_stream<F, S>(&mut self, fut: F) -> SpawnHandle
+    where
+        F: Future<Output = S> + 'static,
+        S: Stream + 'static,
+        A: StreamHandler<S::Item>,
+    {
+        let fut = wrap_future(fut).map(|stream, _, ctx: &mut Self| {
+            ctx.add_stream(stream);
+        });
+        self.parts.spawn_pending_stream(fut)
+    }
+
+    /// Sets the maximum number of futures added with
+    /// [`add_fut_stream`](Self::add_fut_stream) that resolve concurrently.
+    ///
+    /// Further futures are queued and start in the order they were added.
+    /// By default the number is unlimited.
+    pub fn set_max_pending_streams(&mut self, max: usize) {
+        self.parts.set_max_pending_streams(max)
+    }
+
+    /// Creates an additional inbox for messages of type `M`, drained with
+    /// deficit round-robin.
+    ///
+    /// In every round over the weighted inboxes of the context, each inbox
+    /// handles up to `weight` queued messages, so the relative throughput of
+    /// an inbox is bounded by its weight. A round interrupted by
+    /// [`wait`](AsyncContext::wait) resumes with the share left to the
+    /// inbox, rather than starting over. Giving every tenant of a service
+    /// its own inbox keeps a noisy tenant from starving the others. A weight
+    /// of zero is treated as one. The regular mailbox is not affected.
+    ///
+    /// The inbox is closed once all its senders are dropped, or when the
+    /// actor stops or restarts.
+    pub fn weighted_inbox<M>(&mut self, weight: u32) -> InboxSender<M>
+    where
+        M: Message + 'static,
+        A: Handler<M>,
+    {
+        self.labeled_inbox("inbox", weight)
+    }
+
+    /// Creates a weighted inbox like [`weighted_inbox`](Self::weighted_inbox),
+    /// reported under `label` by [`inbox_stats`](Self::inbox_stats).
+    pub fn labeled_inbox<M>(&mut self, label: impl Into<String>, weight: u32) -> InboxSender<M>
+    where
+        M: Message + 'static,
+        A: Handler<M>,
+    {
+        let (tx, mut rx, state) = inbox_channel();
+        self.parts.add_inbox(
+            Inbox::new(
+                weight.max(1),
+                Box::new(move |act, ctx: &mut Self, task| match rx.poll_recv(task) {
+                    Poll::Ready(Some(msg)) => {
+                        ctx.parts.mark_active();
+                        <A as Handler<M>>::handle(act, msg, ctx).handle(ctx, None);
+                        Poll::Ready(Some(()))
+                    }
+                    Poll::Ready(None) => Poll::Ready(None),
+                    Poll::Pending => Poll::Pending,
+                }),
+            ),
+            label.into(),
+            state,
+        );
+        tx
+    }
+
+    /// Returns the queue state of the actor's mailbox followed by its
+    /// weighted inboxes.
+    ///
+    /// This shows which source of messages an actor is behind on, e.g.
+    /// `control: 0 queued, tenant-7: 4210 queued`. Inboxes which were closed
+    /// and drained are eventually dropped from the list.
+    pub fn inbox_stats(&self) -> Vec<InboxStat> {
+        self.parts.inbox_stats()
+    }
+
+    /// Registers a control stream, which is polled before the mailbox and
+    /// before spawned futures and streams.
+    ///
+    /// Items are handled by the actor's `StreamHandler`, like with
+    /// [`add_stream`](AsyncContext::add_stream), so commands such as pause or
+    /// a rate change get their own handler and are never stuck behind a
+    /// busy data stream or a full mailbox. The control stream is dropped when
+    /// the actor restarts.
+    pub fn add_control_stream<S>(&mut self, stream: S)
+    where
+        S: Stream + 'static,
+        A: StreamHandler<S::Item>,
+    {
+        self.parts.add_control(ActorStream::new(stream))
+    }
+
+    /// Splits `io` into a framed stream and sink using `codec`.
+    ///
+    /// Decoded frames are delivered to the actor's `StreamHandler`, while
+    /// frames are written through the returned [`FramedWrite`], whose errors
+    /// go to the actor's `WriteHandler`. Returns the handle of the read
+    /// stream along with the writer, so an actor can manage several framed
+    /// connections side by side.
+    pub fn add_framed<T, U, I>(
+        &mut self,
+        io: T,
+        codec: U,
+    ) -> (SpawnHandle, FramedWrite<I, WriteHalf<T>, U>)
+    where
+        T: AsyncRead + AsyncWrite + 'static,
+        U: Decoder + Encoder<I> + Clone + 'static,
+        <U as Encoder<I>>::Error: 'static,
+        A: StreamHandler<Result<U::Item, <U as Decoder>::Error>>
+            + WriteHandler<<U as Encoder<I>>::Error>,
+    {
+        let (read, write) = tokio::io::split(io);
+        let handle = A::add_stream(FramedRead::new(read, codec.clone()), self);
+        (handle, FramedWrite::new(write, codec, self))
+    }
+
+    /// Reads framed messages from `io` and delivers them to the actor's
+    /// `Handler`, like messages sent to its address.
+    ///
+    /// Frames are decoded with `codec` into messages, which makes the actor
+    /// addressable by another process, e.g. over a unix socket. Responses of
+    /// the handler are dropped. A decode error ends the inbox and is passed
+    /// to [`report_error`](Self::report_error), or logged if there is no
+    /// error stream. Returns the handle of the inbox, which can be used to
+    /// cancel it.
+    pub fn add_codec_inbox<T, U>(&mut self, io: T, codec: U) -> SpawnHandle
+    where
+        T: AsyncRead + 'static,
+        U: Decoder + 'static,
+        U::Item: Message,
+        U::Error: fmt::Debug + 'static,
+        A: Handler<U::Item>,
+    {
+        let inbox = crate::fut::wrap_stream::<_, A>(FramedRead::new(io, codec))
+            .map(|res, _, ctx: &mut Self| match res {
+                Ok(msg) => Some(msg),
+                Err(err) => {
+                    if let Err(err) = ctx.report_error(err) {
+                        error!("Codec inbox: decode error: {:?}", err);
+                    }
+                    None
+                }
+            })
+            .take_while(|msg, _, _| crate::fut::ready(msg.is_some()))
+            .map(|msg, act, ctx| {
+                if let Some(msg) = msg {
+                    Handler::handle(act, msg, ctx).handle(ctx, None);
+                }
+            })
+            .finish();
+        self.spawn(inbox)
+    }
+
+    /// Sets the maximum number of concurrently running futures spawned with
+    /// [`spawn_limited`](Self::spawn_limited).
+    ///
+    /// By default the number is unlimited.
+    pub fn set_max_concurrent(&mut self, max: usize) {
+        self.parts.set_max_concurrent(max)
+    }
+
+    /// Moves the deadline of a future spawned with
+    /// [`spawn_with_ttl`](AsyncContext::spawn_with_ttl) by `additional`.
+    ///
+    /// Returns `false` if the future has already completed or expired, or
+    /// was not spawned with a ttl. This keeps slow but progressing work alive,
+    /// while work that makes no progress still expires.
+    pub fn extend_deadline(&mut self, handle: SpawnHandle, additional: Duration) -> bool {
+        self.parts.extend_deadline(handle, additional)
+    }
+
+    /// Returns the label of a running future spawned with
+    /// [`spawn_labeled`](Self::spawn_labeled).
+    pub fn label_of(&self, handle: SpawnHandle) -> Option<&str> {
+        self.parts.label_of(handle)
+    }
+
+    /// Cancels all running futures spawned with
+    /// [`spawn_labeled`](Self::spawn_labeled) under `label`.
+    ///
+    /// Returns the number of cancelled futures. Their labels are dropped
+    /// right away, so cancelling the same label again returns 0.
+    pub fn cancel_labeled(&mut self, label: &str) -> usize {
+        self.parts.cancel_labeled(label)
+    }
+
+    /// Replaces all spawned futures and streams with the ones installed by
+    /// `setup`.
+    ///
+    /// Every running item is cancelled, then `setup` is called right away to
+    /// spawn the new set. Cancellation goes by handle, so items spawned by
+    /// `setup` are not affected. This suits role changes, e.g. from leader to
+    /// follower, where the old background work must not outlive the old role.
+    /// [`add_ordered_future`](Self::add_ordered_future) futures, weighted
+    /// inboxes and shutdown memberships are part of the context rather than
+    /// of the role, they are kept.
+    ///
+    /// Sink writers are cancelled like any other item, so items they still
+    /// buffer are dropped without being flushed. Use
+    /// [`transition_flushing`](Self::transition_flushing) if sinks have to
+    /// finish first.
+    pub fn transition<F>(&mut self, setup: F)
+    where
+        F: FnOnce(&mut Self),
+    {
+        self.parts.cancel_all_except(&[]);
+        setup(self);
+    }
+
+    /// Like [`transition`](Self::transition), but lets the items in `sinks`
+    /// complete before `setup` is called.
+    ///
+    /// All other items are cancelled right away. Sinks are expected to be
+    /// closed already, e.g. with [`SinkWrite::close`](crate::io::SinkWrite::close),
+    /// so they complete once their buffer is flushed. Sinks that have not
+    /// completed within `timeout` are cancelled and `setup` is called anyway.
+    pub fn transition_flushing<F>(&mut self, sinks: &[SpawnHandle], timeout: Duration, setup: F)
+    where
+        F: FnOnce(&mut Self) + 'static,
+    {
+        self.parts.cancel_all_except(sinks);
+
+        let setup = Rc::new(RefCell::new(Some(setup)));
+        let remaining = Rc::new(Cell::new(0usize));
+        let timer = Rc::new(Cell::new(SpawnHandle::default()));
+        for handle in sinks {
+            let flushing = {
+                let (setup, remaining, timer) =
+                    (Rc::clone(&setup), Rc::clone(&remaining), Rc::clone(&timer));
+                self.on_complete(*handle, move |_, ctx| {
+                    remaining.set(remaining.get() - 1);
+                    if remaining.get() == 0 {
+                        ctx.cancel_future(timer.get());
+                        if let Some(setup) = setup.borrow_mut().take() {
+                            setup(ctx);
+                        }
+                    }
+                })
+            };
+            if flushing {
+                remaining.set(remaining.get() + 1);
+            }
+        }
+
+        if remaining.get() == 0 {
+            if let Some(setup) = setup.borrow_mut().take() {
+                setup(self);
+            }
+            return;
+        }
+        let sinks = sinks.to_vec();
+        let handle = self.run_later(timeout, move |_, ctx| {
+            if let Some(setup) = setup.borrow_mut().take() {
+                for handle in sinks {
+                    ctx.cancel_future(handle);
+                }
+                setup(ctx);
+            }
+        });
+        timer.set(handle);
+    }
+
+    /// Calls `cb` once the spawned future or stream identified by `handle`
+    /// completes.
+    ///
+    /// Unlike chaining the work onto the future itself, the callback can be
+    /// registered by other code than the one that spawned the future, e.g.
+    /// a handler reacting to a job spawned elsewhere. The callback is not
+    /// called if the future is cancelled. Registering another callback for
+    /// the same handle replaces the previous one.
+    ///
+    /// Returns `false` if there is no such future, because it already
+    /// completed or was never spawned with [`spawn`](AsyncContext::spawn).
+    pub fn on_complete<F>(&mut self, handle: SpawnHandle, cb: F) -> bool
+    where
+        F: FnOnce(&mut A, &mut Self) + 'static,
+    {
+        self.parts.on_complete(handle, Box::new(cb))
+    }
+
+    /// Returns the waker of the task that drives this context.
+    ///
+    /// Waking it re-polls the context, including all spawned futures. This
+    /// bridges non-futures event sources into the actor without a channel: an
+    /// external callback records its event and calls `wake()`, possibly from
+    /// another thread, and a future spawned into the context picks it up.
+    ///
+    /// Returns `None` until the context has been polled for the first time.
+    pub fn waker(&self) -> Option<Waker> {
+        self.parts.waker()
+    }
+
+    /// Suspends the whole actor until the returned handle is resumed.
+    ///
+    /// While suspended, the context polls neither the mailbox nor any spawned
+    /// futures or streams; wakeups are absorbed. Once
+    /// [`ResumeHandle::resume`] is called, from any thread, the context
+    /// re-polls everything, so no event that fired in the meantime is lost.
+    ///
+    /// The suspension takes effect once the handler or item calling
+    /// `suspend` returns to the context, e.g. a stream which suspends the
+    /// actor in its handler may still deliver the items it has ready. Calling
+    /// `suspend` on an already suspended context returns a handle to the same
+    /// suspension.
+    pub fn suspend(&mut self) -> ResumeHandle {
+        self.parts.suspend()
+    }
+
+    /// Calls `f` with the metadata of every spawned future and stream that
+    /// has not completed yet, in the order they were spawned.
+    ///
+    /// This is a read-only view for extensions that implement custom
+    /// introspection or scheduling on top of the context. The age of an item
+    /// is only tracked while an [`ItemScheduler`] is installed and is zero
+    /// otherwise.
+    pub fn for_each_item<F>(&self, f: F)
+    where
+        F: FnMut(&ItemInfo<'_>),
+    {
+        self.parts.for_each_item(f)
+    }
+
+    /// Returns a summary of the work the context still has outstanding.
+    ///
+    /// Counts spawned futures and streams, wait futures and queued messages,
+    /// and lists the labels of outstanding
+    /// [`spawn_labeled`](Self::spawn_labeled) futures. This is read-only and
+    /// meant for diagnostics, e.g. finding out why an actor does not finish.
+    pub fn pending(&self) -> PendingItems {
+        self.parts.pending()
+    }
+
+    /// Records the type name of every future and stream spawned from now on.
+    ///
+    /// The names are listed by [`item_typenames`](Self::item_typenames), and
+    /// tell which futures keep an actor busy. Recording only happens in debug
+    /// builds; in release builds this method has no effect.
+    pub fn set_debug_typenames(&mut self, enabled: bool) {
+        self.parts.set_debug_typenames(enabled)
+    }
+
+    /// Returns the type names of outstanding spawned futures and streams, in
+    /// the order they were spawned.
+    ///
+    /// Only items spawned while [`set_debug_typenames`] was enabled are
+    /// listed.
+    ///
+    /// [`set_debug_typenames`]: Self::set_debug_typenames
+    pub fn item_typenames(&self) -> Vec<&'static str> {
+        self.parts.item_typenames()
+    }
+
+    /// Stores a context-local value, returning the previous value of the
+    /// same type.
+    ///
+    /// Values are keyed by type, like `http::Extensions`, and are reachable
+    /// from handlers and from spawned actor futures through their context
+    /// argument. This suits ambient data such as a request ID that should not
+    /// be part of the actor itself.
+    ///
+    /// ```
+    /// # use actix::prelude::*;
+    /// struct RequestId(u64);
+    ///
+    /// struct MyActor;
+    ///
+    /// impl Actor for MyActor {
+    ///     type Context = Context<Self>;
+    ///
+    ///     fn started(&mut self, ctx: &mut Self::Context) {
+    ///         ctx.insert(RequestId(42));
+    ///         assert_eq!(ctx.get::<RequestId>().map(|id| id.0), Some(42));
+    /// #       System::current().stop();
+    ///     }
+    /// }
+    /// # fn main() {
+    /// #    let sys = System::new();
+    /// #    let addr = sys.block_on(async { MyActor.start() });
+    /// #    sys.run().unwrap();
+    /// # }
+    /// ```
+    pub fn insert<V: 'static>(&mut self, val: V) -> Option<V> {
+        self.parts.insert(val)
+    }
+
+    /// Returns a reference to the context-local value of type `V`.
+    pub fn get<V: 'static>(&self) -> Option<&V> {
+        self.parts.get()
+    }
+
+    /// Returns a mutable reference to the context-local value of type `V`.
+    pub fn get_mut<V: 'static>(&mut self) -> Option<&mut V> {
+        self.parts.get_mut()
+    }
+
+    /// Removes and returns the context-local value of type `V`.
+    pub fn remove<V: 'static>(&mut self) -> Option<V> {
+        self.parts.remove()
+    }
+
+    /// Resolves a future before the actor is started.
+    ///
+    /// All prepared futures are polled concurrently before `Actor::started`
+    /// is called, and the output of each is stored as a context-local value,
+    /// available via [`get`](Context::get) from `started` on. Messages sent in
+    /// the meantime stay queued in the mailbox.
+    ///
+    /// If any prepared future fails, the error is logged and the actor is
+    /// dropped without calling `started`, `stopping` or `stopped`. Futures
+    /// prepared after the actor has started are never polled.
+    ///
+    /// ```
+    /// # use actix::prelude::*;
+    /// struct Connection(u16);
+    ///
+    /// struct Client;
+    ///
+    /// impl Actor for Client {
+    ///     type Context = Context<Self>;
+    ///
+    ///     fn started(&mut self, ctx: &mut Self::Context) {
+    ///         assert_eq!(ctx.get::<Connection>().unwrap().0, 8080);
+    ///         System::current().stop();
+    ///     }
+    /// }
+    ///
+    /// # fn main() {
+    /// let sys = System::new();
+    /// sys.block_on(async {
+    ///     Client::create(|ctx| {
+    ///         ctx.prepare(async { Ok::<_, ()>(Connection(8080)) });
+    ///         Client
+    ///     });
+    /// });
+    /// sys.run().unwrap();
+    /// # }
+    /// ```
+    pub fn prepare<F, T, E>(&mut self, fut: F)
+    where
+        F: Future<Output = Result<T, E>> + 'static,
+        T: 'static,
+        E: fmt::Debug,
+    {
+        self.parts.prepare(fut)
+    }
+
+    /// Returns a stream of non-fatal errors of type `E`, reported with
+    /// [`report_error`](Self::report_error).
+    ///
+    /// This is a side channel for errors the actor recovers from, like a
+    /// reconnect, so that monitors can observe them while the actor keeps
+    /// running. The stream ends when the actor stops. Calling this method
+    /// again replaces the previous stream.
+    pub fn error_stream<E: 'static>(&mut self) -> OutputStream<E> {
+        let (tx, rx) = output_channel();
+        self.parts.insert(ErrorSink(tx));
+        rx
+    }
+
+    /// Reports a non-fatal error to the stream returned by
+    /// [`error_stream`](Self::error_stream).
+    ///
+    /// Returns the error back if there is no such stream or it was dropped.
+    pub fn report_error<E: 'static>(&mut self, err: E) -> Result<(), E> {
+        #[cfg(feature = "metrics")]
+        self.parts.incr_metric("errors", 1);
+        match self.parts.get::<ErrorSink<E>>() {
+            Some(sink) => sink.0.send(err),
+            None => Err(err),
+        }
+    }
+
+    /// Returns a stream of intermediate results of type `T`, emitted with
+    /// [`emit`](Self::emit).
+    ///
+    /// This lets a long running actor report progress or partial results to
+    /// interested parties while it keeps running. The stream ends when the
+    /// actor stops. Calling this method again replaces the previous stream.
+    ///
+    /// ```
+    /// # use actix::prelude::*;
+    /// use futures_util::stream::StreamExt;
+    ///
+    /// struct Progress(usize);
+    ///
+    /// struct Job;
+    ///
+    /// impl Actor for Job {
+    ///     type Context = Context<Self>;
+    ///
+    ///     fn started(&mut self, ctx: &mut Self::Context) {
+    ///         for done in 1..=3 {
+    ///             let _ = ctx.emit(Progress(done));
+    ///         }
+    ///         ctx.stop();
+    ///     }
+    /// }
+    ///
+    /// # #[actix::main]
+    /// # async fn main() {
+    /// let mut results = None;
+    /// Job::create(|ctx| {
+    ///     results = Some(ctx.results::<Progress>());
+    ///     Job
+    /// });
+    /// let progress = results.unwrap().map(|p| p.0).collect::<Vec<_>>().await;
+    /// assert_eq!(progress, vec![1, 2, 3]);
+    /// # }
+    /// ```
+    pub fn results<T: 'static>(&mut self) -> OutputStream<T> {
+        let (tx, rx) = output_channel();
+        self.parts.insert(ResultSink(tx));
+        rx
+    }
+
+    /// Emits an intermediate result to the stream returned by
+    /// [`results`](Self::results).
+    ///
+    /// Returns the item back if there is no such stream or it was dropped.
+    pub fn emit<T: 'static>(&mut self, item: T) -> Result<(), T> {
+        match self.parts.get::<ResultSink<T>>() {
+            Some(sink) => sink.0.send(item),
+            None => Err(item),
+        }
+    }
+
+    /// Stashes a message that the actor can not handle yet.
+    ///
+    /// The message is kept until [`unstash`](Self::unstash) is called, after
+    /// which it is delivered to `Handler<M>` again, ahead of new mailbox
+    /// messages. This implements the stash pattern without keeping a buffer
+    /// in the actor, e.g. holding requests until a connection is established.
+    ///
+    /// The result of the redelivered handler is discarded. A message that was
+    /// sent with [`Addr::send`](crate::Addr::send) has already been answered
+    /// by the handler that stashed it.
+    pub fn stash<M>(&mut self, msg: M)
+    where
+        M: Message + 'static,
+        A: Handler<M>,
+    {
+        fn deliver<A, M>(msg: Box<dyn Any>, act: &mut A, ctx: &mut Context<A>)
+        where
+            A: Actor<Context = Context<A>> + Handler<M>,
+            M: Message + 'static,
+        {
+            let msg = *msg.downcast::<M>().unwrap();
+            <A as Handler<M>>::handle(act, msg, ctx).handle(ctx, None)
+        }
+
+        self.parts.stash(Stashed {
+            msg: Box::new(msg),
+            deliver: deliver::<A, M>,
+            at: Instant::now(),
+            aged: false,
+        })
+    }
+
+    /// Limits the stash to `limit` messages.
+    ///
+    /// When a message is stashed while the stash is full, `policy` decides
+    /// which message is dropped. The dropped message is not lost silently,
+    /// it is passed to `on_overflow` once the current handler returns.
+    /// Dropped messages of other types than `M` are discarded. By default
+    /// the stash is unbounded.
+    pub fn set_stash_limit<M, F>(
+        &mut self,
+        limit: usize,
+        policy: StashOverflow,
+        mut on_overflow: F,
+    ) where
+        M: 'static,
+        F: FnMut(&mut A, M, &mut Self) + 'static,
+    {
+        self.parts.set_stash_limit(
+            limit,
+            policy,
+            Box::new(move |act, msg, ctx| {
+                if let Ok(msg) = msg.downcast::<M>() {
+                    on_overflow(act, *msg, ctx)
+                }
+            }),
+        )
+    }
+
+    /// Calls `on_aged` for every message that stayed in the stash for
+    /// longer than `dur`.
+    ///
+    /// This surfaces starvation of stashed messages, e.g. when the actor
+    /// keeps stashing while new messages arrive. Each message is reported
+    /// once, `on_aged` gets it as `&dyn Any` to downcast to its original
+    /// type. The callback may [`unstash`](Self::unstash) to force processing,
+    /// returning `false` drops the message from the stash.
+    pub fn set_stash_age_limit<F>(&mut self, dur: Duration, on_aged: F)
+    where
+        F: FnMut(&mut A, &dyn Any, &mut Self) -> bool + 'static,
+    {
+        self.parts.set_stash_age_limit(dur, Box::new(on_aged))
+    }
+
+    /// Redelivers all stashed messages, in the order they were stashed.
+    ///
+    /// Messages stashed again while being redelivered are kept for the next
+    /// call to `unstash`.
+    pub fn unstash(&mut self) {
+        self.parts.unstash()
+    }
+
+    /// Returns the number of stashed messages.
+    pub fn stashed(&self) -> usize {
+        self.parts.stashed()
+    }
+
     /// Sets the mailbox capacity.
     ///
     /// The default mailbox capacity is 16 messages.
@@ -186,10 +1075,414 @@ where
         self.parts.set_mailbox_capacity(cap)
     }
 
+    /// Lets the mailbox capacity adapt to the backlog, between `min` and
+    /// `max`.
+    ///
+    /// A fixed capacity is hard to tune for bursty workloads. With adaptive
+    /// capacity, every time the mailbox is polled the capacity doubles
+    /// towards `max` while at most a quarter of it is used, so bursts are
+    /// absorbed without blocking senders. It halves towards `min` while
+    /// three quarters or more are used, putting backpressure on senders when
+    /// the actor falls behind. Both bounds are at least one.
+    pub fn set_adaptive_capacity(&mut self, min: usize, max: usize) {
+        self.parts.set_adaptive_capacity(min, max)
+    }
+
+    /// Makes spawned futures be polled in the order they were spawned.
+    ///
+    /// By default a completed future is swap-removed from the context, which
+    /// changes the polling order of the remaining ones. With ordered mode
+    /// enabled futures are removed in place instead, at the cost of shifting
+    /// the futures after it. A future that keeps scheduling
+    /// [`wait`](AsyncContext::wait) futures is also no longer moved to the
+    /// back of the queue, so it can delay the futures spawned after it.
+    pub fn set_preserve_item_order(&mut self, ordered: bool) {
+        self.parts.set_preserve_item_order(ordered)
+    }
+
+    /// Waits until all futures and streams spawned into the context have
+    /// completed, then calls `f`.
+    ///
+    /// Mailbox processing is paused meanwhile, so `f` observes a stable
+    /// point with no half-done background work, e.g. right before taking a
+    /// state snapshot. Unlike stopping, the actor keeps running afterwards
+    /// and message processing resumes once `f` returns.
+    ///
+    /// Intervals and other never-ending streams keep the context busy, they
+    /// have to be cancelled for `f` to be called.
+    pub fn quiesce<F>(&mut self, f: F)
+    where
+        F: FnOnce(&mut A, &mut Self) + 'static,
+    {
+        self.parts.quiesce(f)
+    }
+
+    /// Stops the actor once all spawned futures and streams have completed,
+    /// waiting at most `timeout`.
+    ///
+    /// Mailbox processing is paused while draining, as with
+    /// [`quiesce`](Self::quiesce). If the futures have not completed when
+    /// `timeout` elapses, the remaining ones are dropped and the actor is
+    /// stopped anyway, so a single stuck subscription cannot block shutdown
+    /// forever. [`SinkWrite`](crate::io::SinkWrite)s are polled once more
+    /// before they are dropped, to flush what they buffered.
+    pub fn drain_and_stop(&mut self, timeout: Duration) {
+        self.parts.drain_and_stop(timeout)
+    }
+
+    /// Joins `phase` of a shutdown coordinator.
+    ///
+    /// When [`ShutdownCoordinator::shutdown`] reaches `phase`, the actor is
+    /// stopped with [`drain_and_stop`](Self::drain_and_stop) using `timeout`.
+    /// The next phase starts once the actor has stopped. Joining does not
+    /// keep the actor alive, and the membership survives supervisor restarts
+    /// and [`transition`](Self::transition)s.
+    pub fn join_shutdown(
+        &mut self,
+        coordinator: &ShutdownCoordinator,
+        phase: u8,
+        timeout: Duration,
+    ) {
+        self.parts.join_shutdown(coordinator.join(phase, timeout));
+    }
+
+    /// Returns the number of timers the context currently has armed.
+    ///
+    /// This counts
+    ///
+    /// - pending [`run_later`](AsyncContext::run_later),
+    ///   [`run_interval`](AsyncContext::run_interval) and
+    ///   [`notify_later`](AsyncContext::notify_later) timers,
+    /// - deadlines of running [`spawn_with_ttl`](AsyncContext::spawn_with_ttl)
+    ///   futures,
+    /// - keepalive timers of open [`SinkWrite`](crate::io::SinkWrite)s,
+    /// - the idle timeout, [`expect_within`](Self::expect_within),
+    ///   [`drain_and_stop`](Self::drain_and_stop), wakeup coalescing and
+    ///   mailbox rate limit timers, while their deadline has not passed,
+    /// - the stash age timer, while messages are stashed.
+    ///
+    /// Timers of futures spawned by the actor itself, e.g. a `sleep` passed
+    /// to `spawn`, are not visible to the context. An actor can check the
+    /// count to shed timer heavy work, e.g. stop adding per-request
+    /// timeouts, before the timer wheel grows without bound.
+    pub fn timer_count(&self) -> usize {
+        self.parts.timer_count()
+    }
+
+    /// Registers a callback which is called each time the number of futures
+    /// and streams spawned into the context grows above `threshold`.
+    ///
+    /// The count is checked at the end of every poll pass. The callback fires
+    /// once per upward crossing and is armed again after the count drops back
+    /// to `threshold`, which makes it usable as an early warning for
+    /// subscriptions that are never cancelled.
+    pub fn on_items_high_water<F>(&mut self, threshold: usize, cb: F)
+    where
+        F: FnMut(usize) + 'static,
+    {
+        self.parts.on_items_high_water(threshold, cb)
+    }
+
+    /// Registers a future whose output is delivered to the actor's
+    /// [`Handler`] in the order the futures were added.
+    ///
+    /// Outputs of futures added this way are never reordered: if a later
+    /// future completes first, its message is held until all earlier ones
+    /// have been handled. This gives sequential consistency for ordered
+    /// operations, e.g. numbered writes, at the cost of head-of-line
+    /// blocking, a slow future delays the delivery of every future added
+    /// after it.
+    pub fn add_ordered_future<F>(&mut self, fut: F)
+    where
+        F: Future + 'static,
+        F::Output: Message,
+        A: Handler<F::Output>,
+    {
+        if self.state() == ActorState::Stopped {
+            error!("Context::add_ordered_future called for stopped actor.");
+            return;
+        }
+
+        let fut = async move {
+            let msg = fut.await;
+            let f: Callback<A> = Box::new(move |act, ctx| {
+                let fut = Handler::handle(act, msg, ctx);
+                fut.handle(ctx, None);
+            });
+            f
+        };
+
+        let queue = self.parts.ordered_futures();
+        let idle = queue.is_empty();
+        queue.push_back((Box::pin(fut), None));
+        if idle {
+            self.parts.spawn_internal(ActorOrderedFuturesItem);
+        }
+    }
+
+    /// Sets the priority of mailbox messages relative to spawned futures and
+    /// streams.
+    ///
+    /// With [`MailboxPriority::High`] the mailbox is polled after every
+    /// spawned item, so rare control messages are handled promptly even
+    /// while streams deliver a firehose of data. With
+    /// [`MailboxPriority::Low`] spawned items are processed first.
+    pub fn set_mailbox_priority(&mut self, priority: MailboxPriority) {
+        self.parts.set_mailbox_priority(priority)
+    }
+
+    /// Limits the number of mailbox messages handled per poll of the actor.
+    ///
+    /// Once `budget` messages were handled the actor yields to the executor
+    /// and continues with the remaining messages on its next poll. It can be
+    /// changed at any time, e.g. from a handler that detects a burst.
+    pub fn set_poll_budget(&mut self, budget: usize) {
+        self.parts.set_poll_budget(budget)
+    }
+
+    /// Enables or disables fair polling.
+    ///
+    /// With fair polling a single mailbox message is handled between every
+    /// spawned item, so neither messages nor streams can starve each other.
+    /// This overrides [`set_mailbox_priority`](Self::set_mailbox_priority)
+    /// while enabled and can be toggled at any time.
+    pub fn set_fair_polling(&mut self, fair: bool) {
+        self.parts.set_fair_polling(fair)
+    }
+
+    /// Calls `f` with every mailbox message, right before it is handled.
+    ///
+    /// This is a non-intrusive tap for logging or metrics: messages are not
+    /// altered, and can be inspected by downcasting them to their concrete
+    /// type. Messages queued with [`notify`](AsyncContext::notify) or added
+    /// as streams do not pass through the mailbox and are not observed.
+    ///
+    /// ```
+    /// # use actix::prelude::*;
+    /// struct Ping;
+    ///
+    /// impl Message for Ping {
+    ///     type Result = ();
+    /// }
+    ///
+    /// struct MyActor;
+    ///
+    /// impl Actor for MyActor {
+    ///     type Context = Context<Self>;
+    ///
+    ///     fn started(&mut self, ctx: &mut Self::Context) {
+    ///         ctx.observe_messages(|msg| {
+    ///             if msg.is::<Ping>() {
+    ///                 println!("got ping");
+    ///             }
+    ///         });
+    ///     }
+    /// }
+    /// ```
+    pub fn observe_messages<F>(&mut self, f: F)
+    where
+        F: FnMut(&dyn Any) + 'static,
+    {
+        self.parts.observe_messages(Box::new(f))
+    }
+
+    /// Sets a callback which is called after every mutation of the actor
+    /// made with [`mutate_state`](Self::mutate_state).
+    ///
+    /// Direct mutations through `&mut self` in handlers bypass the callback,
+    /// only mutations routed through `mutate_state` trigger it.
+    pub fn on_state_change<F>(&mut self, f: F)
+    where
+        F: FnMut(&A) + 'static,
+    {
+        self.parts.on_state_change(Box::new(f))
+    }
+
+    /// Applies `f` to the actor, then calls the callback set with
+    /// [`on_state_change`](Self::on_state_change).
+    ///
+    /// ```
+    /// # use actix::prelude::*;
+    /// struct Counter(usize);
+    ///
+    /// impl Actor for Counter {
+    ///     type Context = Context<Self>;
+    ///
+    ///     fn started(&mut self, ctx: &mut Self::Context) {
+    ///         ctx.on_state_change(|act| println!("count is {}", act.0));
+    ///         ctx.mutate_state(self, |act| act.0 += 1);
+    ///     }
+    /// }
+    /// ```
+    pub fn mutate_state<F, R>(&mut self, act: &mut A, f: F) -> R
+    where
+        F: FnOnce(&mut A) -> R,
+    {
+        self.parts.mutate_state(act, f)
+    }
+
+    /// Limits the mailbox to `max` handled messages per `per` interval.
+    ///
+    /// Messages beyond the limit stay queued until the next interval starts,
+    /// and senders see backpressure once the mailbox is full. Spawned futures
+    /// and streams are not throttled. This suits actors calling a rate
+    /// limited API, without a hand-rolled throttling stream.
+    pub fn set_mailbox_rate_limit(&mut self, max: usize, per: Duration) {
+        self.parts.set_mailbox_rate_limit(max, per)
+    }
+
+    /// Logs the start and the duration of lifecycle calls and of every
+    /// handled message at debug level, under `name`.
+    ///
+    /// This makes the per message latency of an actor visible without
+    /// instrumenting each handler. Only available with the `trace` feature,
+    /// so builds without it pay nothing.
+    #[cfg(feature = "trace")]
+    pub fn set_trace(&mut self, name: &'static str) {
+        self.parts.set_trace(name)
+    }
+
+    /// Pushes counters and gauges of the context to `sink`, see
+    /// [`MetricsSink`] for the reported metrics.
+    ///
+    /// This is the push based counterpart of accessors like
+    /// [`pending`](Self::pending), the application does not have to poll the
+    /// actor. Only available with the `metrics` feature.
+    #[cfg(feature = "metrics")]
+    pub fn set_metrics_sink<S: MetricsSink + 'static>(&mut self, sink: S) {
+        self.parts.set_metrics_sink(Box::new(sink))
+    }
+
+    /// Marks the actor as one that is meant to run for the lifetime of the
+    /// program, like a daemon.
+    ///
+    /// An actor already keeps running while it has an address or spawned
+    /// futures, so nothing has to be implemented for that. Marking it makes
+    /// the intent explicit and catches mistakes: if the actor runs out of
+    /// both, e.g. because its last address was dropped by accident, an error
+    /// is logged before it stops as usual. Stopping it explicitly, e.g. with
+    /// [`stop`](ActorContext::stop), does not log anything.
+    pub fn set_perpetual(&mut self) {
+        self.parts.set_perpetual()
+    }
+
+    /// Stops the actor once it has been idle for `dur`.
+    ///
+    /// The actor is idle while it handles no messages and has no running
+    /// spawned futures or streams. The timer restarts after every handled
+    /// message and completed future, so this works like a keep-alive
+    /// timeout of a connection. Open weighted inboxes do not keep the actor
+    /// busy, only their messages count. `Actor::stopping` is called as usual
+    /// and can keep the actor running, the timer then starts over.
+    pub fn set_idle_timeout(&mut self, dur: Duration) {
+        self.parts.set_idle_timeout(dur)
+    }
+
+    /// Calls `on_timeout` unless a mailbox message is handled within `dur`.
+    ///
+    /// The timer restarts after every handled message, unlike
+    /// [`set_idle_timeout`](Self::set_idle_timeout) spawned futures and
+    /// streams do not count as activity. This fits protocols where the peer
+    /// must keep sending, e.g. heartbeats, and missing them means it is gone.
+    /// The timeout fires once, call this method again to keep expecting
+    /// messages. Calling it again also replaces a pending timeout.
+    pub fn expect_within<F>(&mut self, dur: Duration, on_timeout: F)
+    where
+        F: FnOnce(&mut A, &mut Self) + 'static,
+    {
+        self.parts.expect_within(dur, Box::new(on_timeout))
+    }
+
+    /// Runs at most one poll pass of the actor per `window`.
+    ///
+    /// By default every wakeup of a message, spawned future or stream
+    /// re-polls all the items of the context. With many active items this
+    /// leads to a lot of passes doing little work. With coalescing, wakeups
+    /// arriving within `window` of the previous pass are served together by
+    /// a single pass once the window ends.
+    ///
+    /// This trades latency for throughput: every event may be handled up to
+    /// `window` later than it would be otherwise, so keep the window short,
+    /// typically in the order of a millisecond.
+    pub fn coalesce_wakeups(&mut self, window: Duration) {
+        self.parts.coalesce_wakeups(window)
+    }
+
+    /// Starts measuring how long handling mailbox messages takes.
+    ///
+    /// The measured time covers the `Handler::handle` call, futures returned
+    /// by a handler and run afterwards are not included. Stats collected so
+    /// far are reset. Contexts that do not call this method pay nothing.
+    pub fn track_latency(&mut self) {
+        self.parts.track_latency()
+    }
+
+    /// Returns handling time percentiles measured since
+    /// [`track_latency`](Self::track_latency), or `None` if it was not
+    /// called.
+    pub fn latency_stats(&self) -> Option<LatencyStats> {
+        self.parts.latency_stats()
+    }
+
+    /// Calls `on_stall` when the actor was not polled for `timeout`.
+    ///
+    /// The watchdog runs as a separate task on the same arbiter and checks
+    /// the time of the last poll of the context. An actor that is not polled
+    /// for a long time either waits for events that never come or is wedged,
+    /// which makes this useful for liveness monitoring. `on_stall` fires
+    /// once per stall, the watchdog is re-armed by the next poll. It stops
+    /// with the context. Calling this method again replaces the watchdog.
+    pub fn set_watchdog<F>(&mut self, timeout: Duration, on_stall: F)
+    where
+        F: FnMut() + 'static,
+    {
+        self.parts.set_watchdog(timeout, on_stall)
+    }
+
+    /// Sets a scheduler which decides the order in which spawned futures and
+    /// streams are polled.
+    ///
+    /// Before every pass over the spawned items, the scheduler assigns each
+    /// item a priority from its handle, label and age. This allows e.g.
+    /// deadline-aware polling of outbound requests. Use [`OldestFirst`] or
+    /// [`NewestFirst`] for simple orderings. Ages are measured from spawn
+    /// time only for items spawned after the scheduler is set.
+    ///
+    /// [`OldestFirst`]: crate::OldestFirst
+    /// [`NewestFirst`]: crate::NewestFirst
+    pub fn set_item_scheduler<S>(&mut self, scheduler: S)
+    where
+        S: ItemScheduler + 'static,
+    {
+        self.parts.set_item_scheduler(Box::new(scheduler))
+    }
+
     /// Returns whether any addresses are still connected.
     pub fn connected(&self) -> bool {
         self.parts.connected()
     }
+
+    /// Keeps the spawned future identified by `handle` running when the
+    /// actor is restarted by its [`Supervisor`](crate::Supervisor).
+    ///
+    /// By default a restart drops every future and stream registered with
+    /// the context. Preserved items survive the restart, so long-lived
+    /// subscriptions do not have to be re-established in `started`.
+    /// [`Supervised::restarting`](crate::Supervised::restarting) is a good
+    /// place to record that they are still active.
+    pub fn preserve_on_restart(&mut self, handle: SpawnHandle) {
+        self.parts.preserve_on_restart(handle)
+    }
+}
+
+impl<A> Default for Context<A>
+where
+    A: Actor<Context = Self>,
+{
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl<A> AsyncContextParts<A> for Context<A>
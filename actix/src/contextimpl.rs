@@ -1,19 +1,33 @@
+use std::any::{Any, TypeId};
+use std::cell::{Cell, RefCell};
+use std::cmp::Reverse;
+use std::collections::{HashMap, VecDeque};
 use std::fmt;
 use std::future::Future;
 use std::pin::Pin;
-use std::task::{Context, Poll};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
 
 use bitflags::bitflags;
-use futures_core::ready;
+use futures_core::{ready, task::__internal::AtomicWaker};
+use log::error;
 use smallvec::SmallVec;
 
 use crate::actor::{
     Actor, ActorContext, ActorState, AsyncContext, Running, SpawnHandle, Supervised,
 };
 use crate::address::{Addr, AddressSenderProducer};
-use crate::contextitems::ActorWaitItem;
+use crate::clock::{sleep, sleep_until, Instant, Sleep};
+use crate::contextitems::{
+    ActorLimitedItem, ActorTimerItem, ActorTtlItem, ActorWaitItem, ActorWeightedInboxes, Inbox,
+    Inboxes, Limiter,
+};
 use crate::fut::ActorFuture;
-use crate::mailbox::Mailbox;
+use crate::mailbox::{Mailbox, Observer};
+use crate::utils::{InboxState, ShutdownListener};
 
 bitflags! {
     /// internal context state
@@ -28,6 +42,382 @@ bitflags! {
 
 type Item<A> = (SpawnHandle, Pin<Box<dyn ActorFuture<A, Output = ()>>>);
 
+pub(crate) type Callback<A> = Box<dyn FnOnce(&mut A, &mut <A as Actor>::Context)>;
+
+pub(crate) type OrderedItem<A> = (
+    Pin<Box<dyn Future<Output = Callback<A>>>>,
+    Option<Callback<A>>,
+);
+
+type Control<A> = Pin<Box<dyn ActorFuture<A, Output = ()>>>;
+
+type StateCallback<A> = Box<dyn FnMut(&A)>;
+
+type AgedCallback<A> = Box<dyn FnMut(&mut A, &dyn Any, &mut <A as Actor>::Context) -> bool>;
+type OverflowCallback<A> = Box<dyn FnMut(&mut A, Box<dyn Any>, &mut <A as Actor>::Context)>;
+
+type Prepared = Pin<Box<dyn Future<Output = Option<(TypeId, Box<dyn Any>)>>>>;
+
+struct HighWater {
+    threshold: usize,
+    exceeded: bool,
+    cb: Box<dyn FnMut(usize)>,
+}
+
+/// Stops the context once it has been idle for a while.
+struct IdleTimeout {
+    dur: Duration,
+    // a message was handled or an item completed since the last check
+    active: bool,
+    timer: Pin<Box<Sleep>>,
+}
+
+impl IdleTimeout {
+    /// Returns `true` if the timeout elapsed. The context is not idle while
+    /// it is `busy` with items, the timer restarts after every activity.
+    fn elapsed(&mut self, busy: bool, cx: &mut Context<'_>) -> bool {
+        if busy || self.active {
+            self.active = false;
+            self.timer.as_mut().reset(Instant::now() + self.dur);
+        }
+        !busy && self.timer.as_mut().poll(cx).is_ready()
+    }
+}
+
+/// Calls back when no mailbox message was handled for a while.
+struct Expect<A: Actor> {
+    dur: Duration,
+    timer: Pin<Box<Sleep>>,
+    on_timeout: Callback<A>,
+}
+
+/// Reports messages that stayed in the stash for too long.
+struct StashAge<A: Actor> {
+    dur: Duration,
+    // deadline of the oldest message not reported yet
+    timer: Pin<Box<Sleep>>,
+    on_aged: AgedCallback<A>,
+}
+
+/// Runs at most one poll pass per window.
+struct Coalesce {
+    window: Duration,
+    // end of the last pass
+    last: Option<Instant>,
+    // armed by a wakeup within the window, deadline of the next pass
+    timer: Pin<Box<Sleep>>,
+}
+
+/// Fixed window limit on the number of handled mailbox messages.
+struct RateLimit {
+    max: usize,
+    per: Duration,
+    window: Instant,
+    used: usize,
+    timer: Option<Pin<Box<Sleep>>>,
+}
+
+impl RateLimit {
+    /// Number of messages that can be handled now. Registers a timer with
+    /// the task when the limit is reached.
+    fn available(&mut self, cx: &mut Context<'_>) -> usize {
+        let now = Instant::now();
+        if now >= self.window + self.per {
+            self.window = now;
+            self.used = 0;
+            self.timer = None;
+        }
+        if self.used < self.max {
+            return self.max - self.used;
+        }
+
+        let next = self.window + self.per;
+        let timer = self
+            .timer
+            .get_or_insert_with(|| Box::pin(sleep_until(next)));
+        if timer.as_mut().poll(cx).is_ready() {
+            self.window = next;
+            self.used = 0;
+            self.timer = None;
+            self.max
+        } else {
+            0
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Suspension {
+    resumed: AtomicBool,
+    task: AtomicWaker,
+}
+
+/// A handle which resumes an actor suspended with
+/// [`Context::suspend`](crate::Context::suspend).
+///
+/// It can be cloned and sent to other threads.
+#[derive(Clone, Debug)]
+pub struct ResumeHandle {
+    inner: Arc<Suspension>,
+}
+
+impl ResumeHandle {
+    /// Resumes the suspended actor.
+    pub fn resume(&self) {
+        self.inner.resumed.store(true, Ordering::SeqCst);
+        self.inner.task.wake();
+    }
+}
+
+/// Priority of mailbox messages relative to spawned futures and streams.
+///
+/// Set with [`Context::set_mailbox_priority`](crate::Context::set_mailbox_priority).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MailboxPriority {
+    /// Mailbox is polled after every spawned item, so messages preempt busy
+    /// streams as early as possible.
+    High,
+    /// Mailbox is polled once per pass, before spawned items. This is the
+    /// default.
+    Normal,
+    /// Mailbox is polled once per pass, after spawned items.
+    Low,
+}
+
+#[allow(clippy::derivable_impls)] // `#[default]` is not available on MSRV
+impl Default for MailboxPriority {
+    fn default() -> Self {
+        MailboxPriority::Normal
+    }
+}
+
+/// What happens to a message stashed while the stash is full.
+///
+/// Set with [`Context::set_stash_limit`](crate::Context::set_stash_limit),
+/// which also takes the callback for dropped messages.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StashOverflow {
+    /// The oldest stashed message is dropped to make room.
+    DropOldest,
+    /// The most recently stashed message is dropped to make room.
+    DropNewest,
+    /// The message being stashed is dropped.
+    Reject,
+}
+
+/// Latency percentiles of handled mailbox messages.
+///
+/// Returned by [`Context::latency_stats`](crate::Context::latency_stats).
+/// Percentiles are approximated by a bucketed histogram and are accurate
+/// to within about 12%.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LatencyStats {
+    /// Number of measured messages.
+    pub count: u64,
+    /// Shortest handling time.
+    pub min: Duration,
+    /// Longest handling time.
+    pub max: Duration,
+    /// Average handling time.
+    pub mean: Duration,
+    /// Median handling time.
+    pub p50: Duration,
+    /// Handling time not exceeded by 99% of the messages.
+    pub p99: Duration,
+}
+
+// values below 8ns get a bucket each, above that every power of two is
+// divided into 8 buckets
+const SUB_BUCKETS: u64 = 8;
+const BUCKETS: usize = 62 * SUB_BUCKETS as usize;
+
+/// Histogram of message handling times in nanoseconds.
+pub(crate) struct LatencyHistogram {
+    buckets: Vec<u64>,
+    count: u64,
+    sum: u128,
+    min: u64,
+    max: u64,
+}
+
+impl LatencyHistogram {
+    fn new() -> Self {
+        LatencyHistogram {
+            buckets: vec![0; BUCKETS],
+            count: 0,
+            sum: 0,
+            min: u64::MAX,
+            max: 0,
+        }
+    }
+
+    fn index(val: u64) -> usize {
+        if val < SUB_BUCKETS {
+            return val as usize;
+        }
+        let exp = 63 - u64::from(val.leading_zeros());
+        let mantissa = (val >> (exp - 3)) & (SUB_BUCKETS - 1);
+        ((exp - 2) * SUB_BUCKETS + mantissa) as usize
+    }
+
+    /// Smallest value of the bucket at `idx`.
+    fn lowest(idx: usize) -> u64 {
+        let idx = idx as u64;
+        if idx < SUB_BUCKETS {
+            return idx;
+        }
+        let exp = idx / SUB_BUCKETS + 2;
+        (SUB_BUCKETS + idx % SUB_BUCKETS) << (exp - 3)
+    }
+
+    pub(crate) fn record(&mut self, dur: Duration) {
+        let val = dur.as_nanos().min(u128::from(u64::MAX)) as u64;
+        self.buckets[Self::index(val)] += 1;
+        self.count += 1;
+        self.sum += u128::from(val);
+        self.min = self.min.min(val);
+        self.max = self.max.max(val);
+    }
+
+    /// Upper bound of the bucket holding the `q` quantile.
+    fn quantile(&self, q: f64) -> u64 {
+        let rank = ((self.count as f64 * q).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (idx, n) in self.buckets.iter().enumerate() {
+            seen += n;
+            if seen >= rank {
+                let upper = match idx + 1 {
+                    BUCKETS => u64::MAX,
+                    next => Self::lowest(next) - 1,
+                };
+                return upper.max(self.min).min(self.max);
+            }
+        }
+        self.max
+    }
+
+    fn stats(&self) -> LatencyStats {
+        if self.count == 0 {
+            return LatencyStats::default();
+        }
+        LatencyStats {
+            count: self.count,
+            min: Duration::from_nanos(self.min),
+            max: Duration::from_nanos(self.max),
+            mean: Duration::from_nanos((self.sum / u128::from(self.count)) as u64),
+            p50: Duration::from_nanos(self.quantile(0.5)),
+            p99: Duration::from_nanos(self.quantile(0.99)),
+        }
+    }
+}
+
+/// Queue state of one of the inboxes of an actor.
+///
+/// Returned by [`Context::inbox_stats`](crate::Context::inbox_stats).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InboxStat {
+    /// Label of the inbox, `"mailbox"` for the actor's address.
+    pub label: String,
+    /// All senders of the inbox were dropped.
+    pub closed: bool,
+    /// Number of messages waiting to be handled.
+    pub queued: usize,
+}
+
+/// Receives counters and gauges of an actor context.
+///
+/// Installed with
+/// [`Context::set_metrics_sink`](crate::Context::set_metrics_sink) to feed the
+/// internals of an actor into a metrics registry, like a statsd or
+/// prometheus client. The context reports:
+///
+/// - `messages_handled`: counter of handled mailbox messages.
+/// - `items_completed`: counter of completed spawned futures and streams.
+/// - `errors`: counter of errors passed to
+///   [`Context::report_error`](crate::Context::report_error).
+/// - `items_pending`: gauge of running spawned futures and streams, set at
+///   the end of every poll pass.
+/// - `messages_queued`: gauge of messages waiting in the mailbox, set at the
+///   end of every poll pass.
+#[cfg(feature = "metrics")]
+pub trait MetricsSink {
+    /// Increments the counter `name` by `by`.
+    fn incr(&self, name: &str, by: u64);
+
+    /// Sets the gauge `name` to `value`.
+    fn gauge(&self, name: &str, value: f64);
+}
+
+/// A stashed message along with the handler that redelivers it.
+pub(crate) struct Stashed<A: Actor> {
+    pub(crate) msg: Box<dyn Any>,
+    pub(crate) deliver: fn(Box<dyn Any>, &mut A, &mut A::Context),
+    // time the message was stashed
+    pub(crate) at: Instant,
+    // the message was passed to the age limit callback
+    pub(crate) aged: bool,
+}
+
+/// A summary of the work an actor context still has outstanding.
+///
+/// Returned by [`Context::pending`](crate::Context::pending), mainly to find
+/// out what keeps an actor from finishing.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PendingItems {
+    /// Number of spawned futures and streams.
+    pub spawned: usize,
+    /// Number of futures the context waits on before processing messages.
+    pub waiting: usize,
+    /// Number of messages queued in the mailbox.
+    pub messages: usize,
+    /// Labels of outstanding futures spawned with
+    /// [`Context::spawn_labeled`](crate::Context::spawn_labeled).
+    pub labels: Vec<String>,
+}
+
+/// Information about a spawned item, passed to an [`ItemScheduler`].
+#[derive(Clone, Copy, Debug)]
+pub struct ItemInfo<'a> {
+    /// Handle returned when the item was spawned.
+    pub handle: SpawnHandle,
+    /// Label of an item spawned with
+    /// [`Context::spawn_labeled`](crate::Context::spawn_labeled).
+    pub label: Option<&'a str>,
+    /// Time since the item was spawned.
+    pub age: Duration,
+}
+
+/// Decides the order in which spawned futures and streams are polled.
+///
+/// Installed with
+/// [`Context::set_item_scheduler`](crate::Context::set_item_scheduler). Before
+/// every pass over the spawned items, items are polled in descending order of
+/// their priority. Items with equal priority keep their current order.
+pub trait ItemScheduler {
+    /// Returns the priority of an item for the current pass.
+    fn priority(&mut self, item: &ItemInfo<'_>) -> i64;
+}
+
+/// Scheduler that polls items in the order they were spawned.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct OldestFirst;
+
+impl ItemScheduler for OldestFirst {
+    fn priority(&mut self, item: &ItemInfo<'_>) -> i64 {
+        -(item.handle.into_usize() as i64)
+    }
+}
+
+/// Scheduler that polls items spawned most recently first.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NewestFirst;
+
+impl ItemScheduler for NewestFirst {
+    fn priority(&mut self, item: &ItemInfo<'_>) -> i64 {
+        item.handle.into_usize() as i64
+    }
+}
+
 pub trait AsyncContextParts<A>: ActorContext + AsyncContext<A>
 where
     A: Actor<Context = Self>,
@@ -45,6 +435,227 @@ where
     wait: SmallVec<[ActorWaitItem<A>; 2]>,
     items: SmallVec<[Item<A>; 3]>,
     handles: SmallVec<[SpawnHandle; 2]>,
+    waker: Option<Waker>,
+    // number of items and wait futures merged into ContextFut
+    merged: (usize, usize),
+    // handles of items merged into ContextFut, in the order of its items
+    running: SmallVec<[SpawnHandle; 3]>,
+    // state of opt-in features, allocated on first use
+    features: Option<Box<Features<A>>>,
+}
+
+/// State of opt-in context features.
+struct Features<A: Actor> {
+    preserved: SmallVec<[SpawnHandle; 2]>,
+    // items flushed once more when the drain deadline elapses
+    flushers: SmallVec<[SpawnHandle; 2]>,
+    // drivers of context features, kept by transitions
+    internal: SmallVec<[SpawnHandle; 2]>,
+    ordered: bool,
+    quiesce: SmallVec<[Callback<A>; 1]>,
+    ordered_futures: VecDeque<OrderedItem<A>>,
+    drain_deadline: Option<Pin<Box<Sleep>>>,
+    // memberships in shutdown coordinators
+    shutdowns: Vec<ShutdownListener>,
+    high_water: Option<HighWater>,
+    labels: HashMap<SpawnHandle, String>,
+    suspension: Option<Arc<Suspension>>,
+    mailbox_priority: MailboxPriority,
+    // max messages per poll of the context, and messages handled so far
+    poll_budget: Option<(usize, usize)>,
+    fair_polling: bool,
+    extensions: HashMap<TypeId, Box<dyn Any>>,
+    stash: VecDeque<Stashed<A>>,
+    stash_limit: Option<(usize, StashOverflow)>,
+    on_overflow: Option<OverflowCallback<A>>,
+    stash_age: Option<StashAge<A>>,
+    // messages dropped from a full stash, for `on_overflow`
+    overflowed: VecDeque<Box<dyn Any>>,
+    // number of stashed messages scheduled for redelivery
+    unstash: usize,
+    scheduler: Option<Box<dyn ItemScheduler>>,
+    spawned_at: HashMap<SpawnHandle, Instant>,
+    completions: HashMap<SpawnHandle, Callback<A>>,
+    // deadlines of futures spawned with a ttl
+    deadlines: HashMap<SpawnHandle, Rc<Cell<Instant>>>,
+    // number of alive timer items, allocated with the first timer
+    timers: Option<Rc<Cell<usize>>>,
+    rate_limit: Option<RateLimit>,
+    // bounds of the mailbox capacity, adapted to the backlog
+    adaptive_capacity: Option<(usize, usize)>,
+    idle_timeout: Option<IdleTimeout>,
+    // the actor is not expected to run out of work, checked once
+    perpetual: bool,
+    coalesce: Option<Coalesce>,
+    expect: Option<Expect<A>>,
+    latency: Option<Rc<RefCell<LatencyHistogram>>>,
+    // time of the last poll, read by the watchdog
+    last_poll: Option<Rc<Cell<Instant>>>,
+    on_state_change: Option<StateCallback<A>>,
+    observer: Option<Box<Observer>>,
+    limiter: Option<Rc<RefCell<Limiter>>>,
+    // limits futures resolving to streams, added with `add_fut_stream`
+    stream_limiter: Option<Rc<RefCell<Limiter>>>,
+    // type names of spawned items, if enabled
+    typenames: Option<HashMap<SpawnHandle, &'static str>>,
+    inboxes: Option<Rc<RefCell<Inboxes<A>>>>,
+    // labels and queue states of weighted inboxes
+    inbox_states: Vec<(String, Arc<InboxState>)>,
+    // streams polled ahead of the mailbox and spawned items
+    control: Vec<Control<A>>,
+    #[cfg(feature = "trace")]
+    trace: Option<&'static str>,
+    #[cfg(feature = "metrics")]
+    metrics: Option<Box<dyn MetricsSink>>,
+    // futures resolved before `Actor::started`, `None` on failure
+    prepared: Vec<Prepared>,
+}
+
+impl<A: Actor> Features<A> {
+    fn new() -> Self {
+        Features {
+            preserved: SmallVec::new(),
+            flushers: SmallVec::new(),
+            internal: SmallVec::new(),
+            ordered: false,
+            quiesce: SmallVec::new(),
+            ordered_futures: VecDeque::new(),
+            drain_deadline: None,
+            shutdowns: Vec::new(),
+            high_water: None,
+            labels: HashMap::new(),
+            suspension: None,
+            mailbox_priority: MailboxPriority::Normal,
+            poll_budget: None,
+            fair_polling: false,
+            extensions: HashMap::new(),
+            stash: VecDeque::new(),
+            stash_limit: None,
+            on_overflow: None,
+            stash_age: None,
+            overflowed: VecDeque::new(),
+            unstash: 0,
+            scheduler: None,
+            spawned_at: HashMap::new(),
+            completions: HashMap::new(),
+            deadlines: HashMap::new(),
+            rate_limit: None,
+            adaptive_capacity: None,
+            idle_timeout: None,
+            perpetual: false,
+            coalesce: None,
+            expect: None,
+            timers: None,
+            last_poll: None,
+            latency: None,
+            on_state_change: None,
+            observer: None,
+            limiter: None,
+            stream_limiter: None,
+            typenames: None,
+            inboxes: None,
+            inbox_states: Vec::new(),
+            control: Vec::new(),
+            #[cfg(feature = "trace")]
+            trace: None,
+            #[cfg(feature = "metrics")]
+            metrics: None,
+            prepared: Vec::new(),
+        }
+    }
+
+    #[inline]
+    fn mark_active(&mut self) {
+        if let Some(ref mut idle) = self.idle_timeout {
+            idle.active = true;
+        }
+    }
+
+    /// Number of armed timers owned by the features.
+    fn timer_count(&self) -> usize {
+        let pending = |timer: &Pin<Box<Sleep>>| !timer.is_elapsed();
+        let stashed = self.stash.iter().any(|item| !item.aged);
+        let context_timers = [
+            matches!(self.drain_deadline, Some(ref t) if pending(t)),
+            matches!(self.coalesce, Some(ref c) if pending(&c.timer)),
+            matches!(self.expect, Some(ref e) if pending(&e.timer)),
+            matches!(self.idle_timeout, Some(ref i) if pending(&i.timer)),
+            matches!(self.stash_age, Some(ref a) if stashed && pending(&a.timer)),
+            matches!(self.rate_limit, Some(RateLimit { timer: Some(ref t), .. }) if pending(t)),
+        ];
+        self.timers.as_ref().map_or(0, |timers| timers.get())
+            + self.deadlines.len()
+            + context_timers.iter().filter(|armed| **armed).count()
+    }
+
+    /// Drop label and spawn time of a finished or cancelled item.
+    fn forget(&mut self, handle: SpawnHandle) {
+        if !self.completions.is_empty() {
+            self.completions.remove(&handle);
+        }
+        if !self.labels.is_empty() {
+            self.labels.remove(&handle);
+        }
+        if !self.spawned_at.is_empty() {
+            self.spawned_at.remove(&handle);
+        }
+        if !self.deadlines.is_empty() {
+            self.deadlines.remove(&handle);
+        }
+        if let Some(ref mut typenames) = self.typenames {
+            typenames.remove(&handle);
+        }
+        if !self.flushers.is_empty() {
+            self.flushers.retain(|h| *h != handle);
+        }
+        if !self.internal.is_empty() {
+            self.internal.retain(|h| *h != handle);
+        }
+    }
+
+    /// Stash a message handler for later redelivery.
+    fn stash(&mut self, item: Stashed<A>) {
+        let policy = match self.stash_limit {
+            Some((0, _)) => StashOverflow::Reject,
+            Some((limit, policy)) if self.stash.len() >= limit => policy,
+            _ => return self.stash.push_back(item),
+        };
+        let dropped = match policy {
+            StashOverflow::DropOldest => {
+                // the oldest message may be scheduled for redelivery
+                self.unstash = self.unstash.saturating_sub(1);
+                let dropped = self.stash.pop_front().unwrap();
+                self.stash.push_back(item);
+                dropped
+            }
+            StashOverflow::DropNewest => {
+                let dropped = self.stash.pop_back().unwrap();
+                self.unstash = self.unstash.min(self.stash.len());
+                self.stash.push_back(item);
+                dropped
+            }
+            StashOverflow::Reject => item,
+        };
+        self.overflowed.push_back(dropped.msg);
+    }
+
+    /// Reset state of the features on restart, keeping preserved futures.
+    fn restart(&mut self) {
+        self.ordered_futures = VecDeque::new();
+        self.control.clear();
+        self.expect = None;
+        self.inbox_states.clear();
+        let preserved = &self.preserved;
+        self.internal.retain(|handle| preserved.contains(handle));
+        self.flushers.retain(|handle| preserved.contains(handle));
+        self.labels.retain(|handle, _| preserved.contains(handle));
+        self.spawned_at
+            .retain(|handle, _| preserved.contains(handle));
+        self.deadlines
+            .retain(|handle, _| preserved.contains(handle));
+        self.completions
+            .retain(|handle, _| preserved.contains(handle));
+    }
 }
 
 impl<A> fmt::Debug for ContextParts<A>
@@ -73,85 +684,750 @@ where
             wait: SmallVec::new(),
             items: SmallVec::new(),
             handles: SmallVec::from_slice(&[SpawnHandle::default(), SpawnHandle::default()]),
+            waker: None,
+            merged: (0, 0),
+            running: SmallVec::new(),
+            features: None,
         }
     }
 
-    #[inline]
-    /// Initiate stop process for actor execution
-    ///
-    /// Actor could prevent stopping by returning `false` from
-    /// `Actor::stopping()` method.
-    pub fn stop(&mut self) {
-        if self.flags.contains(ContextFlags::RUNNING) {
-            self.flags.remove(ContextFlags::RUNNING);
-            self.flags.insert(ContextFlags::STOPPING);
-        }
+    #[inline]
+    /// State of opt-in features, allocated on first use.
+    fn features(&mut self) -> &mut Features<A> {
+        self.features
+            .get_or_insert_with(|| Box::new(Features::new()))
+    }
+
+    #[inline]
+    /// Initiate stop process for actor execution
+    ///
+    /// Actor could prevent stopping by returning `false` from
+    /// `Actor::stopping()` method.
+    pub fn stop(&mut self) {
+        if self.flags.contains(ContextFlags::RUNNING) {
+            self.flags.remove(ContextFlags::RUNNING);
+            self.flags.insert(ContextFlags::STOPPING);
+        }
+    }
+
+    #[inline]
+    /// Terminate actor execution
+    pub fn terminate(&mut self) {
+        self.flags = ContextFlags::STOPPED;
+    }
+
+    #[inline]
+    /// Actor execution state
+    pub fn state(&self) -> ActorState {
+        if self.flags.contains(ContextFlags::RUNNING) {
+            ActorState::Running
+        } else if self.flags.contains(ContextFlags::STOPPED) {
+            ActorState::Stopped
+        } else if self.flags.contains(ContextFlags::STOPPING) {
+            ActorState::Stopping
+        } else {
+            ActorState::Started
+        }
+    }
+
+    #[inline]
+    /// Is context waiting for future completion
+    pub fn waiting(&self) -> bool {
+        !self.wait.is_empty()
+            || self
+                .flags
+                .intersects(ContextFlags::STOPPING | ContextFlags::STOPPED)
+    }
+
+    #[inline]
+    /// Should processing of messages and items pause, because of a wait
+    /// future, a suspension or messages scheduled for redelivery.
+    pub(crate) fn paused(&self) -> bool {
+        self.waiting()
+            || matches!(self.features, Some(ref f) if f.suspension.is_some() || f.unstash > 0)
+    }
+
+    #[inline]
+    fn suspended(&self) -> bool {
+        matches!(self.features, Some(ref f) if f.suspension.is_some())
+    }
+
+    #[inline]
+    fn unstashing(&self) -> bool {
+        matches!(self.features, Some(ref f) if f.unstash > 0)
+    }
+
+    #[inline]
+    /// Handle of the running future
+    pub fn curr_handle(&self) -> SpawnHandle {
+        self.handles[1]
+    }
+
+    #[inline]
+    /// Spawn new future to this context.
+    pub fn spawn<F>(&mut self, fut: F) -> SpawnHandle
+    where
+        F: ActorFuture<A, Output = ()> + 'static,
+    {
+        let handle = self.handles[0].next();
+        self.handles[0] = handle;
+        let fut: Box<dyn ActorFuture<A, Output = ()>> = Box::new(fut);
+        self.items.push((handle, Pin::from(fut)));
+        if let Some(features) = self.features.as_deref_mut() {
+            if features.scheduler.is_some() {
+                features.spawned_at.insert(handle, Instant::now());
+            }
+            if let Some(ref mut typenames) = features.typenames {
+                typenames.insert(handle, std::any::type_name::<F>());
+            }
+        }
+        handle
+    }
+
+    /// Spawn a driver of a context feature, which is not cancelled by
+    /// transitions.
+    pub(crate) fn spawn_internal<F>(&mut self, fut: F) -> SpawnHandle
+    where
+        F: ActorFuture<A, Output = ()> + 'static,
+    {
+        let handle = self.spawn(fut);
+        self.features().internal.push(handle);
+        handle
+    }
+
+    /// A message was handled outside of the mailbox, restarts the idle
+    /// timeout.
+    pub(crate) fn mark_active(&mut self) {
+        if let Some(features) = self.features.as_deref_mut() {
+            features.mark_active();
+        }
+    }
+
+    /// Spawn new timer based future to this context.
+    pub(crate) fn spawn_timer<F>(&mut self, fut: F) -> SpawnHandle
+    where
+        F: ActorFuture<A, Output = ()> + 'static,
+    {
+        let timers = self.armed_timers();
+        let handle = self.spawn(ActorTimerItem::new(fut, timers));
+        if let Some(typenames) = self.features().typenames.as_mut() {
+            typenames.insert(handle, std::any::type_name::<F>());
+        }
+        handle
+    }
+
+    /// Number of armed timers: timer items, ttl deadlines and context timers
+    /// whose deadline has not passed yet.
+    pub fn timer_count(&self) -> usize {
+        self.features.as_ref().map_or(0, |f| f.timer_count())
+    }
+
+    /// Polls the item once more before the drain deadline drops it.
+    pub(crate) fn flush_on_drain(&mut self, handle: SpawnHandle) {
+        self.features().flushers.push(handle);
+    }
+
+    #[inline]
+    /// Counter of timers owned by items, like sink keepalives.
+    pub(crate) fn armed_timers(&mut self) -> Rc<Cell<usize>> {
+        Rc::clone(self.features().timers.get_or_insert_with(Rc::default))
+    }
+
+    /// Spawn new future which only runs while fewer than the maximum number
+    /// of limited futures are running.
+    pub fn spawn_limited<F>(&mut self, fut: F) -> SpawnHandle
+    where
+        F: ActorFuture<A, Output = ()> + 'static,
+    {
+        let limiter = Rc::clone(self.features().limiter.get_or_insert_with(Rc::default));
+        self.spawn(ActorLimitedItem::new(fut, limiter))
+    }
+
+    /// Spawn new future resolving a stream, which only runs while fewer than
+    /// the maximum number of pending streams are resolving.
+    pub(crate) fn spawn_pending_stream<F>(&mut self, fut: F) -> SpawnHandle
+    where
+        F: ActorFuture<A, Output = ()> + 'static,
+    {
+        let limiter = Rc::clone(
+            self.features()
+                .stream_limiter
+                .get_or_insert_with(Rc::default),
+        );
+        self.spawn(ActorLimitedItem::new(fut, limiter))
+    }
+
+    /// Spawn new future which is dropped once its deadline passes.
+    pub fn spawn_with_ttl<F>(
+        &mut self,
+        fut: F,
+        ttl: Duration,
+        on_expired: Callback<A>,
+    ) -> SpawnHandle
+    where
+        F: ActorFuture<A, Output = ()> + 'static,
+    {
+        let deadline = Rc::new(Cell::new(Instant::now() + ttl));
+        let handle = self.spawn(ActorTtlItem::new(fut, Rc::clone(&deadline), on_expired));
+        self.features().deadlines.insert(handle, deadline);
+        handle
+    }
+
+    /// Move deadline of a future spawned with a ttl.
+    pub fn extend_deadline(&mut self, handle: SpawnHandle, additional: Duration) -> bool {
+        match self
+            .features
+            .as_ref()
+            .and_then(|f| f.deadlines.get(&handle))
+        {
+            Some(deadline) => {
+                deadline.set(deadline.get() + additional);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Add weighted inbox, spawns the item draining inboxes if needed.
+    pub(crate) fn add_inbox(&mut self, inbox: Inbox<A>, label: String, state: Arc<InboxState>) {
+        let features = self.features();
+        // forget closed inboxes which were drained
+        features
+            .inbox_states
+            .retain(|(_, state)| !state.closed() || state.queued() > 0);
+        features.inbox_states.push((label, state));
+        let inboxes = features.inboxes.get_or_insert_with(Rc::default);
+        if inboxes.borrow_mut().add(inbox) {
+            let inboxes = Rc::clone(inboxes);
+            self.spawn_internal(ActorWeightedInboxes::new(inboxes));
+        }
+    }
+
+    #[cfg(feature = "trace")]
+    /// Log lifecycle calls and handled messages under `name`.
+    pub fn set_trace(&mut self, name: &'static str) {
+        self.features().trace = Some(name);
+    }
+
+    #[cfg(feature = "metrics")]
+    /// Push counters of the context to `sink`.
+    pub fn set_metrics_sink(&mut self, sink: Box<dyn MetricsSink>) {
+        self.features().metrics = Some(sink);
+    }
+
+    #[cfg(feature = "metrics")]
+    /// Increment a counter of the metrics sink, if one is set.
+    pub(crate) fn incr_metric(&self, name: &str, by: u64) {
+        if let Some(metrics) = self.features.as_ref().and_then(|f| f.metrics.as_ref()) {
+            metrics.incr(name, by);
+        }
+    }
+
+    /// Add stream that is polled before the mailbox.
+    pub fn add_control<F>(&mut self, fut: F)
+    where
+        F: ActorFuture<A, Output = ()> + 'static,
+    {
+        self.features().control.push(Box::pin(fut));
+    }
+
+    /// Set maximum number of concurrently resolving streams.
+    pub fn set_max_pending_streams(&mut self, max: usize) {
+        self.features()
+            .stream_limiter
+            .get_or_insert_with(Rc::default)
+            .borrow_mut()
+            .set_max(max);
+    }
+
+    /// Set maximum number of concurrently running limited futures.
+    pub fn set_max_concurrent(&mut self, max: usize) {
+        self.features()
+            .limiter
+            .get_or_insert_with(Rc::default)
+            .borrow_mut()
+            .set_max(max);
+    }
+
+    #[inline]
+    /// Spawn new future to this context and attach `label` to its handle.
+    pub fn spawn_labeled<F>(&mut self, label: String, fut: F) -> SpawnHandle
+    where
+        F: ActorFuture<A, Output = ()> + 'static,
+    {
+        let handle = self.spawn(fut);
+        self.features().labels.insert(handle, label);
+        handle
+    }
+
+    #[inline]
+    /// Label of a running future spawned with `spawn_labeled`.
+    pub fn label_of(&self, handle: SpawnHandle) -> Option<&str> {
+        self.features
+            .as_ref()?
+            .labels
+            .get(&handle)
+            .map(String::as_str)
+    }
+
+    /// Cancel all running futures with the given label.
+    pub fn cancel_labeled(&mut self, label: &str) -> usize {
+        let handles: SmallVec<[SpawnHandle; 2]> = match self.features {
+            Some(ref features) => features
+                .labels
+                .iter()
+                .filter(|(_, l)| l.as_str() == label)
+                .map(|(handle, _)| *handle)
+                .collect(),
+            None => return 0,
+        };
+        for handle in &handles {
+            self.cancel_future(*handle);
+            self.forget(*handle);
+        }
+        handles.len()
+    }
+
+    /// Cancel all running futures except the ones in `keep` and the drivers
+    /// of context features.
+    pub(crate) fn cancel_all_except(&mut self, keep: &[SpawnHandle]) -> usize {
+        let internal = self.features.as_ref().map_or(&[][..], |f| &f.internal[..]);
+        let handles: Vec<SpawnHandle> = self
+            .pending_handles()
+            .filter(|handle| !keep.contains(handle) && !internal.contains(handle))
+            .collect();
+        for handle in &handles {
+            self.cancel_future(*handle);
+            self.forget(*handle);
+        }
+        handles.len()
+    }
+
+    #[inline]
+    /// Drop label and spawn time of a finished or cancelled item.
+    fn forget(&mut self, handle: SpawnHandle) {
+        if let Some(features) = self.features.as_deref_mut() {
+            features.forget(handle);
+        }
+    }
+
+    /// Handles of spawned items that are neither completed nor cancelled.
+    fn pending_handles(&self) -> impl Iterator<Item = SpawnHandle> + '_ {
+        let cancelled = &self.handles[2..];
+        self.running
+            .iter()
+            .copied()
+            .chain(self.items.iter().map(|item| item.0))
+            .filter(move |handle| !cancelled.contains(handle))
+    }
+
+    /// Record type names of spawned items, only has effect in debug builds.
+    pub fn set_debug_typenames(&mut self, enabled: bool) {
+        if enabled && cfg!(debug_assertions) {
+            self.features().typenames.get_or_insert_with(HashMap::new);
+        } else if let Some(features) = self.features.as_deref_mut() {
+            features.typenames = None;
+        }
+    }
+
+    /// Type names of spawned items, sorted by spawn order.
+    pub fn item_typenames(&self) -> Vec<&'static str> {
+        let mut typenames: Vec<_> = self
+            .features
+            .iter()
+            .flat_map(|f| f.typenames.iter().flatten())
+            .collect();
+        typenames.sort_by_key(|(handle, _)| handle.into_usize());
+        typenames.into_iter().map(|(_, name)| *name).collect()
+    }
+
+    #[inline]
+    /// Whether removing items must keep the order of the remaining ones.
+    fn keep_order(&self) -> bool {
+        matches!(self.features, Some(ref f) if f.ordered || f.scheduler.is_some())
+    }
+
+    /// Set scheduler which orders spawned items before each pass.
+    pub fn set_item_scheduler(&mut self, scheduler: Box<dyn ItemScheduler>) {
+        self.features().scheduler = Some(scheduler);
+    }
+
+    #[inline]
+    /// Spawn new future to this context and wait future completion.
+    ///
+    /// During wait period actor does not receive any messages.
+    pub fn wait<F>(&mut self, f: F)
+    where
+        F: ActorFuture<A, Output = ()> + 'static,
+    {
+        self.wait.push(ActorWaitItem::new(f));
+    }
+
+    #[inline]
+    /// Cancel previously scheduled future.
+    pub fn cancel_future(&mut self, handle: SpawnHandle) -> bool {
+        self.handles.push(handle);
+        true
+    }
+
+    /// Call `cb` once the spawned item with `handle` completes.
+    pub fn on_complete(&mut self, handle: SpawnHandle, cb: Callback<A>) -> bool {
+        if self.pending_handles().any(|h| h == handle) {
+            self.features().completions.insert(handle, cb);
+            true
+        } else {
+            false
+        }
+    }
+
+    #[inline]
+    /// Keep a spawned future alive when the supervisor restarts the actor.
+    pub fn preserve_on_restart(&mut self, handle: SpawnHandle) {
+        let preserved = &mut self.features().preserved;
+        if !preserved.contains(&handle) {
+            preserved.push(handle);
+        }
+    }
+
+    #[inline]
+    /// Poll spawned futures in the order they were spawned.
+    pub fn set_preserve_item_order(&mut self, ordered: bool) {
+        self.features().ordered = ordered;
+    }
+
+    #[inline]
+    /// Pause mailbox processing until all spawned futures complete, then call `f`.
+    pub fn quiesce<F>(&mut self, f: F)
+    where
+        F: FnOnce(&mut A, &mut A::Context) + 'static,
+    {
+        self.features().quiesce.push(Box::new(f));
+    }
+
+    /// Stop once all items completed, dropping the remaining ones after `timeout`.
+    pub(crate) fn drain_and_stop(&mut self, timeout: Duration) {
+        self.features().drain_deadline = Some(Box::pin(sleep(timeout)));
+        self.quiesce(|_, ctx| ctx.stop());
+    }
+
+    /// Drain and stop the actor once the shutdown of `listener` begins.
+    pub(crate) fn join_shutdown(&mut self, listener: ShutdownListener) {
+        self.features().shutdowns.push(listener);
+    }
+
+    #[inline]
+    /// Call `cb` each time number of spawned futures grows above `threshold`.
+    pub fn on_items_high_water<F>(&mut self, threshold: usize, cb: F)
+    where
+        F: FnMut(usize) + 'static,
+    {
+        self.features().high_water = Some(HighWater {
+            threshold,
+            exceeded: false,
+            cb: Box::new(cb),
+        });
+    }
+
+    /// Queue of futures whose results are delivered in the order they were added
+    #[inline]
+    pub(crate) fn ordered_futures(&mut self) -> &mut VecDeque<OrderedItem<A>> {
+        &mut self.features().ordered_futures
+    }
+
+    #[inline]
+    /// Stop polling the context until returned handle is resumed.
+    pub fn suspend(&mut self) -> ResumeHandle {
+        let inner = self
+            .features()
+            .suspension
+            .get_or_insert_with(|| Arc::new(Suspension::default()))
+            .clone();
+        ResumeHandle { inner }
+    }
+
+    /// Queue states of the mailbox and the weighted inboxes.
+    pub fn inbox_stats(&self) -> Vec<InboxStat> {
+        let mailbox = InboxStat {
+            label: "mailbox".to_owned(),
+            closed: !self.addr.connected(),
+            queued: self.addr.queued(),
+        };
+        let inboxes = self
+            .features
+            .iter()
+            .flat_map(|f| f.inbox_states.iter())
+            .map(|(label, state)| InboxStat {
+                label: label.clone(),
+                closed: state.closed(),
+                queued: state.queued(),
+            });
+        std::iter::once(mailbox).chain(inboxes).collect()
+    }
+
+    /// Visit spawned items that have not completed, in spawn order.
+    pub fn for_each_item<F: FnMut(&ItemInfo<'_>)>(&self, mut f: F) {
+        let now = Instant::now();
+        let features = self.features.as_deref();
+        let mut handles: Vec<SpawnHandle> = self.pending_handles().collect();
+        handles.sort_unstable();
+        for handle in handles {
+            f(&ItemInfo {
+                handle,
+                label: features
+                    .and_then(|f| f.labels.get(&handle))
+                    .map(String::as_str),
+                age: features
+                    .and_then(|f| f.spawned_at.get(&handle))
+                    .map_or(Duration::from_secs(0), |at| now - *at),
+            });
+        }
+    }
+
+    /// Summary of outstanding work.
+    pub fn pending(&self) -> PendingItems {
+        let mut labels: Vec<String> = self
+            .features
+            .iter()
+            .flat_map(|f| f.labels.values().cloned())
+            .collect();
+        labels.sort();
+        PendingItems {
+            spawned: self.merged.0 + self.items.len(),
+            waiting: self.merged.1 + self.wait.len(),
+            messages: self.addr.queued(),
+            labels,
+        }
+    }
+
+    #[inline]
+    /// Waker of the task that drives this context.
+    pub fn waker(&self) -> Option<Waker> {
+        self.waker.clone()
+    }
+
+    #[inline]
+    /// Set priority of mailbox messages relative to spawned items.
+    pub fn set_mailbox_priority(&mut self, priority: MailboxPriority) {
+        self.features().mailbox_priority = priority;
+    }
+
+    #[inline]
+    /// Limit the number of messages handled per poll of the context.
+    pub fn set_poll_budget(&mut self, budget: usize) {
+        let features = self.features();
+        let used = features.poll_budget.map_or(0, |(_, used)| used);
+        features.poll_budget = Some((budget, used));
+    }
+
+    #[inline]
+    /// Interleave mailbox messages one by one with spawned items.
+    pub fn set_fair_polling(&mut self, fair: bool) {
+        self.features().fair_polling = fair;
+    }
+
+    /// Stop the context after it has been idle for `dur`.
+    pub fn set_idle_timeout(&mut self, dur: Duration) {
+        self.features().idle_timeout = Some(IdleTimeout {
+            dur,
+            active: false,
+            timer: Box::pin(sleep_until(Instant::now() + dur)),
+        });
+    }
+
+    /// Call `on_timeout` unless a mailbox message is handled within `dur`.
+    pub fn expect_within(&mut self, dur: Duration, on_timeout: Callback<A>) {
+        self.features().expect = Some(Expect {
+            dur,
+            timer: Box::pin(sleep_until(Instant::now() + dur)),
+            on_timeout,
+        });
+    }
+
+    /// Mark the actor as running for the lifetime of the program.
+    pub fn set_perpetual(&mut self) {
+        self.features().perpetual = true;
+    }
+
+    /// Coalesce wakeups of the context into one poll pass per `window`.
+    pub fn coalesce_wakeups(&mut self, window: Duration) {
+        self.features().coalesce = Some(Coalesce {
+            window,
+            last: None,
+            timer: Box::pin(sleep_until(Instant::now())),
+        });
+    }
+
+    /// Measure handling times of mailbox messages, resets collected stats.
+    pub fn track_latency(&mut self) {
+        self.features().latency = Some(Rc::new(RefCell::new(LatencyHistogram::new())));
+    }
+
+    /// Handling time stats, if latency tracking is enabled.
+    pub fn latency_stats(&self) -> Option<LatencyStats> {
+        self.features
+            .as_ref()?
+            .latency
+            .as_ref()
+            .map(|latency| latency.borrow().stats())
+    }
+
+    /// Call `on_stall` when the context was not polled for `timeout`.
+    pub fn set_watchdog<F>(&mut self, timeout: Duration, mut on_stall: F)
+    where
+        F: FnMut() + 'static,
+    {
+        let last_poll = Rc::new(Cell::new(Instant::now()));
+        let seen = Rc::downgrade(&last_poll);
+        self.features().last_poll = Some(last_poll);
+
+        // stops once the context is gone or the watchdog is replaced
+        actix_rt::spawn(async move {
+            let mut reported = None;
+            while let Some(last) = seen.upgrade().map(|last| last.get()) {
+                if reported != Some(last) && last.elapsed() >= timeout {
+                    reported = Some(last);
+                    on_stall();
+                }
+                let next = if reported == Some(last) {
+                    Instant::now() + timeout
+                } else {
+                    last + timeout
+                };
+                sleep_until(next).await;
+            }
+        });
+    }
+
+    /// Store a context-local value, replacing any value of the same type.
+    pub fn insert<V: 'static>(&mut self, val: V) -> Option<V> {
+        self.features()
+            .extensions
+            .insert(TypeId::of::<V>(), Box::new(val))
+            .and_then(|prev| prev.downcast().ok().map(|prev| *prev))
+    }
+
+    /// Get a reference to a context-local value.
+    pub fn get<V: 'static>(&self) -> Option<&V> {
+        self.features
+            .as_ref()?
+            .extensions
+            .get(&TypeId::of::<V>())
+            .and_then(|val| val.downcast_ref())
+    }
+
+    /// Get a mutable reference to a context-local value.
+    pub fn get_mut<V: 'static>(&mut self) -> Option<&mut V> {
+        self.features
+            .as_mut()?
+            .extensions
+            .get_mut(&TypeId::of::<V>())
+            .and_then(|val| val.downcast_mut())
+    }
+
+    /// Remove a context-local value.
+    pub fn remove<V: 'static>(&mut self) -> Option<V> {
+        self.features
+            .as_mut()?
+            .extensions
+            .remove(&TypeId::of::<V>())
+            .and_then(|val| val.downcast().ok().map(|val| *val))
+    }
+
+    /// Resolve a future before the actor is started and store its output
+    /// as a context-local value.
+    pub fn prepare<F, T, E>(&mut self, fut: F)
+    where
+        F: Future<Output = Result<T, E>> + 'static,
+        T: 'static,
+        E: fmt::Debug,
+    {
+        self.features().prepared.push(Box::pin(async move {
+            match fut.await {
+                Ok(val) => {
+                    let val: Box<dyn Any> = Box::new(val);
+                    Some((TypeId::of::<T>(), val))
+                }
+                Err(err) => {
+                    error!("Prepared future failed, actor is not started: {:?}", err);
+                    None
+                }
+            }
+        }));
+    }
+
+    /// Stash a message handler for later redelivery.
+    pub(crate) fn stash(&mut self, item: Stashed<A>) {
+        self.features().stash(item);
     }
 
-    #[inline]
-    /// Terminate actor execution
-    pub fn terminate(&mut self) {
-        self.flags = ContextFlags::STOPPED;
+    /// Limit number of stashed messages, `on_overflow` gets dropped messages.
+    pub fn set_stash_limit(
+        &mut self,
+        limit: usize,
+        policy: StashOverflow,
+        on_overflow: OverflowCallback<A>,
+    ) {
+        let features = self.features();
+        features.stash_limit = Some((limit, policy));
+        features.on_overflow = Some(on_overflow);
     }
 
-    #[inline]
-    /// Actor execution state
-    pub fn state(&self) -> ActorState {
-        if self.flags.contains(ContextFlags::RUNNING) {
-            ActorState::Running
-        } else if self.flags.contains(ContextFlags::STOPPED) {
-            ActorState::Stopped
-        } else if self.flags.contains(ContextFlags::STOPPING) {
-            ActorState::Stopping
-        } else {
-            ActorState::Started
+    /// Call `on_aged` for stashed messages older than `dur`.
+    pub fn set_stash_age_limit(&mut self, dur: Duration, on_aged: AgedCallback<A>) {
+        self.features().stash_age = Some(StashAge {
+            dur,
+            timer: Box::pin(sleep_until(Instant::now() + dur)),
+            on_aged,
+        });
+    }
+
+    /// Schedule all stashed messages for redelivery.
+    pub fn unstash(&mut self) {
+        if let Some(features) = self.features.as_deref_mut() {
+            features.unstash = features.stash.len();
         }
     }
 
-    #[inline]
-    /// Is context waiting for future completion
-    pub fn waiting(&self) -> bool {
-        !self.wait.is_empty()
-            || self
-                .flags
-                .intersects(ContextFlags::STOPPING | ContextFlags::STOPPED)
+    /// Number of stashed messages.
+    pub fn stashed(&self) -> usize {
+        self.features.as_ref().map_or(0, |f| f.stash.len())
     }
 
-    #[inline]
-    /// Handle of the running future
-    pub fn curr_handle(&self) -> SpawnHandle {
-        self.handles[1]
+    /// Set callback observing mailbox messages before they are handled.
+    pub fn observe_messages(&mut self, observer: Box<Observer>) {
+        self.features().observer = Some(observer);
     }
 
-    #[inline]
-    /// Spawn new future to this context.
-    pub fn spawn<F>(&mut self, fut: F) -> SpawnHandle
-    where
-        F: ActorFuture<A, Output = ()> + 'static,
-    {
-        let handle = self.handles[0].next();
-        self.handles[0] = handle;
-        let fut: Box<dyn ActorFuture<A, Output = ()>> = Box::new(fut);
-        self.items.push((handle, Pin::from(fut)));
-        handle
+    /// Set callback called after actor state is mutated with `mutate_state`.
+    pub fn on_state_change(&mut self, f: StateCallback<A>) {
+        self.features().on_state_change = Some(f);
     }
 
-    #[inline]
-    /// Spawn new future to this context and wait future completion.
-    ///
-    /// During wait period actor does not receive any messages.
-    pub fn wait<F>(&mut self, f: F)
+    /// Apply mutation to the actor and notify the state change callback.
+    pub fn mutate_state<F, R>(&mut self, act: &mut A, f: F) -> R
     where
-        F: ActorFuture<A, Output = ()> + 'static,
+        F: FnOnce(&mut A) -> R,
     {
-        self.wait.push(ActorWaitItem::new(f));
+        let res = f(act);
+        if let Some(cb) = self
+            .features
+            .as_mut()
+            .and_then(|f| f.on_state_change.as_mut())
+        {
+            cb(act);
+        }
+        res
     }
 
-    #[inline]
-    /// Cancel previously scheduled future.
-    pub fn cancel_future(&mut self, handle: SpawnHandle) -> bool {
-        self.handles.push(handle);
-        true
+    /// Limit number of handled mailbox messages to `max` per `per`.
+    pub fn set_mailbox_rate_limit(&mut self, max: usize, per: Duration) {
+        self.features().rate_limit = Some(RateLimit {
+            max,
+            per,
+            window: Instant::now(),
+            used: 0,
+            timer: None,
+        });
     }
 
     #[inline]
@@ -165,18 +1441,59 @@ where
         self.addr.set_capacity(cap);
     }
 
+    /// Adapt mailbox capacity to the backlog, keeping it within the bounds.
+    pub fn set_adaptive_capacity(&mut self, min: usize, max: usize) {
+        let min = min.max(1);
+        let max = max.max(min);
+        self.features().adaptive_capacity = Some((min, max));
+        let cap = self.addr.capacity().max(min).min(max);
+        self.set_mailbox_capacity(cap);
+    }
+
+    /// Grow capacity while the mailbox is mostly empty, shrink it while
+    /// the actor falls behind.
+    fn adapt_capacity(&mut self) {
+        if let Some((min, max)) = self.features.as_ref().and_then(|f| f.adaptive_capacity) {
+            let (cap, queued) = (self.addr.capacity(), self.addr.queued());
+            let adapted = if queued.saturating_mul(4) >= cap.saturating_mul(3) {
+                (cap / 2).max(min)
+            } else if queued.saturating_mul(4) <= cap {
+                cap.saturating_mul(2).min(max)
+            } else {
+                cap
+            };
+            // new messages wake the context, no need for another pass
+            if adapted != cap {
+                self.addr.set_capacity(adapted);
+            }
+        }
+    }
+
     #[inline]
     pub fn address(&self) -> Addr<A> {
         Addr::new(self.addr.sender())
     }
 
-    /// Restart context. Cleanup all futures, except address queue.
+    /// Restart context. Cleanup all futures, except address queue and
+    /// preserved futures.
     #[inline]
     pub(crate) fn restart(&mut self) {
         self.flags = ContextFlags::RUNNING;
         self.wait = SmallVec::new();
-        self.items = SmallVec::new();
-        self.handles[0] = SpawnHandle::default();
+        match self.features {
+            Some(ref mut features) if !features.preserved.is_empty() => {
+                // handle counter is not reset, preserved futures keep their handles
+                let preserved = &features.preserved;
+                self.items.retain(|item| preserved.contains(&item.0));
+            }
+            _ => {
+                self.items = SmallVec::new();
+                self.handles[0] = SpawnHandle::default();
+            }
+        }
+        if let Some(features) = self.features.as_deref_mut() {
+            features.restart();
+        }
     }
 
     #[inline]
@@ -191,6 +1508,13 @@ where
     }
 }
 
+/// Future driving an actor and its context.
+///
+/// Usually it is spawned on the current arbiter by
+/// [`Context::run`](crate::Context::run), but it can also be obtained with
+/// [`Context::into_future`](crate::Context::into_future) and polled by an
+/// external event loop. The waker of the last poll gets woken whenever the
+/// actor has work to do, e.g. when a message arrives.
 pub struct ContextFut<A, C>
 where
     C: AsyncContextParts<A> + Unpin,
@@ -253,6 +1577,49 @@ where
         self.mailbox.address()
     }
 
+    /// Returns a reference to the actor driven by this future.
+    #[inline]
+    pub fn actor(&self) -> &A {
+        &self.act
+    }
+
+    /// Returns a mutable reference to the actor driven by this future.
+    #[inline]
+    pub fn actor_mut(&mut self) -> &mut A {
+        &mut self.act
+    }
+
+    /// Replaces the actor driven by this future, returning the old one.
+    ///
+    /// Context state, spawned futures and queued messages are kept, only the
+    /// actor value is swapped. `Actor::started` is not called for the new
+    /// value once the context is running.
+    #[inline]
+    pub fn replace_actor(&mut self, act: A) -> A {
+        std::mem::replace(&mut self.act, act)
+    }
+
+    /// Calls `Actor::started` unless prepared futures are outstanding.
+    pub(crate) fn start_eagerly(&mut self) {
+        let parts = self.ctx.parts();
+        let prepared = matches!(parts.features, Some(ref f) if !f.prepared.is_empty());
+        if !prepared && !parts.flags.contains(ContextFlags::STARTED) {
+            self.start();
+        }
+    }
+
+    fn start(&mut self) {
+        self.ctx.parts().flags.insert(ContextFlags::STARTED);
+        if self.act.should_start() {
+            self.traced("started", Actor::started);
+        }
+
+        // check cancelled handles, just in case
+        if self.merge() {
+            self.clean_canceled_handle();
+        }
+    }
+
     #[inline]
     fn stopping(&mut self) -> bool {
         self.ctx
@@ -261,6 +1628,38 @@ where
             .intersects(ContextFlags::STOPPING | ContextFlags::STOPPED)
     }
 
+    #[inline]
+    fn sync_merged(&mut self) {
+        self.ctx.parts().merged = (self.items.len(), self.wait.len());
+    }
+
+    /// Rebuild the handles of merged items after they were reordered.
+    fn sync_running(&mut self) {
+        let running = self.items.iter().map(|item| item.0).collect();
+        self.ctx.parts().running = running;
+    }
+
+    /// Should mailbox be polled at given point of poll pass.
+    #[inline]
+    fn poll_mailbox_at(&mut self, at: MailboxPriority) -> bool {
+        let features = match self.ctx.parts().features {
+            Some(ref features) => features,
+            None => return at == MailboxPriority::Normal,
+        };
+        if features.fair_polling {
+            return features.quiesce.is_empty() && at != MailboxPriority::Low;
+        }
+        features.quiesce.is_empty()
+            && (features.mailbox_priority == at
+                || (at == MailboxPriority::Normal
+                    && features.mailbox_priority == MailboxPriority::High))
+    }
+
+    #[inline]
+    fn suspended(&mut self) -> bool {
+        self.ctx.parts().suspended()
+    }
+
     #[inline]
     pub fn alive(&mut self) -> bool {
         if self.ctx.parts().flags.contains(ContextFlags::STOPPED) {
@@ -270,19 +1669,41 @@ where
                 || self.mailbox.connected()
                 || !self.items.is_empty()
                 || !self.wait.is_empty()
+                || matches!(self.ctx.parts().features, Some(ref f) if !f.control.is_empty())
         }
     }
 
     /// Restart context. Cleanup all futures, except address queue.
+    ///
+    /// An actor stopped by a joined shutdown is not restarted.
     #[inline]
     pub(crate) fn restart(&mut self) -> bool
     where
         A: Supervised,
     {
-        if self.mailbox.connected() {
+        let shut_down = matches!(
+            self.ctx.parts().features,
+            Some(ref f) if f.shutdowns.iter().any(|l| l.begun())
+        );
+        if self.mailbox.connected() && !shut_down {
             self.wait = SmallVec::new();
-            self.items = SmallVec::new();
+            let parts = self.ctx.parts();
+            match parts.features {
+                Some(ref mut features) if !features.preserved.is_empty() => {
+                    let items = &mut self.items;
+                    items.retain(|item| features.preserved.contains(&item.0));
+                    // forget handles of preserved futures that already completed
+                    let pending = &parts.items;
+                    features.preserved.retain(|handle| {
+                        items.iter().any(|item| item.0 == *handle)
+                            || pending.iter().any(|item| item.0 == *handle)
+                    });
+                }
+                _ => self.items = SmallVec::new(),
+            }
             self.ctx.parts().restart();
+            self.sync_merged();
+            self.sync_running();
             self.act.restarting(&mut self.ctx);
             true
         } else {
@@ -290,6 +1711,251 @@ where
         }
     }
 
+    /// Call a lifecycle method, logging its duration if tracing is enabled.
+    #[inline]
+    fn traced<R>(&mut self, what: &str, f: impl FnOnce(&mut A, &mut C) -> R) -> R {
+        #[cfg(feature = "trace")]
+        let trace = self
+            .ctx
+            .parts()
+            .features
+            .as_ref()
+            .and_then(|f| f.trace)
+            .map(|name| {
+                log::debug!("{}: {} begins", name, what);
+                (name, Instant::now())
+            });
+        #[cfg(not(feature = "trace"))]
+        let _ = what;
+
+        let res = f(&mut self.act, &mut self.ctx);
+
+        #[cfg(feature = "trace")]
+        {
+            if let Some((name, start)) = trace {
+                log::debug!("{}: {} done in {:?}", name, what, start.elapsed());
+            }
+        }
+        res
+    }
+
+    /// Process mailbox, honouring the rate limit.
+    fn poll_mailbox(&mut self, cx: &mut Context<'_>) {
+        // without features no limits apply and nothing is accounted
+        if self.ctx.parts().features.is_none() {
+            self.mailbox
+                .poll_limited(&mut self.act, &mut self.ctx, cx, usize::MAX, None, None);
+            return;
+        }
+        self.ctx.parts().adapt_capacity();
+
+        // stops at an exhausted limit, once its timer is registered
+        loop {
+            let features = self.ctx.parts().features();
+            let limit = match features.rate_limit {
+                Some(ref mut rate_limit) => rate_limit.available(cx),
+                None => usize::MAX,
+            };
+            if limit == 0 {
+                return;
+            }
+            // messages left over by the budget or fair polling are handled
+            // after a self wake up
+            let budget = match features.poll_budget {
+                Some((budget, used)) => budget.saturating_sub(used),
+                None => usize::MAX,
+            };
+            let cap = if features.fair_polling { 1 } else { usize::MAX };
+            if budget == 0 {
+                cx.waker().wake_by_ref();
+                return;
+            }
+            let limit = limit.min(budget).min(cap);
+            // traced messages are handled one at a time to time each of them
+            #[cfg(feature = "trace")]
+            let (limit, start) = match features.trace {
+                Some(_) => (limit.min(1), Instant::now()),
+                None => (limit, Instant::now()),
+            };
+            let mut observer = features.observer.take();
+            let latency = features.latency.clone();
+            let handled = self.mailbox.poll_limited(
+                &mut self.act,
+                &mut self.ctx,
+                cx,
+                limit,
+                observer.as_deref_mut(),
+                latency.as_deref(),
+            );
+            let features = self.ctx.parts().features();
+            #[cfg(feature = "trace")]
+            {
+                if let (Some(name), 1) = (features.trace, handled) {
+                    log::debug!("{}: message handled in {:?}", name, start.elapsed());
+                }
+            }
+            if features.observer.is_none() {
+                features.observer = observer;
+            }
+            if let Some(ref mut rate_limit) = features.rate_limit {
+                rate_limit.used += handled;
+            }
+            if let Some((_, ref mut used)) = features.poll_budget {
+                *used += handled;
+            }
+            if let Some(ref mut idle) = features.idle_timeout {
+                idle.active |= handled > 0;
+            }
+            #[cfg(feature = "metrics")]
+            {
+                if handled > 0 {
+                    self.ctx
+                        .parts()
+                        .incr_metric("messages_handled", handled as u64);
+                }
+            }
+            if let Some(ref mut expect) = self.ctx.parts().features().expect {
+                if handled > 0 {
+                    let deadline = Instant::now() + expect.dur;
+                    expect.timer.as_mut().reset(deadline);
+                }
+            }
+            if handled < limit {
+                return;
+            }
+            if limit == budget || limit == cap {
+                cx.waker().wake_by_ref();
+                return;
+            }
+        }
+    }
+
+    /// Reports stashed messages older than the stash age limit. Returns
+    /// `true` if the callback was called.
+    fn stash_aged(&mut self, cx: &mut Context<'_>) -> bool {
+        let features = match self.ctx.parts().features.as_deref_mut() {
+            Some(features) => features,
+            None => return false,
+        };
+        let age = match features.stash_age {
+            Some(ref mut age) => age,
+            None => return false,
+        };
+        let oldest = features
+            .stash
+            .iter()
+            .filter(|item| !item.aged)
+            .map(|item| item.at)
+            .min();
+        match oldest {
+            Some(at) => age.timer.as_mut().reset(at + age.dur),
+            None => return false,
+        }
+        if age.timer.as_mut().poll(cx).is_pending() {
+            return false;
+        }
+
+        let mut age = features.stash_age.take().unwrap();
+        let now = Instant::now();
+        let mut idx = 0;
+        while idx < self.ctx.parts().features().stash.len() {
+            let features = self.ctx.parts().features();
+            let item = &features.stash[idx];
+            if item.aged || item.at + age.dur > now {
+                idx += 1;
+                continue;
+            }
+
+            // the callback gets the context, take the message out meanwhile
+            let mut item = features.stash.remove(idx).unwrap();
+            item.aged = true;
+            let scheduled = idx < features.unstash;
+            if scheduled {
+                features.unstash -= 1;
+            }
+            let unstash = features.unstash;
+            let keep = (age.on_aged)(&mut self.act, &*item.msg, &mut self.ctx);
+
+            let features = self.ctx.parts().features();
+            if keep {
+                let all = features.unstash != unstash;
+                features.stash.insert(idx.min(features.stash.len()), item);
+                if all {
+                    features.unstash = features.stash.len();
+                } else if scheduled {
+                    features.unstash += 1;
+                }
+                idx += 1;
+            }
+        }
+        let features = self.ctx.parts().features();
+        if features.stash_age.is_none() {
+            features.stash_age = Some(age);
+        }
+        true
+    }
+
+    /// Checks the timers and thresholds of features at the end of a pass.
+    /// Returns `true` if another pass is needed.
+    fn poll_features(&mut self, cx: &mut Context<'_>) -> bool {
+        // no message arrived in time
+        let features = self.ctx.parts().features();
+        if let Some(ref mut expect) = features.expect {
+            if expect.timer.as_mut().poll(cx).is_ready() {
+                let expect = features.expect.take().unwrap();
+                (expect.on_timeout)(&mut self.act, &mut self.ctx);
+                return true;
+            }
+        }
+
+        // messages stayed in the stash for too long
+        if self.stash_aged(cx) {
+            return true;
+        }
+
+        // nothing happened for the configured duration, stop. Drivers of
+        // context features do not keep the actor busy by themselves.
+        let features = self.ctx.parts().features();
+        if let Some(ref mut idle) = features.idle_timeout {
+            let internal = &features.internal;
+            let busy = !self.wait.is_empty()
+                || !features.ordered_futures.is_empty()
+                || self.items.iter().any(|item| !internal.contains(&item.0));
+            if idle.elapsed(busy, cx) {
+                idle.active = true;
+                self.ctx.parts().stop();
+                return true;
+            }
+        }
+
+        // fire once per upward crossing of the threshold
+        let len = self.items.len();
+        if let Some(ref mut high_water) = self.ctx.parts().features().high_water {
+            if len <= high_water.threshold {
+                high_water.exceeded = false;
+            } else if !high_water.exceeded {
+                high_water.exceeded = true;
+                (high_water.cb)(len);
+            }
+        }
+
+        #[cfg(feature = "metrics")]
+        {
+            let parts = self.ctx.parts();
+            let queued = parts.addr.queued();
+            if let Some(ref metrics) = parts.features().metrics {
+                metrics.gauge("items_pending", len as f64);
+                metrics.gauge("messages_queued", queued as f64);
+            }
+        }
+
+        // the timer is only armed once a wakeup arrives within the window
+        if let Some(ref mut coalesce) = self.ctx.parts().features().coalesce {
+            coalesce.last = Some(Instant::now());
+        }
+        false
+    }
+
     fn merge(&mut self) -> bool {
         let mut modified = false;
 
@@ -300,8 +1966,10 @@ where
         }
         if !parts.items.is_empty() {
             modified = true;
+            parts.running.extend(parts.items.iter().map(|item| item.0));
             self.items.extend(parts.items.drain(0..));
         }
+        parts.merged = (self.items.len(), self.wait.len());
         //
         if parts.flags.contains(ContextFlags::MB_CAP_CHANGED) {
             modified = true;
@@ -318,12 +1986,17 @@ where
         fn remove_item_by_handle<C>(
             items: &mut SmallVec<[Item<C>; 3]>,
             handle: &SpawnHandle,
+            ordered: bool,
         ) -> bool {
             let mut idx = 0;
             let mut removed = false;
             while idx < items.len() {
                 if &items[idx].0 == handle {
-                    items.swap_remove(idx);
+                    if ordered {
+                        items.remove(idx);
+                    } else {
+                        items.swap_remove(idx);
+                    }
                     removed = true;
                 } else {
                     idx += 1;
@@ -332,19 +2005,22 @@ where
             removed
         }
 
+        let ordered = self.ctx.parts().keep_order();
         while self.ctx.parts().handles.len() > 2 {
             let handle = self.ctx.parts().handles.pop().unwrap();
+            self.ctx.parts().forget(handle);
             // remove item from ContextFut.items in case associated item is already merged
-            if !remove_item_by_handle(&mut self.items, &handle) {
+            if !remove_item_by_handle(&mut self.items, &handle, ordered) {
                 // item is not merged into ContextFut.items yet,
                 // so it should be in ContextParts.items
-                remove_item_by_handle(&mut self.ctx.parts().items, &handle);
+                remove_item_by_handle(&mut self.ctx.parts().items, &handle, ordered);
             }
         }
+        self.sync_merged();
+        self.sync_running();
     }
 }
 
-#[doc(hidden)]
 impl<A, C> Future for ContextFut<A, C>
 where
     C: AsyncContextParts<A> + Unpin,
@@ -355,17 +2031,143 @@ where
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         let this = self.get_mut();
 
+        // a finished context must not run `started` or `stopped` again
+        debug_assert!(
+            !this
+                .ctx
+                .parts()
+                .flags
+                .contains(ContextFlags::STOPPED | ContextFlags::STARTED),
+            "ContextFut polled after the actor has stopped"
+        );
+
+        let parts = this.ctx.parts();
+        if !matches!(parts.waker, Some(ref waker) if waker.will_wake(cx.waker())) {
+            parts.waker = Some(cx.waker().clone());
+        }
+        if let Some(features) = parts.features.as_deref_mut() {
+            if let Some(ref last_poll) = features.last_poll {
+                last_poll.set(Instant::now());
+            }
+            if let Some((_, ref mut used)) = features.poll_budget {
+                *used = 0;
+            }
+        }
+
         if !this.ctx.parts().flags.contains(ContextFlags::STARTED) {
-            this.ctx.parts().flags.insert(ContextFlags::STARTED);
-            Actor::started(&mut this.act, &mut this.ctx);
+            // resolve prepared futures, the actor is not started if any fails
+            let parts = this.ctx.parts();
+            if let Some(features) = parts.features.as_deref_mut() {
+                let mut idx = 0;
+                while idx < features.prepared.len() {
+                    match features.prepared[idx].as_mut().poll(cx) {
+                        Poll::Ready(Some((id, val))) => {
+                            drop(features.prepared.swap_remove(idx));
+                            features.extensions.insert(id, val);
+                        }
+                        Poll::Ready(None) => {
+                            features.prepared.clear();
+                            parts.flags = ContextFlags::STOPPED | ContextFlags::STARTED;
+                            return Poll::Ready(());
+                        }
+                        Poll::Pending => idx += 1,
+                    }
+                }
+                if !features.prepared.is_empty() {
+                    return Poll::Pending;
+                }
+            }
+
+            this.start();
+        }
 
-            // check cancelled handles, just in case
-            if this.merge() {
-                this.clean_canceled_handle();
+        // wakeups until the end of the window are served by a single pass,
+        // stopping is not deferred, e.g. when the context is dropped
+        let parts = this.ctx.parts();
+        let stopping = parts
+            .flags
+            .intersects(ContextFlags::STOPPING | ContextFlags::STOPPED);
+        let coalesce = parts.features.as_mut().and_then(|f| f.coalesce.as_mut());
+        if let (Some(coalesce), false) = (coalesce, stopping) {
+            if let Some(last) = coalesce.last {
+                let deadline = last + coalesce.window;
+                if Instant::now() < deadline {
+                    if coalesce.timer.deadline() != deadline {
+                        coalesce.timer.as_mut().reset(deadline);
+                    }
+                    if coalesce.timer.as_mut().poll(cx).is_pending() {
+                        return Poll::Pending;
+                    }
+                }
             }
         }
 
         'outer: loop {
+            this.sync_merged();
+
+            // context is suspended, nothing gets polled until it is resumed
+            if !this.stopping() {
+                let features = this.ctx.parts().features.as_deref_mut();
+                if let Some(features) = features {
+                    if let Some(ref suspension) = features.suspension {
+                        suspension.task.register(cx.waker());
+                        if !suspension.resumed.load(Ordering::SeqCst) {
+                            return Poll::Pending;
+                        }
+                        features.suspension = None;
+                    }
+                }
+            }
+
+            // a joined shutdown began, drain and stop
+            if !this.stopping() {
+                let parts = this.ctx.parts();
+                if let Some(features) = parts.features.as_deref_mut() {
+                    let mut begun = None;
+                    for listener in features.shutdowns.iter_mut() {
+                        begun = listener.poll_begin(cx).or(begun);
+                    }
+                    if let (Some(timeout), None) = (begun, &features.drain_deadline) {
+                        parts.drain_and_stop(timeout);
+                    }
+                }
+            }
+
+            // drain deadline elapsed, drop remaining futures and stop
+            let features = this.ctx.parts().features.as_deref_mut();
+            if let Some(deadline) = features.and_then(|f| f.drain_deadline.as_mut()) {
+                if deadline.as_mut().poll(cx).is_ready() {
+                    this.ctx.parts().features().drain_deadline = None;
+
+                    // give writers a last chance to flush what they buffered
+                    this.merge();
+                    let flushers = std::mem::take(&mut this.ctx.parts().features().flushers);
+                    for (handle, item) in this.items.iter_mut() {
+                        if flushers.contains(handle) {
+                            this.ctx.parts().handles[1] = *handle;
+                            let _ = Pin::new(item).poll(&mut this.act, &mut this.ctx, cx);
+                        }
+                    }
+
+                    let parts = this.ctx.parts();
+                    parts.items.clear();
+                    parts.running.clear();
+                    let features = parts.features();
+                    features.quiesce.clear();
+                    features.control.clear();
+                    features.labels.clear();
+                    features.spawned_at.clear();
+                    features.deadlines.clear();
+                    features.internal.clear();
+                    features.completions.clear();
+                    if let Some(ref mut typenames) = features.typenames {
+                        typenames.clear();
+                    }
+                    parts.stop();
+                    this.items.clear();
+                }
+            }
+
             // check wait futures. order does matter
             // ctx.wait() always add to the back of the list
             // and we always have to check most recent future
@@ -377,20 +2179,93 @@ where
                 this.merge();
             }
 
-            // process mailbox
-            this.mailbox.poll(&mut this.act, &mut this.ctx, cx);
-            if !this.wait.is_empty() && !this.stopping() {
+            // hand messages dropped from a full stash back to the actor
+            if matches!(this.ctx.parts().features, Some(ref f) if !f.overflowed.is_empty()) {
+                let mut on_overflow = this.ctx.parts().features().on_overflow.take();
+                while let Some(msg) = this.ctx.parts().features().overflowed.pop_front() {
+                    if let Some(ref mut on_overflow) = on_overflow {
+                        on_overflow(&mut this.act, msg, &mut this.ctx);
+                    }
+                }
+                let features = this.ctx.parts().features();
+                if features.on_overflow.is_none() {
+                    features.on_overflow = on_overflow;
+                }
+            }
+
+            // redeliver unstashed messages before new ones
+            while this.ctx.parts().unstashing() && !this.stopping() {
+                let features = this.ctx.parts().features();
+                features.unstash -= 1;
+                let item = features.stash.pop_front().unwrap();
+                (item.deliver)(item.msg, &mut this.act, &mut this.ctx);
+                if !this.ctx.parts().wait.is_empty() || this.suspended() {
+                    this.merge();
+                    continue 'outer;
+                }
+            }
+
+            // control streams go ahead of the mailbox
+            let control =
+                matches!(this.ctx.parts().features, Some(ref f) if !f.control.is_empty());
+            if control && !this.stopping() {
+                let mut control = std::mem::take(&mut this.ctx.parts().features().control);
+                let mut idx = 0;
+                while idx < control.len() {
+                    let fut = control[idx].as_mut();
+                    if fut.poll(&mut this.act, &mut this.ctx, cx).is_ready() {
+                        drop(control.swap_remove(idx));
+                    } else {
+                        idx += 1;
+                    }
+                }
+                let features = this.ctx.parts().features();
+                control.append(&mut features.control);
+                features.control = control;
+                if !this.ctx.parts().wait.is_empty() || this.suspended() {
+                    this.merge();
+                    continue 'outer;
+                }
+            }
+
+            // process mailbox, unless context waits for its items to complete
+            if this.poll_mailbox_at(MailboxPriority::Normal) {
+                this.poll_mailbox(cx);
+            }
+            if (!this.wait.is_empty() || this.suspended() || this.ctx.parts().unstashing())
+                && !this.stopping()
+            {
                 continue;
             }
 
+            // let scheduler decide the poll order of this pass
+            let features = this.ctx.parts().features.as_deref_mut();
+            if let Some(features) = features.filter(|f| f.scheduler.is_some()) {
+                let now = Instant::now();
+                let scheduler = features.scheduler.as_mut().unwrap();
+                let (labels, spawned_at) = (&features.labels, &features.spawned_at);
+                this.items.sort_by_cached_key(|item| {
+                    let info = ItemInfo {
+                        handle: item.0,
+                        label: labels.get(&item.0).map(String::as_str),
+                        age: spawned_at
+                            .get(&item.0)
+                            .map_or(Duration::from_secs(0), |at| now - *at),
+                    };
+                    Reverse(scheduler.priority(&info))
+                });
+                this.sync_running();
+            }
+
             // process items
             let mut idx = 0;
-            while idx < this.items.len() && !this.stopping() {
+            while idx < this.items.len() && !this.stopping() && !this.suspended() {
                 this.ctx.parts().handles[1] = this.items[idx].0;
                 match Pin::new(&mut this.items[idx].1).poll(&mut this.act, &mut this.ctx, cx) {
                     Poll::Pending => {
-                        // got new waiting item. merge
-                        if this.ctx.waiting() {
+                        // got new waiting item. merge, items spawned in this
+                        // pass are polled in the next one
+                        if !this.ctx.parts().wait.is_empty() {
                             this.merge();
                         }
 
@@ -411,8 +2286,9 @@ where
                             // future and prevents polling
                             // of other items
                             let next = this.items.len() - 1;
-                            if idx != next {
+                            if idx != next && !this.ctx.parts().keep_order() {
                                 this.items.swap(idx, next);
+                                this.ctx.parts().running.swap(idx, next);
                             }
                             continue 'outer;
                         } else {
@@ -420,10 +2296,34 @@ where
                         }
                     }
                     Poll::Ready(()) => {
-                        this.items.swap_remove(idx);
+                        let handle = this.items[idx].0;
+                        let parts = this.ctx.parts();
+                        debug_assert_eq!(parts.running[idx], handle);
+                        let mut on_complete = None;
+                        if let Some(features) = parts.features.as_deref_mut() {
+                            on_complete = features.completions.remove(&handle);
+                            if !features.internal.contains(&handle) {
+                                features.mark_active();
+                            }
+                            features.forget(handle);
+                        }
+                        #[cfg(feature = "metrics")]
+                        parts.incr_metric("items_completed", 1);
+                        if parts.keep_order() {
+                            parts.running.remove(idx);
+                            this.items.remove(idx);
+                        } else {
+                            parts.running.swap_remove(idx);
+                            this.items.swap_remove(idx);
+                        }
+                        this.sync_merged();
+                        if let Some(cb) = on_complete {
+                            cb(&mut this.act, &mut this.ctx);
+                        }
 
-                        // got new waiting item. merge
-                        if this.ctx.waiting() {
+                        // got new waiting item. merge, items spawned in this
+                        // pass are polled in the next one
+                        if !this.ctx.parts().wait.is_empty() {
                             this.merge();
                         }
 
@@ -433,8 +2333,29 @@ where
                         }
                     }
                 }
+
+                // high priority mailbox preempts remaining items
+                if this.poll_mailbox_at(MailboxPriority::High) && !this.stopping() {
+                    this.poll_mailbox(cx);
+                    if (!this.wait.is_empty() || this.suspended()) && !this.stopping() {
+                        continue 'outer;
+                    }
+                }
             }
             this.ctx.parts().handles[1] = SpawnHandle::default();
+            this.sync_merged();
+
+            // low priority mailbox is processed after items
+            if this.poll_mailbox_at(MailboxPriority::Low) && !this.stopping() {
+                this.poll_mailbox(cx);
+                if !this.wait.is_empty() && !this.stopping() {
+                    continue;
+                }
+            }
+
+            if this.suspended() && !this.stopping() {
+                continue;
+            }
 
             // merge returns true if context contains new items or handles to be cancelled
             if this.merge() && !this.ctx.parts().flags.contains(ContextFlags::STOPPING) {
@@ -448,20 +2369,48 @@ where
                 continue;
             }
 
+            // messages were unstashed or dropped while processing items
+            let parts = this.ctx.parts();
+            let stashed = matches!(parts.features, Some(ref f) if f.unstash > 0 || !f.overflowed.is_empty());
+            if stashed && !this.stopping() {
+                continue;
+            }
+
+            // all items are completed, notify quiesce callbacks and resume mailbox
+            let quiesce =
+                matches!(this.ctx.parts().features, Some(ref f) if !f.quiesce.is_empty());
+            if quiesce && this.items.is_empty() && !this.stopping() {
+                let features = this.ctx.parts().features();
+                features.drain_deadline = None;
+                let callbacks = std::mem::take(&mut features.quiesce);
+                for f in callbacks {
+                    f(&mut this.act, &mut this.ctx);
+                }
+                continue;
+            }
+
             // check state
             if this.ctx.parts().flags.contains(ContextFlags::RUNNING) {
                 // possible stop condition
-                if !this.alive()
-                    && Actor::stopping(&mut this.act, &mut this.ctx) == Running::Stop
-                {
-                    this.ctx.parts().flags = ContextFlags::STOPPED | ContextFlags::STARTED;
-                    Actor::stopped(&mut this.act, &mut this.ctx);
-                    return Poll::Ready(());
+                if !this.alive() {
+                    let features = this.ctx.parts().features.as_deref_mut();
+                    if let Some(features) = features.filter(|f| f.perpetual) {
+                        features.perpetual = false;
+                        error!(
+                            "Perpetual actor {} has no addresses and no futures left",
+                            std::any::type_name::<A>()
+                        );
+                    }
+                    if this.traced("stopping", Actor::stopping) == Running::Stop {
+                        this.ctx.parts().flags = ContextFlags::STOPPED | ContextFlags::STARTED;
+                        this.traced("stopped", Actor::stopped);
+                        return Poll::Ready(());
+                    }
                 }
             } else if this.ctx.parts().flags.contains(ContextFlags::STOPPING) {
-                if Actor::stopping(&mut this.act, &mut this.ctx) == Running::Stop {
+                if this.traced("stopping", Actor::stopping) == Running::Stop {
                     this.ctx.parts().flags = ContextFlags::STOPPED | ContextFlags::STARTED;
-                    Actor::stopped(&mut this.act, &mut this.ctx);
+                    this.traced("stopped", Actor::stopped);
                     return Poll::Ready(());
                 } else {
                     this.ctx.parts().flags.remove(ContextFlags::STOPPING);
@@ -469,10 +2418,16 @@ where
                     continue;
                 }
             } else if this.ctx.parts().flags.contains(ContextFlags::STOPPED) {
-                Actor::stopped(&mut this.act, &mut this.ctx);
+                this.ctx.parts().flags.insert(ContextFlags::STARTED);
+                this.traced("stopped", Actor::stopped);
                 return Poll::Ready(());
             }
 
+            // opt-in checks at the end of a pass
+            if this.ctx.parts().features.is_some() && this.poll_features(cx) {
+                continue;
+            }
+
             return Poll::Pending;
         }
     }
@@ -1,5 +1,8 @@
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
 use std::future::Future;
 use std::pin::Pin;
+use std::rc::Rc;
 use std::task::{self, Poll};
 use std::time::Duration;
 
@@ -7,7 +10,8 @@ use futures_core::{ready, stream::Stream};
 use pin_project_lite::pin_project;
 
 use crate::actor::{Actor, ActorContext, AsyncContext};
-use crate::clock::Sleep;
+use crate::clock::{sleep_until, Instant, Sleep};
+use crate::contextimpl::{AsyncContextParts, Callback};
 use crate::fut::ActorFuture;
 use crate::handler::{Handler, Message, MessageResponse};
 
@@ -158,3 +162,479 @@ where
         Poll::Ready(())
     }
 }
+
+/// Drives futures added with `Context::add_ordered_future` and delivers
+/// their results in the order the futures were added.
+pub(crate) struct ActorOrderedFuturesItem;
+
+impl<A> ActorFuture<A> for ActorOrderedFuturesItem
+where
+    A: Actor,
+    A::Context: AsyncContextParts<A>,
+{
+    type Output = ();
+
+    fn poll(
+        self: Pin<&mut Self>,
+        act: &mut A,
+        ctx: &mut A::Context,
+        task: &mut task::Context<'_>,
+    ) -> Poll<Self::Output> {
+        loop {
+            for item in ctx.parts().ordered_futures().iter_mut() {
+                if item.1.is_none() {
+                    if let Poll::Ready(f) = item.0.as_mut().poll(task) {
+                        item.1 = Some(f);
+                    }
+                }
+            }
+
+            // later results are held until all earlier futures complete
+            let mut delivered = false;
+            while matches!(ctx.parts().ordered_futures().front(), Some((_, Some(_)))) {
+                let (_, f) = ctx.parts().ordered_futures().pop_front().unwrap();
+                f.unwrap()(act, ctx);
+                delivered = true;
+                if ctx.waiting() {
+                    return Poll::Pending;
+                }
+            }
+
+            if ctx.parts().ordered_futures().is_empty() {
+                return Poll::Ready(());
+            }
+            if !delivered {
+                return Poll::Pending;
+            }
+        }
+    }
+}
+
+/// Drives futures added with `AsyncContext::spawn_join` and passes their
+/// results to a callback once all of them completed.
+pub(crate) struct ActorJoinItem<A: Actor, Fut: ActorFuture<A>, F> {
+    futs: Vec<Option<Pin<Box<Fut>>>>,
+    results: Vec<Fut::Output>,
+    f: Option<F>,
+}
+
+impl<A, Fut, F> ActorJoinItem<A, Fut, F>
+where
+    A: Actor,
+    Fut: ActorFuture<A>,
+{
+    pub fn new<I: IntoIterator<Item = Fut>>(futs: I, f: F) -> Self {
+        let futs: Vec<_> = futs.into_iter().map(|fut| Some(Box::pin(fut))).collect();
+        Self {
+            results: Vec::with_capacity(futs.len()),
+            futs,
+            f: Some(f),
+        }
+    }
+}
+
+impl<A: Actor, Fut: ActorFuture<A>, F> Unpin for ActorJoinItem<A, Fut, F> {}
+
+impl<A, Fut, F> ActorFuture<A> for ActorJoinItem<A, Fut, F>
+where
+    A: Actor,
+    Fut: ActorFuture<A>,
+    F: FnOnce(&mut A, &mut A::Context, Vec<Fut::Output>),
+{
+    type Output = ();
+
+    fn poll(
+        self: Pin<&mut Self>,
+        act: &mut A,
+        ctx: &mut A::Context,
+        task: &mut task::Context<'_>,
+    ) -> Poll<Self::Output> {
+        let this = self.get_mut();
+
+        for slot in this.futs.iter_mut() {
+            if let Some(fut) = slot {
+                if let Poll::Ready(res) = fut.as_mut().poll(act, ctx, task) {
+                    this.results.push(res);
+                    *slot = None;
+                }
+            }
+        }
+
+        if this.results.len() < this.futs.len() {
+            return Poll::Pending;
+        }
+
+        let f = this.f.take().expect("ActorJoinItem polled after finish");
+        f(act, ctx, std::mem::take(&mut this.results));
+        Poll::Ready(())
+    }
+}
+
+/// Shared state of futures spawned with `Context::spawn_limited`, or added
+/// with `Context::add_fut_stream`.
+pub(crate) struct Limiter {
+    max: usize,
+    running: usize,
+    // tickets of futures waiting for a slot
+    queue: VecDeque<u64>,
+    next: u64,
+    waker: Option<task::Waker>,
+}
+
+impl Default for Limiter {
+    fn default() -> Self {
+        Limiter {
+            max: usize::MAX,
+            running: 0,
+            queue: VecDeque::new(),
+            next: 0,
+            waker: None,
+        }
+    }
+}
+
+impl Limiter {
+    pub(crate) fn set_max(&mut self, max: usize) {
+        self.max = max;
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+
+    fn release(&mut self) {
+        self.running -= 1;
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+/// Future spawned with `Context::spawn_limited`, polled only once the
+/// limiter has a free slot for it.
+pub(crate) struct ActorLimitedItem<A: Actor> {
+    fut: Pin<Box<dyn ActorFuture<A, Output = ()>>>,
+    limiter: Rc<RefCell<Limiter>>,
+    ticket: u64,
+    running: bool,
+    done: bool,
+}
+
+impl<A: Actor> ActorLimitedItem<A> {
+    pub fn new<F>(fut: F, limiter: Rc<RefCell<Limiter>>) -> Self
+    where
+        F: ActorFuture<A, Output = ()> + 'static,
+    {
+        let ticket = {
+            let mut limiter = limiter.borrow_mut();
+            let ticket = limiter.next;
+            limiter.next += 1;
+            limiter.queue.push_back(ticket);
+            ticket
+        };
+        Self {
+            fut: Box::pin(fut),
+            limiter,
+            ticket,
+            running: false,
+            done: false,
+        }
+    }
+}
+
+impl<A: Actor> ActorFuture<A> for ActorLimitedItem<A> {
+    type Output = ();
+
+    fn poll(
+        self: Pin<&mut Self>,
+        act: &mut A,
+        ctx: &mut A::Context,
+        task: &mut task::Context<'_>,
+    ) -> Poll<Self::Output> {
+        let this = self.get_mut();
+
+        if !this.running {
+            let mut limiter = this.limiter.borrow_mut();
+            if limiter.running >= limiter.max || limiter.queue.front() != Some(&this.ticket) {
+                limiter.waker = Some(task.waker().clone());
+                return Poll::Pending;
+            }
+            limiter.queue.pop_front();
+            limiter.running += 1;
+            this.running = true;
+            // next queued future may already have been polled in this pass
+            if limiter.running < limiter.max && !limiter.queue.is_empty() {
+                task.waker().wake_by_ref();
+            }
+        }
+
+        ready!(this.fut.as_mut().poll(act, ctx, task));
+        this.done = true;
+        this.limiter.borrow_mut().release();
+        Poll::Ready(())
+    }
+}
+
+impl<A: Actor> Drop for ActorLimitedItem<A> {
+    fn drop(&mut self) {
+        // cancelled, free the slot or leave the queue
+        if self.running && !self.done {
+            self.limiter.borrow_mut().release();
+        } else if !self.running {
+            let mut limiter = self.limiter.borrow_mut();
+            limiter.queue.retain(|ticket| *ticket != self.ticket);
+            if let Some(waker) = limiter.waker.take() {
+                waker.wake();
+            }
+        }
+    }
+}
+
+/// Handles a single message of a weighted inbox, `Ready(None)` once the
+/// inbox is closed and empty.
+pub(crate) type PollInbox<A> = Box<
+    dyn FnMut(&mut A, &mut <A as Actor>::Context, &mut task::Context<'_>) -> Poll<Option<()>>,
+>;
+
+pub(crate) struct Inbox<A: Actor> {
+    weight: u32,
+    // messages left of the share of the current round
+    deficit: u32,
+    poll: PollInbox<A>,
+}
+
+impl<A: Actor> Inbox<A> {
+    pub fn new(weight: u32, poll: PollInbox<A>) -> Self {
+        Inbox {
+            weight,
+            deficit: 0,
+            poll,
+        }
+    }
+}
+
+/// Weighted inboxes of a context, shared between the context and the
+/// driving `ActorWeightedInboxes` item.
+pub(crate) struct Inboxes<A: Actor> {
+    added: Vec<Inbox<A>>,
+    running: bool,
+}
+
+impl<A: Actor> Default for Inboxes<A> {
+    fn default() -> Self {
+        Inboxes {
+            added: Vec::new(),
+            running: false,
+        }
+    }
+}
+
+impl<A: Actor> Inboxes<A> {
+    /// Adds an inbox, returns `true` if a new driver has to be spawned.
+    pub fn add(&mut self, inbox: Inbox<A>) -> bool {
+        self.added.push(inbox);
+        !std::mem::replace(&mut self.running, true)
+    }
+}
+
+/// Drains weighted inboxes with deficit round-robin: in every round each
+/// inbox is credited `weight` messages and handles messages until the credit
+/// is used up or the inbox is empty. An empty inbox loses its credit, credit
+/// left when the actor starts waiting is kept, and the round resumes at the
+/// same inbox on the next poll.
+pub(crate) struct ActorWeightedInboxes<A: Actor> {
+    shared: Rc<RefCell<Inboxes<A>>>,
+    inboxes: Vec<Inbox<A>>,
+    // inbox the current round resumes at
+    current: usize,
+}
+
+impl<A: Actor> ActorWeightedInboxes<A> {
+    pub fn new(shared: Rc<RefCell<Inboxes<A>>>) -> Self {
+        Self {
+            shared,
+            inboxes: Vec::new(),
+            current: 0,
+        }
+    }
+}
+
+impl<A> ActorFuture<A> for ActorWeightedInboxes<A>
+where
+    A: Actor,
+    A::Context: AsyncContext<A>,
+{
+    type Output = ();
+
+    fn poll(
+        self: Pin<&mut Self>,
+        act: &mut A,
+        ctx: &mut A::Context,
+        task: &mut task::Context<'_>,
+    ) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        this.inboxes.append(&mut this.shared.borrow_mut().added);
+
+        // inboxes before a resumed round may have more messages
+        let mut idx = std::mem::take(&mut this.current);
+        let mut more = idx > 0;
+        while idx < this.inboxes.len() {
+            let inbox = &mut this.inboxes[idx];
+            if inbox.deficit == 0 {
+                inbox.deficit = inbox.weight;
+            }
+            let mut closed = false;
+            let mut empty = false;
+            while inbox.deficit > 0 {
+                match (inbox.poll)(act, ctx, task) {
+                    Poll::Ready(Some(())) => inbox.deficit -= 1,
+                    Poll::Ready(None) => {
+                        closed = true;
+                        break;
+                    }
+                    Poll::Pending => {
+                        inbox.deficit = 0;
+                        empty = true;
+                        break;
+                    }
+                }
+                if ctx.waiting() {
+                    // an inbox which used up its credit is done for this round
+                    this.current = if inbox.deficit == 0 { idx + 1 } else { idx };
+                    return Poll::Pending;
+                }
+            }
+
+            if closed {
+                this.inboxes.remove(idx);
+            } else {
+                // inbox used its whole share, it may have more messages
+                more |= !empty;
+                idx += 1;
+            }
+        }
+
+        let mut shared = this.shared.borrow_mut();
+        if this.inboxes.is_empty() && shared.added.is_empty() {
+            shared.running = false;
+            return Poll::Ready(());
+        }
+        if more || !shared.added.is_empty() {
+            task.waker().wake_by_ref();
+        }
+        Poll::Pending
+    }
+}
+
+impl<A: Actor> Drop for ActorWeightedInboxes<A> {
+    fn drop(&mut self) {
+        // dropped inboxes are closed, later inboxes need a new driver
+        if let Ok(mut shared) = self.shared.try_borrow_mut() {
+            shared.running = false;
+        }
+    }
+}
+
+pin_project! {
+    /// Timer based item, counted in the armed timers of its context while it
+    /// is alive.
+    pub(crate) struct ActorTimerItem<F> {
+        #[pin]
+        fut: F,
+        armed: ArmedTimer,
+    }
+}
+
+impl<F> ActorTimerItem<F> {
+    pub fn new(fut: F, timers: Rc<Cell<usize>>) -> Self {
+        ActorTimerItem {
+            fut,
+            armed: ArmedTimer::new(timers),
+        }
+    }
+}
+
+/// Counts a timer in the armed timers of a context while it is alive.
+pub(crate) struct ArmedTimer(Rc<Cell<usize>>);
+
+impl ArmedTimer {
+    pub fn new(timers: Rc<Cell<usize>>) -> Self {
+        timers.set(timers.get() + 1);
+        ArmedTimer(timers)
+    }
+}
+
+impl Drop for ArmedTimer {
+    fn drop(&mut self) {
+        self.0.set(self.0.get() - 1);
+    }
+}
+
+impl<A, F> ActorFuture<A> for ActorTimerItem<F>
+where
+    A: Actor,
+    F: ActorFuture<A, Output = ()>,
+{
+    type Output = ();
+
+    fn poll(
+        self: Pin<&mut Self>,
+        act: &mut A,
+        ctx: &mut A::Context,
+        task: &mut task::Context<'_>,
+    ) -> Poll<Self::Output> {
+        self.project().fut.poll(act, ctx, task)
+    }
+}
+
+/// Future dropped once its deadline passes, the deadline can be moved
+/// through the shared cell.
+pub(crate) struct ActorTtlItem<A: Actor> {
+    fut: Pin<Box<dyn ActorFuture<A, Output = ()>>>,
+    timer: Pin<Box<Sleep>>,
+    deadline: Rc<Cell<Instant>>,
+    on_expired: Option<Callback<A>>,
+}
+
+impl<A: Actor> ActorTtlItem<A> {
+    pub fn new<F>(fut: F, deadline: Rc<Cell<Instant>>, on_expired: Callback<A>) -> Self
+    where
+        F: ActorFuture<A, Output = ()> + 'static,
+    {
+        ActorTtlItem {
+            fut: Box::pin(fut),
+            timer: Box::pin(sleep_until(deadline.get())),
+            deadline,
+            on_expired: Some(on_expired),
+        }
+    }
+}
+
+impl<A: Actor> ActorFuture<A> for ActorTtlItem<A> {
+    type Output = ();
+
+    fn poll(
+        self: Pin<&mut Self>,
+        act: &mut A,
+        ctx: &mut A::Context,
+        task: &mut task::Context<'_>,
+    ) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        if this.fut.as_mut().poll(act, ctx, task).is_ready() {
+            return Poll::Ready(());
+        }
+
+        loop {
+            ready!(this.timer.as_mut().poll(task));
+            // the deadline was extended in the meantime
+            let deadline = this.deadline.get();
+            if deadline > this.timer.deadline() {
+                this.timer.as_mut().reset(deadline);
+                continue;
+            }
+            if let Some(on_expired) = this.on_expired.take() {
+                on_expired(act, ctx);
+            }
+            return Poll::Ready(());
+        }
+    }
+}
@@ -0,0 +1,50 @@
+use std::any::Any;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use pin_project_lite::pin_project;
+
+use crate::actor::Actor;
+use crate::fut::ActorFuture;
+
+pin_project! {
+    /// Future for the `catch_unwind` combinator, turns a panic while polling
+    /// the inner future into an error.
+    ///
+    /// This is created by the `ActorFuture::catch_unwind()` method.
+    #[derive(Debug)]
+    #[must_use = "futures do nothing unless polled"]
+    pub struct CatchUnwind<F> {
+        #[pin]
+        fut: F,
+    }
+}
+
+impl<F> CatchUnwind<F> {
+    pub(super) fn new(fut: F) -> Self {
+        Self { fut }
+    }
+}
+
+impl<F, A> ActorFuture<A> for CatchUnwind<F>
+where
+    F: ActorFuture<A>,
+    A: Actor,
+{
+    type Output = Result<F::Output, Box<dyn Any + Send>>;
+
+    fn poll(
+        self: Pin<&mut Self>,
+        act: &mut A,
+        ctx: &mut A::Context,
+        task: &mut Context<'_>,
+    ) -> Poll<Self::Output> {
+        let fut = self.project().fut;
+        match catch_unwind(AssertUnwindSafe(|| fut.poll(act, ctx, task))) {
+            Ok(Poll::Ready(res)) => Poll::Ready(Ok(res)),
+            Ok(Poll::Pending) => Poll::Pending,
+            Err(err) => Poll::Ready(Err(err)),
+        }
+    }
+}
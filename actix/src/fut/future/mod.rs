@@ -9,12 +9,14 @@ use std::{
 
 use pin_project_lite::pin_project;
 
+pub use catch_unwind::CatchUnwind;
 pub use map::Map;
 pub use then::Then;
 pub use timeout::Timeout;
 
 use crate::actor::Actor;
 
+mod catch_unwind;
 mod either;
 mod map;
 pub mod result;
@@ -158,6 +160,22 @@ pub trait ActorFutureExt<A: Actor>: ActorFuture<A> {
         Timeout::new(self, timeout)
     }
 
+    /// Catch panics raised while polling this future.
+    ///
+    /// A panic is returned as `Err` with the panic payload, keeping the actor
+    /// context alive instead of unwinding it. This isolates bugs in
+    /// third-party futures from crashing the actor.
+    ///
+    /// The actor and its context are asserted to be unwind safe. A panic may
+    /// leave the actor in an inconsistent state, so the error should be
+    /// treated as fatal for whatever state the future was updating.
+    fn catch_unwind(self) -> CatchUnwind<Self>
+    where
+        Self: Sized,
+    {
+        CatchUnwind::new(self)
+    }
+
     /// Wrap the future in a Box, pinning it.
     ///
     /// A shortcut for wrapping in [`Box::pin`](std::boxed::Box::pin).
@@ -0,0 +1,77 @@
+use std::collections::{HashSet, VecDeque};
+use std::hash::Hash;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures_core::ready;
+use pin_project_lite::pin_project;
+
+use crate::actor::Actor;
+use crate::fut::ActorStream;
+
+pin_project! {
+    /// Stream for the [`dedup`](super::ActorStreamExt::dedup) method.
+    #[must_use = "streams do nothing unless polled"]
+    pub struct Dedup<S, K, F> {
+        #[pin]
+        stream: S,
+        key: F,
+        window: usize,
+        // recently seen keys, least recently seen first
+        recent: VecDeque<K>,
+        seen: HashSet<K>,
+    }
+}
+
+pub(super) fn new<S, K, F>(stream: S, window: usize, key: F) -> Dedup<S, K, F> {
+    Dedup {
+        stream,
+        key,
+        window,
+        recent: VecDeque::with_capacity(window),
+        seen: HashSet::with_capacity(window),
+    }
+}
+
+impl<S, K, F, A> ActorStream<A> for Dedup<S, K, F>
+where
+    S: ActorStream<A>,
+    K: Hash + Eq + Clone,
+    F: FnMut(&S::Item) -> K,
+    A: Actor,
+{
+    type Item = S::Item;
+
+    fn poll_next(
+        self: Pin<&mut Self>,
+        act: &mut A,
+        ctx: &mut A::Context,
+        task: &mut Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        let mut this = self.project();
+
+        while let Some(item) = ready!(this.stream.as_mut().poll_next(act, ctx, task)) {
+            let key = (this.key)(&item);
+            if this.seen.contains(&key) {
+                // a duplicate counts as a recent sighting
+                if let Some(pos) = this.recent.iter().position(|k| *k == key) {
+                    let key = this.recent.remove(pos).unwrap();
+                    this.recent.push_back(key);
+                }
+                continue;
+            }
+            if *this.window == 0 {
+                return Poll::Ready(Some(item));
+            }
+            if this.recent.len() == *this.window {
+                if let Some(oldest) = this.recent.pop_front() {
+                    this.seen.remove(&oldest);
+                }
+            }
+            this.seen.insert(key.clone());
+            this.recent.push_back(key);
+            return Poll::Ready(Some(item));
+        }
+        Poll::Ready(None)
+    }
+}
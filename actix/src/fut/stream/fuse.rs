@@ -0,0 +1,60 @@
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures_core::ready;
+use pin_project_lite::pin_project;
+
+use crate::actor::Actor;
+use crate::fut::ActorStream;
+
+pin_project! {
+    /// Stream for the [`fuse`](super::ActorStreamExt::fuse) method.
+    #[derive(Debug)]
+    #[must_use = "streams do nothing unless polled"]
+    pub struct Fuse<S> {
+        #[pin]
+        stream: S,
+        done: bool,
+    }
+}
+
+pub(super) fn new<S>(stream: S) -> Fuse<S> {
+    Fuse {
+        stream,
+        done: false,
+    }
+}
+
+impl<S> Fuse<S> {
+    /// Returns `true` if the underlying stream has finished.
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+}
+
+impl<S, A> ActorStream<A> for Fuse<S>
+where
+    S: ActorStream<A>,
+    A: Actor,
+{
+    type Item = S::Item;
+
+    fn poll_next(
+        self: Pin<&mut Self>,
+        act: &mut A,
+        ctx: &mut A::Context,
+        task: &mut Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        let this = self.project();
+
+        if *this.done {
+            return Poll::Ready(None);
+        }
+
+        let item = ready!(this.stream.poll_next(act, ctx, task));
+        if item.is_none() {
+            *this.done = true;
+        }
+        Poll::Ready(item)
+    }
+}
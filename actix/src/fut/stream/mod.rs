@@ -1,4 +1,5 @@
 use std::{
+    hash::Hash,
     marker::PhantomData,
     pin::Pin,
     task::{Context, Poll},
@@ -9,8 +10,10 @@ use futures_core::stream::Stream;
 use pin_project_lite::pin_project;
 
 pub use collect::Collect;
+pub use dedup::Dedup;
 pub use finish::Finish;
 pub use fold::Fold;
+pub use fuse::Fuse;
 pub use map::Map;
 pub use skip_while::SkipWhile;
 pub use take_while::TakeWhile;
@@ -22,8 +25,10 @@ use crate::actor::Actor;
 use super::future::ActorFuture;
 
 mod collect;
+mod dedup;
 mod finish;
 mod fold;
+mod fuse;
 mod map;
 mod skip_while;
 mod take_while;
@@ -152,6 +157,35 @@ pub trait ActorStreamExt<A: Actor>: ActorStream<A> {
         Collect::new(self)
     }
 
+    /// Fuse a stream such that [`poll_next`](ActorStream::poll_next) will
+    /// never again be called once it has finished.
+    ///
+    /// Polling a stream after it returned `None` is unspecified behaviour for
+    /// many stream implementations. The fused stream keeps returning `None`
+    /// instead, so it can be polled again safely.
+    fn fuse(self) -> Fuse<Self>
+    where
+        Self: Sized,
+    {
+        fuse::new(self)
+    }
+
+    /// Skip items whose key was seen among the last `window` distinct keys.
+    ///
+    /// `key` extracts the deduplication key of an item. Keys are kept in a
+    /// least recently seen order, so a key that keeps being repeated remains
+    /// in the window. This makes consuming an at-least-once delivery queue
+    /// effectively exactly-once for recent duplicates. A `window` of zero
+    /// disables deduplication.
+    fn dedup<K, F>(self, window: usize, key: F) -> Dedup<Self, K, F>
+    where
+        K: Hash + Eq + Clone,
+        F: FnMut(&Self::Item) -> K,
+        Self: Sized,
+    {
+        dedup::new(self, window, key)
+    }
+
     /// Transforms a stream to a future that resolves when stream finishes.
     fn finish(self) -> Finish<Self>
     where
@@ -1,4 +1,4 @@
-use std::{fmt, future::Future, pin::Pin, sync::Arc};
+use std::{fmt, future::Future, pin::Pin, rc::Rc, sync::Arc};
 
 pub use tokio::sync::oneshot::Sender as OneshotSender;
 
@@ -26,6 +26,14 @@ where
 
     /// This method is called for every message received by this actor.
     fn handle(&mut self, msg: M, ctx: &mut Self::Context) -> Self::Result;
+
+    /// This method is called instead of `handle` for a message sent with
+    /// [`Addr::send_with_deadline`](crate::Addr::send_with_deadline) that
+    /// was not handled before its deadline.
+    ///
+    /// The sender has already received `MailboxError::Timeout`. By default
+    /// the message is dropped.
+    fn expired(&mut self, msg: M, ctx: &mut Self::Context) {}
 }
 
 /// Represent message that can be handled by an actor.
@@ -43,6 +51,19 @@ where
     type Result = M::Result;
 }
 
+/// Allow users to use `Rc<M>` as a message without having to re-impl `Message`
+///
+/// `Rc` is not `Send`, so it can not be sent through an `Addr`. It is meant
+/// for delivery paths that stay on one thread, like `AsyncContext::notify`
+/// and `Context::weighted_inbox`, to share one large payload between many
+/// actors without cloning it.
+impl<M> Message for Rc<M>
+where
+    M: Message,
+{
+    type Result = M::Result;
+}
+
 /// Allow users to use `Box<M>` as a message without having to re-impl `Message`
 impl<M> Message for Box<M>
 where
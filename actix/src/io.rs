@@ -1,18 +1,28 @@
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
+use std::future::Future;
 use std::marker::PhantomData;
 use std::ops::DerefMut;
 use std::pin::Pin;
 use std::rc::Rc;
 use std::task::{Context, Poll};
-use std::{collections::VecDeque, io, task};
+use std::time::Duration;
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fmt, io, task,
+};
 
 use bitflags::bitflags;
 use bytes::BytesMut;
+use futures_core::stream::Stream;
 use futures_sink::Sink;
+use log::error;
 use tokio::io::{AsyncWrite, AsyncWriteExt};
 use tokio_util::codec::Encoder;
 
 use crate::actor::{Actor, ActorContext, AsyncContext, Running, SpawnHandle};
+use crate::clock::{sleep, Instant, Sleep};
+use crate::contextimpl::AsyncContextParts;
+use crate::contextitems::ArmedTimer;
 use crate::fut::ActorFuture;
 
 /// A helper trait for write handling.
@@ -405,12 +415,26 @@ impl<I, T: AsyncWrite + Unpin, U: Encoder<I>> Drop for FramedWrite<I, T, U> {
         let inner = self.inner.0.borrow_mut();
         if !inner.buffer.is_empty() {
             // Results must be ignored during drop, as the errors cannot be handled meaningfully
-            let _ = async_writer.write(&inner.buffer);
-            let _ = async_writer.flush();
+            drop(async_writer.write(&inner.buffer));
+            drop(async_writer.flush());
         }
     }
 }
 
+/// What happens to an item written while the buffer of a [`SinkWrite`] is
+/// full.
+///
+/// Set with [`SinkWrite::set_buffer_limit`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SinkOverflow {
+    /// The oldest buffered item is dropped to make room.
+    DropOldest,
+    /// The most recently buffered item is dropped to make room.
+    DropNewest,
+    /// The item is returned by `write`.
+    Reject,
+}
+
 /// A wrapper for the `Sink` type.
 pub struct SinkWrite<I, S: Sink<I> + Unpin> {
     inner: Rc<RefCell<InnerSinkWrite<I, S>>>,
@@ -420,7 +444,46 @@ impl<I: 'static, S: Sink<I> + Unpin + 'static> SinkWrite<I, S> {
     pub fn new<A, C>(sink: S, ctxt: &mut C) -> Self
     where
         A: Actor<Context = C> + WriteHandler<S::Error>,
-        C: AsyncContext<A>,
+        C: AsyncContextParts<A>,
+    {
+        let inner = Rc::new(RefCell::new(InnerSinkWrite {
+            _i: PhantomData,
+            closing_flag: Flags::empty(),
+            sink,
+            task: None,
+            handle: SpawnHandle::default(),
+            buffer: VecDeque::new(),
+            reader: None,
+            keepalive: None,
+            batch: false,
+            dirty: false,
+            ordered: BTreeMap::new(),
+            next_seq: 0,
+            limit: None,
+            error: None,
+            timers: ctxt.parts().armed_timers(),
+        }));
+
+        let handle = ctxt.spawn(SinkWriteFuture {
+            inner: inner.clone(),
+            _events: PhantomData::<fn() -> ActorEvents>,
+        });
+
+        ctxt.parts().flush_on_drain(handle);
+        inner.borrow_mut().handle = handle;
+        SinkWrite { inner }
+    }
+
+    /// Creates a writer which forwards items to a plain sink.
+    ///
+    /// Unlike [`new`](Self::new), the actor does not have to implement
+    /// [`WriteHandler`]. Sink errors are logged and stop the writer, and
+    /// finishing the writer does not stop the actor.
+    pub fn forward<A, C>(sink: S, ctxt: &mut C) -> Self
+    where
+        A: Actor<Context = C>,
+        C: AsyncContextParts<A>,
+        S::Error: fmt::Debug,
     {
         let inner = Rc::new(RefCell::new(InnerSinkWrite {
             _i: PhantomData,
@@ -429,34 +492,250 @@ impl<I: 'static, S: Sink<I> + Unpin + 'static> SinkWrite<I, S> {
             task: None,
             handle: SpawnHandle::default(),
             buffer: VecDeque::new(),
+            reader: None,
+            keepalive: None,
+            batch: false,
+            dirty: false,
+            ordered: BTreeMap::new(),
+            next_seq: 0,
+            limit: None,
+            error: None,
+            timers: ctxt.parts().armed_timers(),
         }));
 
         let handle = ctxt.spawn(SinkWriteFuture {
             inner: inner.clone(),
+            _events: PhantomData::<fn() -> LogEvents>,
         });
 
+        ctxt.parts().flush_on_drain(handle);
         inner.borrow_mut().handle = handle;
         SinkWrite { inner }
     }
 
     /// Queues an item to be sent to the sink.
     ///
-    /// Returns unsent item if sink is closing or closed.
+    /// Returns unsent item if sink is closing or closed, or if the buffer is
+    /// full and the [`SinkOverflow::Reject`] policy is set.
     pub fn write(&mut self, item: I) -> Result<(), I> {
-        if self.inner.borrow().closing_flag.is_empty() {
-            self.inner.borrow_mut().buffer.push_back(item);
-            self.notify_task();
-            Ok(())
-        } else {
-            Err(item)
+        let mut inner = self.inner.borrow_mut();
+        if !inner.closing_flag.is_empty() {
+            return Err(item);
+        }
+        match inner.limit {
+            Some((limit, policy)) if inner.buffer.len() >= limit => match policy {
+                SinkOverflow::DropOldest if limit > 0 => {
+                    inner.buffer.pop_front();
+                }
+                SinkOverflow::DropNewest if limit > 0 => {
+                    inner.buffer.pop_back();
+                }
+                _ => return Err(item),
+            },
+            _ => {}
+        }
+        inner.buffer.push_back(item);
+        drop(inner);
+        self.notify_task();
+        Ok(())
+    }
+
+    /// Limits the number of items buffered by [`write`](Self::write).
+    ///
+    /// When an item is written while `limit` items are waiting for the sink,
+    /// `policy` decides which item is dropped. Dropping old samples suits a
+    /// metrics sink, while a protocol sink would reject the item and let the
+    /// caller retry it later. To pause a producing stream instead, use
+    /// [`pipe`](Self::pipe), which reads the next item only once the buffer
+    /// has been drained. By default the buffer is unbounded.
+    pub fn set_buffer_limit(&mut self, limit: usize, policy: SinkOverflow) {
+        self.inner.borrow_mut().limit = Some((limit, policy));
+    }
+
+    /// Queues an item tagged with a sequence number, sending items in
+    /// ascending sequence order.
+    ///
+    /// Items written ahead of their turn are held back until all items with
+    /// lower sequence numbers have been written, so responses to pipelined
+    /// requests keep the request order even if they are produced out of
+    /// order. Sequence numbers start at 0 and must not be skipped: an item
+    /// after a gap is held until the gap is filled, even when the writer is
+    /// closed.
+    ///
+    /// Returns unsent item if sink is closing or closed or if `seq` was
+    /// already written.
+    pub fn write_ordered(&mut self, seq: u64, item: I) -> Result<(), I> {
+        let mut inner = self.inner.borrow_mut();
+        if !inner.closing_flag.is_empty()
+            || seq < inner.next_seq
+            || inner.ordered.contains_key(&seq)
+        {
+            return Err(item);
+        }
+        if seq > inner.next_seq {
+            inner.ordered.insert(seq, item);
+            return Ok(());
         }
+
+        {
+            let inner = &mut *inner;
+            inner.buffer.push_back(item);
+            inner.next_seq += 1;
+            while let Some(item) = inner.ordered.remove(&inner.next_seq) {
+                inner.buffer.push_back(item);
+                inner.next_seq += 1;
+            }
+        }
+        drop(inner);
+        self.notify_task();
+        Ok(())
+    }
+
+    /// Sends an item to the sink without buffering it.
+    ///
+    /// The item is handed to the sink only if no previously queued items are
+    /// pending, no batch is being collected and the sink is ready to accept
+    /// it right away. Otherwise the item is returned, letting the caller
+    /// drop, merge or retry it later instead of growing the buffer.
+    ///
+    /// If the sink fails to accept the item, the item is lost and the error
+    /// is reported to [`WriteHandler::error`] as for any other sink error.
+    /// If the sink fails while getting ready, the error is reported the same
+    /// way and the item is returned.
+    ///
+    /// Until the writer has been polled by the context for the first time,
+    /// the item is queued and sent by the writer once it runs.
+    ///
+    /// Returns unsent item if sink is closing or closed.
+    pub fn try_write(&mut self, item: I) -> Result<(), I> {
+        let mut inner = self.inner.borrow_mut();
+        if !inner.closing_flag.is_empty() || !inner.buffer.is_empty() || inner.batch {
+            return Err(item);
+        }
+
+        // readiness can only be signaled to the writer task once it runs
+        let waker = match inner.task {
+            Some(ref waker) => waker.clone(),
+            None => {
+                inner.buffer.push_back(item);
+                return Ok(());
+            }
+        };
+        let mut cx = Context::from_waker(&waker);
+        match Pin::new(&mut inner.sink).poll_ready(&mut cx) {
+            Poll::Ready(Ok(())) => {
+                match Pin::new(&mut inner.sink).start_send(item) {
+                    Ok(()) => {
+                        inner.dirty = true;
+                        if let Some(ref mut keepalive) = inner.keepalive {
+                            keepalive.reset();
+                        }
+                    }
+                    Err(err) => inner.error = Some(err),
+                }
+                drop(inner);
+                // writer future flushes the sink or reports the error
+                self.notify_task();
+                Ok(())
+            }
+            Poll::Ready(Err(err)) => {
+                inner.error.get_or_insert(err);
+                drop(inner);
+                self.notify_task();
+                Err(item)
+            }
+            Poll::Pending => Err(item),
+        }
+    }
+
+    /// Forwards all items of `stream` to the sink, reading the stream only as
+    /// fast as the sink accepts items.
+    ///
+    /// The next item is read once previously queued items were handed to the
+    /// sink, so a slow sink slows down the stream instead of growing the
+    /// buffer. This makes a flow-controlled proxy without coupling reads and
+    /// writes by hand. Piping stops when the stream ends or the writer is
+    /// closing; the writer itself stays open.
+    ///
+    /// Returns the handle of the spawned pipe, which can be used to cancel it.
+    pub fn pipe<A, C, St>(&mut self, stream: St, ctxt: &mut C) -> SpawnHandle
+    where
+        A: Actor<Context = C>,
+        C: AsyncContext<A>,
+        St: Stream<Item = I> + Unpin + 'static,
+    {
+        ctxt.spawn(SinkPipe {
+            inner: self.inner.clone(),
+            stream,
+        })
+    }
+
+    /// Sends an item made by `make_ping` whenever nothing was sent to the
+    /// sink for `dur`.
+    ///
+    /// This keeps long lived connections, like websockets, from timing out
+    /// while there is no data, without scheduling pings by hand. The timer
+    /// restarts whenever an item is sent, and keepalives stop once the writer
+    /// is closing. Calling this method again replaces the previous settings.
+    pub fn set_keepalive<F>(&mut self, dur: Duration, make_ping: F)
+    where
+        F: Fn() -> I + 'static,
+    {
+        let mut inner = self.inner.borrow_mut();
+        let armed = ArmedTimer::new(Rc::clone(&inner.timers));
+        inner.keepalive = Some(Keepalive {
+            dur,
+            ping: Box::new(make_ping),
+            timer: Box::pin(sleep(dur)),
+            _armed: armed,
+        });
+        drop(inner);
+        self.notify_task();
+    }
+
+    /// Replaces the underlying sink, returning the old one.
+    ///
+    /// Items still queued in the writer's buffer are kept and sent to the new
+    /// sink, so a connection can be re-established without losing writes.
+    /// Only fully buffered items are preserved: items already handed to the
+    /// old sink, including ones it has not flushed to its transport yet, are
+    /// dropped together with it.
+    ///
+    /// This is typically called from [`WriteHandler::error`] before returning
+    /// [`Running::Continue`]. The writer must still be running; once it has
+    /// finished, create a new `SinkWrite` instead.
+    pub fn replace_preserving(&mut self, sink: S) -> S {
+        let old = std::mem::replace(&mut self.inner.borrow_mut().sink, sink);
+        self.notify_task();
+        old
+    }
+
+    /// Starts collecting a batch of items.
+    ///
+    /// Items written until [`end_batch`](Self::end_batch) are kept in the
+    /// writer's buffer instead of being sent right away. This reduces the
+    /// number of flushes, and so syscalls, when many small items are written
+    /// at once. Closing the writer sends and flushes a pending batch.
+    pub fn start_batch(&mut self) {
+        self.inner.borrow_mut().batch = true;
+    }
+
+    /// Ends the batch, handing all collected items to the sink and flushing
+    /// it once.
+    pub fn end_batch(&mut self) {
+        self.inner.borrow_mut().batch = false;
+        self.notify_task();
     }
 
     /// Gracefully closes the sink.
     ///
     /// The closing happens asynchronously.
     pub fn close(&mut self) {
-        self.inner.borrow_mut().closing_flag.insert(Flags::CLOSING);
+        let mut inner = self.inner.borrow_mut();
+        inner.closing_flag.insert(Flags::CLOSING);
+        // no pings are sent while closing
+        inner.keepalive = None;
+        drop(inner);
         self.notify_task();
     }
 
@@ -487,17 +766,103 @@ struct InnerSinkWrite<I, S: Sink<I>> {
     // buffer of items to be sent so that multiple
     // calls to start_send don't silently skip items
     buffer: VecDeque<I>,
+
+    // pipe waiting for the buffer to drain
+    reader: Option<task::Waker>,
+
+    keepalive: Option<Keepalive<I>>,
+
+    // items are held in the buffer until the batch ends
+    batch: bool,
+
+    // items were sent since the sink was last flushed
+    dirty: bool,
+
+    // items written ahead of their sequence number
+    ordered: BTreeMap<u64, I>,
+    next_seq: u64,
+
+    limit: Option<(usize, SinkOverflow)>,
+
+    // error of `poll_ready` or of an item rejected by `try_write`, reported
+    // by the writer future
+    error: Option<S::Error>,
+
+    // armed timers of the context, counting the keepalive timer
+    timers: Rc<Cell<usize>>,
+}
+
+/// Produces ping items for a sink that has been idle for a while.
+struct Keepalive<I> {
+    dur: Duration,
+    ping: Box<dyn Fn() -> I>,
+    timer: Pin<Box<Sleep>>,
+    _armed: ArmedTimer,
+}
+
+impl<I> Keepalive<I> {
+    fn reset(&mut self) {
+        self.timer.as_mut().reset(Instant::now() + self.dur);
+    }
+
+    /// Returns a ping item once the interval elapsed since the last reset.
+    fn poll_ping(&mut self, cx: &mut Context<'_>) -> Option<I> {
+        if self.timer.as_mut().poll(cx).is_ready() {
+            self.reset();
+            Some((self.ping)())
+        } else {
+            None
+        }
+    }
+}
+
+/// Routes sink events of `SinkWriteFuture`.
+trait SinkEvents<A: Actor, E> {
+    fn error(act: &mut A, err: E, ctx: &mut A::Context) -> Running;
+
+    fn finished(act: &mut A, ctx: &mut A::Context);
+}
+
+/// Delivers sink events to the actor's `WriteHandler`.
+struct ActorEvents;
+
+impl<A, E> SinkEvents<A, E> for ActorEvents
+where
+    A: Actor + WriteHandler<E>,
+    A::Context: ActorContext,
+{
+    fn error(act: &mut A, err: E, ctx: &mut A::Context) -> Running {
+        act.error(err, ctx)
+    }
+
+    fn finished(act: &mut A, ctx: &mut A::Context) {
+        act.finished(ctx)
+    }
 }
 
-struct SinkWriteFuture<I: 'static, S: Sink<I>> {
+/// Logs sink errors, used by `SinkWrite::forward`.
+struct LogEvents;
+
+impl<A: Actor, E: fmt::Debug> SinkEvents<A, E> for LogEvents {
+    fn error(_: &mut A, err: E, _: &mut A::Context) -> Running {
+        error!("SinkWrite: sink error: {:?}", err);
+        Running::Stop
+    }
+
+    fn finished(_: &mut A, _: &mut A::Context) {}
+}
+
+struct SinkWriteFuture<I: 'static, S: Sink<I>, H> {
     inner: Rc<RefCell<InnerSinkWrite<I, S>>>,
+    _events: PhantomData<fn() -> H>,
 }
 
-impl<I: 'static, S: Sink<I>, A> ActorFuture<A> for SinkWriteFuture<I, S>
+impl<I: 'static, S: Sink<I>, A, H> ActorFuture<A> for SinkWriteFuture<I, S, H>
 where
     S: Sink<I> + Unpin,
-    A: Actor + WriteHandler<S::Error>,
+    A: Actor,
     A::Context: AsyncContext<A>,
+    H: SinkEvents<A, S::Error>,
 {
     type Output = ();
 
@@ -508,54 +873,144 @@ where
         cx: &mut Context<'_>,
     ) -> Poll<Self::Output> {
         let this = self.get_mut();
-        let inner = &mut this.inner.borrow_mut();
+        let mut inner = this.inner.borrow_mut();
+        inner.task.replace(cx.waker().clone());
 
-        // ensure sink is ready to receive next item
-        match Pin::new(&mut inner.sink).poll_ready(cx) {
-            Poll::Ready(Ok(())) => {
-                if let Some(item) = inner.buffer.pop_front() {
-                    // send front of buffer to sink
-                    let _ = Pin::new(&mut inner.sink).start_send(item);
+        // a batch is held back until it ends, closing sends it anyway
+        let hold = inner.batch && !inner.closing_flag.contains(Flags::CLOSING);
+
+        if !hold {
+            // send buffered items for as long as the sink is ready
+            loop {
+                match Pin::new(&mut inner.sink).poll_ready(cx) {
+                    Poll::Ready(Ok(())) => match inner.buffer.pop_front() {
+                        Some(item) => {
+                            if let Err(err) = Pin::new(&mut inner.sink).start_send(item) {
+                                // reported below, the item is lost
+                                inner.error.get_or_insert(err);
+                                break;
+                            }
+                            inner.dirty = true;
+                            if let Some(ref mut keepalive) = inner.keepalive {
+                                keepalive.reset();
+                            }
+                        }
+                        None => break,
+                    },
+                    Poll::Ready(Err(err)) => {
+                        // reported below, the buffered items stay queued
+                        inner.error.get_or_insert(err);
+                        break;
+                    }
+                    Poll::Pending => break,
+                }
+            }
+            if inner.buffer.is_empty() {
+                if let Some(reader) = inner.reader.take() {
+                    reader.wake();
                 }
             }
-            Poll::Ready(Err(_err)) => {}
-            Poll::Pending => {}
         }
 
-        if !inner.closing_flag.contains(Flags::CLOSING) {
+        let closing = inner.closing_flag.contains(Flags::CLOSING);
+        let res = if let Some(err) = inner.error.take() {
+            Poll::Ready(Err(err))
+        } else if hold || (!closing && !inner.dirty) {
+            Poll::Pending
+        } else if !closing {
             match Pin::new(&mut inner.sink).poll_flush(cx) {
-                Poll::Ready(Err(e)) => {
-                    if act.error(e, ctxt) == Running::Stop {
-                        act.finished(ctxt);
-                        return Poll::Ready(());
-                    }
+                Poll::Ready(Ok(())) => {
+                    inner.dirty = false;
+                    Poll::Ready(Ok(()))
                 }
-                Poll::Ready(Ok(())) => {}
-                Poll::Pending => {}
+                res => res,
             }
         } else {
             assert!(!inner.closing_flag.contains(Flags::CLOSED));
             match Pin::new(&mut inner.sink).poll_close(cx) {
-                Poll::Ready(Err(e)) => {
-                    if act.error(e, ctxt) == Running::Stop {
-                        act.finished(ctxt);
-                        return Poll::Ready(());
-                    }
+                // ensure all items in buffer have been sent before closing
+                Poll::Ready(Ok(())) if inner.buffer.is_empty() => {
+                    inner.closing_flag |= Flags::CLOSED;
+                    drop(inner);
+                    H::finished(act, ctxt);
+                    return Poll::Ready(());
                 }
-                Poll::Ready(Ok(())) => {
-                    // ensure all items in buffer have been sent before closing
-                    if inner.buffer.is_empty() {
-                        inner.closing_flag |= Flags::CLOSED;
-                        act.finished(ctxt);
-                        return Poll::Ready(());
-                    }
+                res => res,
+            }
+        };
+
+        if let Poll::Ready(Err(e)) = res {
+            // the handler may replace the sink, release it first
+            drop(inner);
+            if H::error(act, e, ctxt) == Running::Stop {
+                // nothing drains the writer anymore, reject further writes
+                let mut inner = this.inner.borrow_mut();
+                inner.closing_flag |= Flags::CLOSED;
+                inner.keepalive = None;
+                inner.buffer.clear();
+                if let Some(reader) = inner.reader.take() {
+                    reader.wake();
                 }
-                Poll::Pending => {}
+                drop(inner);
+                H::finished(act, ctxt);
+                return Poll::Ready(());
             }
+            inner = this.inner.borrow_mut();
         }
 
-        inner.task.replace(cx.waker().clone());
+        // nothing was sent for a while, queue a ping
+        if !inner.closing_flag.contains(Flags::CLOSING) {
+            if let Some(ping) = inner.keepalive.as_mut().and_then(|k| k.poll_ping(cx)) {
+                inner.buffer.push_back(ping);
+                cx.waker().wake_by_ref();
+            }
+        }
+
+        Poll::Pending
+    }
+}
+
+struct SinkPipe<I: 'static, S: Sink<I>, St> {
+    inner: Rc<RefCell<InnerSinkWrite<I, S>>>,
+    stream: St,
+}
+
+impl<I: 'static, S: Sink<I>, St, A> ActorFuture<A> for SinkPipe<I, S, St>
+where
+    S: Sink<I> + Unpin,
+    St: Stream<Item = I> + Unpin,
+    A: Actor,
+{
+    type Output = ();
+
+    fn poll(
+        self: Pin<&mut Self>,
+        _: &mut A,
+        _: &mut A::Context,
+        cx: &mut Context<'_>,
+    ) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let mut inner = this.inner.borrow_mut();
+
+        if !inner.closing_flag.is_empty() {
+            return Poll::Ready(());
+        }
+
+        // read next item only after queued items were sent
+        if inner.buffer.is_empty() {
+            match Pin::new(&mut this.stream).poll_next(cx) {
+                Poll::Ready(Some(item)) => {
+                    inner.buffer.push_back(item);
+                    if let Some(task) = &inner.task {
+                        task.wake_by_ref();
+                    }
+                }
+                Poll::Ready(None) => return Poll::Ready(()),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
 
+        inner.reader = Some(cx.waker().clone());
         Poll::Pending
     }
 }
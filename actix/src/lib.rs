@@ -60,6 +60,12 @@ pub use crate::actor::{
 };
 pub use crate::address::{Addr, MailboxError, Recipient, WeakAddr, WeakRecipient};
 pub use crate::context::Context;
+#[cfg(feature = "metrics")]
+pub use crate::contextimpl::MetricsSink;
+pub use crate::contextimpl::{
+    InboxStat, ItemInfo, ItemScheduler, LatencyStats, MailboxPriority, NewestFirst,
+    OldestFirst, PendingItems, ResumeHandle, StashOverflow,
+};
 pub use crate::fut::{
     ActorFuture, ActorFutureExt, ActorStream, ActorStreamExt, ActorTryFuture,
     ActorTryFutureExt, WrapFuture, WrapStream,
@@ -136,7 +142,9 @@ pub mod dev {
 
     pub use crate::prelude::*;
 
-    pub use crate::address::{Envelope, EnvelopeProxy, RecipientRequest, Request, ToEnvelope};
+    pub use crate::address::{
+        AddrSink, Envelope, EnvelopeProxy, RecipientRequest, Request, ToEnvelope,
+    };
     pub mod channel {
         pub use crate::address::channel::{channel, AddressReceiver, AddressSender};
     }
@@ -1,3 +1,5 @@
+use std::any::Any;
+use std::cell::RefCell;
 use std::pin::Pin;
 use std::task::Poll;
 use std::{fmt, task};
@@ -5,8 +7,13 @@ use std::{fmt, task};
 use futures_core::stream::Stream;
 
 use crate::actor::{Actor, AsyncContext};
-use crate::address::EnvelopeProxy;
 use crate::address::{channel, Addr, AddressReceiver, AddressSenderProducer};
+use crate::address::{Envelope, EnvelopeProxy};
+use crate::clock::Instant;
+use crate::contextimpl::{AsyncContextParts, LatencyHistogram};
+
+/// Callback observing mailbox messages before they are handled.
+pub(crate) type Observer = dyn FnMut(&dyn Any);
 
 /// Default address channel capacity
 pub const DEFAULT_CAPACITY: usize = 16;
@@ -70,18 +77,57 @@ where
         Addr::new(self.msgs.sender())
     }
 
+    /// Take the next message without handling it.
+    pub(crate) fn poll_envelope(
+        &mut self,
+        task: &mut task::Context<'_>,
+    ) -> Poll<Option<Envelope<A>>> {
+        Pin::new(&mut self.msgs).poll_next(task)
+    }
+
     pub fn sender_producer(&self) -> AddressSenderProducer<A> {
         self.msgs.sender_producer()
     }
 
-    pub fn poll(&mut self, act: &mut A, ctx: &mut A::Context, task: &mut task::Context<'_>) {
+    /// Handle queued messages until the mailbox is empty or the context
+    /// pauses.
+    pub fn poll(&mut self, act: &mut A, ctx: &mut A::Context, task: &mut task::Context<'_>)
+    where
+        A::Context: AsyncContextParts<A>,
+    {
+        self.poll_limited(act, ctx, task, usize::MAX, None, None);
+    }
+
+    /// Handle at most `limit` messages, returns number of handled messages.
+    pub(crate) fn poll_limited<C>(
+        &mut self,
+        act: &mut A,
+        ctx: &mut C,
+        task: &mut task::Context<'_>,
+        limit: usize,
+        mut observer: Option<&mut Observer>,
+        latency: Option<&RefCell<LatencyHistogram>>,
+    ) -> usize
+    where
+        A: Actor<Context = C>,
+        C: AsyncContextParts<A>,
+    {
         #[cfg(feature = "mailbox_assert")]
         let mut n_polls = 0u16;
+        let mut handled = 0;
 
-        while !ctx.waiting() {
+        while !ctx.parts().paused() && handled < limit {
             match Pin::new(&mut self.msgs).poll_next(task) {
                 Poll::Ready(Some(mut msg)) => {
+                    if let (Some(observer), Some(m)) = (observer.as_mut(), msg.message()) {
+                        observer(m);
+                    }
+                    let start = latency.as_ref().map(|_| Instant::now());
                     msg.handle(act, ctx);
+                    if let (Some(latency), Some(start)) = (latency, start) {
+                        latency.borrow_mut().record(start.elapsed());
+                    }
+                    handled += 1;
                     #[cfg(feature = "mailbox_assert")]
                     {
                         n_polls += 1;
@@ -89,8 +135,9 @@ where
                         assert!(n_polls < 256u16, "Too many messages are being processed. Use Self::Context::notify() instead of direct use of address");
                     }
                 }
-                Poll::Ready(None) | Poll::Pending => return,
+                Poll::Ready(None) | Poll::Pending => break,
             }
         }
+        handled
     }
 }
@@ -1,11 +1,15 @@
+use std::future::Future;
 use std::pin::Pin;
 use std::task::{Context, Poll};
+use std::time::Duration;
 
 use futures_core::{ready, stream::Stream};
+use futures_util::future;
 use log::error;
 use pin_project_lite::pin_project;
 
 use crate::actor::{Actor, ActorContext, ActorState, AsyncContext, SpawnHandle};
+use crate::clock::{sleep, Sleep};
 use crate::fut::ActorFuture;
 
 /// Stream handling for Actors.
@@ -64,6 +68,12 @@ where
     /// Default implementation does nothing.
     fn started(&mut self, ctx: &mut Self::Context) {}
 
+    /// Called when a stream registered with `add_stream_repeating` or
+    /// `add_stream_reconnecting` is re-created after the previous one ended.
+    ///
+    /// Default implementation does nothing.
+    fn reconnected(&mut self, ctx: &mut Self::Context) {}
+
     /// Called when stream finishes.
     ///
     /// Default implementation stops Actor execution.
@@ -85,6 +95,87 @@ where
             ctx.spawn(ActorStream::new(stream))
         }
     }
+
+    /// Register a Stream that is re-created by `mk` every time it finishes.
+    ///
+    /// Each completion of the stream is followed by an optional `delay`,
+    /// after which a fresh stream is constructed and `reconnected()` is
+    /// called. `finished()` is not called, the repetition lasts until the
+    /// returned handle is cancelled.
+    fn add_stream_repeating<F, S>(
+        mk: F,
+        delay: Option<Duration>,
+        ctx: &mut Self::Context,
+    ) -> SpawnHandle
+    where
+        F: FnMut() -> S + 'static,
+        S: Stream + 'static,
+        Self: StreamHandler<S::Item>,
+        Self::Context: AsyncContext<Self>,
+    {
+        if ctx.state() == ActorState::Stopped {
+            error!("Context::add_stream_repeating called for stopped actor.");
+            SpawnHandle::default()
+        } else {
+            let mut mk = mk;
+            ctx.spawn(ActorStreamReconnecting::new(
+                move || future::ready(mk()),
+                delay,
+            ))
+        }
+    }
+
+    /// Register a Stream that is re-established through `mk` every time it
+    /// finishes.
+    ///
+    /// `mk` returns a future which connects and resolves to the stream. When
+    /// the stream ends, `mk` is called again after an optional `delay` and
+    /// the new stream seamlessly takes over. `started()` is called once the
+    /// first connection is established, `reconnected()` for every following
+    /// one. `finished()` is not called, reconnecting lasts until the
+    /// returned handle is cancelled.
+    fn add_stream_reconnecting<F, Fut, S>(
+        mk: F,
+        delay: Option<Duration>,
+        ctx: &mut Self::Context,
+    ) -> SpawnHandle
+    where
+        F: FnMut() -> Fut + 'static,
+        Fut: Future<Output = S> + 'static,
+        S: Stream + 'static,
+        Self: StreamHandler<S::Item>,
+        Self::Context: AsyncContext<Self>,
+    {
+        if ctx.state() == ActorState::Stopped {
+            error!("Context::add_stream_reconnecting called for stopped actor.");
+            SpawnHandle::default()
+        } else {
+            ctx.spawn(ActorStreamReconnecting::new(mk, delay))
+        }
+    }
+
+    /// Register a Stream whose items are collected and handled in batches,
+    /// one batch every `window`.
+    ///
+    /// Windows without items are skipped. When the stream ends the last,
+    /// partial batch is handled before `finished()` is called.
+    fn add_stream_windowed<S>(
+        stream: S,
+        window: Duration,
+        ctx: &mut Self::Context,
+    ) -> SpawnHandle
+    where
+        S: Stream + 'static,
+        Self: StreamHandler<Vec<S::Item>>,
+        Self::Context: AsyncContext<Self>,
+    {
+        if ctx.state() == ActorState::Stopped {
+            error!("Context::add_stream_windowed called for stopped actor.");
+            SpawnHandle::default()
+        } else {
+            ctx.spawn(ActorStreamWindowed::new(stream, window))
+        }
+    }
 }
 
 pin_project! {
@@ -147,3 +238,171 @@ where
         Poll::Ready(())
     }
 }
+
+pin_project! {
+    pub(crate) struct ActorStreamReconnecting<F, Fut, S> {
+        mk: F,
+        connect: Option<Pin<Box<Fut>>>,
+        stream: Option<Pin<Box<S>>>,
+        delay: Option<Duration>,
+        timeout: Option<Pin<Box<Sleep>>>,
+        connected: bool,
+    }
+}
+
+impl<F, Fut, S> ActorStreamReconnecting<F, Fut, S> {
+    pub fn new(mk: F, delay: Option<Duration>) -> Self {
+        Self {
+            mk,
+            connect: None,
+            stream: None,
+            delay,
+            timeout: None,
+            connected: false,
+        }
+    }
+}
+
+impl<A, F, Fut, S> ActorFuture<A> for ActorStreamReconnecting<F, Fut, S>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = S>,
+    S: Stream,
+    A: Actor + StreamHandler<S::Item>,
+    A::Context: AsyncContext<A>,
+{
+    type Output = ();
+
+    fn poll(
+        self: Pin<&mut Self>,
+        act: &mut A,
+        ctx: &mut A::Context,
+        task: &mut Context<'_>,
+    ) -> Poll<Self::Output> {
+        let this = self.project();
+        let mut polled = 0;
+
+        loop {
+            if this.stream.is_none() {
+                if let Some(timeout) = this.timeout {
+                    ready!(timeout.as_mut().poll(task));
+                    *this.timeout = None;
+                }
+
+                let mk = &mut *this.mk;
+                let connect = this.connect.get_or_insert_with(|| Box::pin(mk()));
+                let stream = ready!(connect.as_mut().poll(task));
+                *this.connect = None;
+                *this.stream = Some(Box::pin(stream));
+
+                if *this.connected {
+                    <A as StreamHandler<S::Item>>::reconnected(act, ctx);
+                } else {
+                    *this.connected = true;
+                    <A as StreamHandler<S::Item>>::started(act, ctx);
+                }
+            }
+
+            let stream = this.stream.as_mut().unwrap();
+            match ready!(stream.as_mut().poll_next(task)) {
+                Some(msg) => A::handle(act, msg, ctx),
+                None => {
+                    *this.stream = None;
+                    *this.timeout = this.delay.map(|delay| Box::pin(sleep(delay)));
+                }
+            }
+
+            polled += 1;
+
+            if ctx.waiting() {
+                return Poll::Pending;
+            } else if polled == 16 {
+                // Yield after 16 consecutive polls, same as `ActorStream`. This also
+                // prevents busy looping on streams that finish immediately.
+                task.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+        }
+    }
+}
+
+pin_project! {
+    pub(crate) struct ActorStreamWindowed<S: Stream> {
+        #[pin]
+        stream: S,
+        window: Duration,
+        timeout: Pin<Box<Sleep>>,
+        batch: Vec<S::Item>,
+        started: bool,
+    }
+}
+
+impl<S: Stream> ActorStreamWindowed<S> {
+    pub fn new(stream: S, window: Duration) -> Self {
+        Self {
+            stream,
+            window,
+            timeout: Box::pin(sleep(window)),
+            batch: Vec::new(),
+            started: false,
+        }
+    }
+}
+
+impl<A, S> ActorFuture<A> for ActorStreamWindowed<S>
+where
+    S: Stream,
+    A: Actor + StreamHandler<Vec<S::Item>>,
+    A::Context: AsyncContext<A>,
+{
+    type Output = ();
+
+    fn poll(
+        self: Pin<&mut Self>,
+        act: &mut A,
+        ctx: &mut A::Context,
+        task: &mut Context<'_>,
+    ) -> Poll<Self::Output> {
+        let mut this = self.project();
+
+        if !*this.started {
+            *this.started = true;
+            <A as StreamHandler<Vec<S::Item>>>::started(act, ctx);
+        }
+
+        let mut polled = 0;
+
+        loop {
+            if this.timeout.as_mut().poll(task).is_ready() {
+                let deadline = this.timeout.deadline() + *this.window;
+                this.timeout.as_mut().reset(deadline);
+                if !this.batch.is_empty() {
+                    A::handle(act, std::mem::take(this.batch), ctx);
+                    if ctx.waiting() {
+                        return Poll::Pending;
+                    }
+                }
+                continue;
+            }
+
+            match ready!(this.stream.as_mut().poll_next(task)) {
+                Some(item) => this.batch.push(item),
+                None => break,
+            }
+
+            polled += 1;
+
+            if polled == 16 {
+                // Yield after 16 consecutive polls, same as `ActorStream`.
+                task.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+        }
+
+        if !this.batch.is_empty() {
+            A::handle(act, std::mem::take(this.batch), ctx);
+        }
+        A::finished(act, ctx);
+        Poll::Ready(())
+    }
+}
@@ -253,7 +253,9 @@ where
         let mut act = self.act.take().unwrap();
 
         // started
-        A::started(&mut act, self);
+        if act.should_start() {
+            A::started(&mut act, self);
+        }
         self.state = ActorState::Running;
 
         loop {
@@ -283,7 +285,9 @@ where
                 // start new actor
                 self.state = ActorState::Started;
                 act = (*self.factory)();
-                A::started(&mut act, self);
+                if act.should_start() {
+                    A::started(&mut act, self);
+                }
                 self.state = ActorState::Running;
             }
         }
@@ -1,14 +1,19 @@
+use std::collections::BTreeMap;
 use std::future::Future;
 use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 use std::task::{Context, Poll};
 use std::time::Duration;
 
-use futures_core::ready;
+use futures_core::{ready, stream::Stream};
+use parking_lot::Mutex;
 use pin_project_lite::pin_project;
-use tokio::sync::oneshot;
+use tokio::sync::{mpsc, oneshot};
 
 use crate::actor::Actor;
-use crate::clock::{sleep, Sleep};
+use crate::address::Addr;
+use crate::clock::{sleep, Instant, Sleep};
 use crate::fut::{ActorFuture, ActorStream};
 
 #[deprecated(
@@ -50,6 +55,495 @@ where
     }
 }
 
+/// The sending half of an actor's output stream.
+///
+/// Created by [`Actor::create_with_output`](crate::Actor::create_with_output).
+/// The actor keeps it and pushes items produced over its lifetime.
+pub struct OutputSender<T> {
+    tx: mpsc::UnboundedSender<T>,
+}
+
+impl<T> OutputSender<T> {
+    /// Pushes an item to the output stream.
+    ///
+    /// Returns the item back if the stream has been dropped.
+    pub fn send(&self, item: T) -> Result<(), T> {
+        self.tx.send(item).map_err(|err| err.0)
+    }
+
+    /// Checks if the output stream has been dropped.
+    pub fn is_closed(&self) -> bool {
+        self.tx.is_closed()
+    }
+}
+
+impl<T> Clone for OutputSender<T> {
+    fn clone(&self) -> Self {
+        OutputSender {
+            tx: self.tx.clone(),
+        }
+    }
+}
+
+/// A stream of items emitted by an actor over its lifetime.
+///
+/// The stream ends once the actor stops and all of its
+/// [`OutputSender`]s are dropped.
+pub struct OutputStream<T> {
+    rx: mpsc::UnboundedReceiver<T>,
+}
+
+impl<T> Stream for OutputStream<T> {
+    type Item = T;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.rx.poll_recv(cx)
+    }
+}
+
+/// The sending half of a weighted inbox of an actor.
+///
+/// Created by [`Context::weighted_inbox`](crate::Context::weighted_inbox).
+/// Messages are delivered to the actor's handler and their results are
+/// discarded, like with [`Addr::do_send`](crate::Addr::do_send).
+pub struct InboxSender<M> {
+    tx: mpsc::UnboundedSender<M>,
+    state: Arc<InboxState>,
+}
+
+impl<M> InboxSender<M> {
+    /// Sends a message to the inbox.
+    ///
+    /// Returns the message back if the actor has stopped.
+    pub fn send(&self, msg: M) -> Result<(), M> {
+        // counted first, so the receiver never sees a negative queue length
+        self.state.queued.fetch_add(1, Ordering::SeqCst);
+        self.tx.send(msg).map_err(|err| {
+            self.state.queued.fetch_sub(1, Ordering::SeqCst);
+            err.0
+        })
+    }
+
+    /// Returns `true` if the actor has stopped.
+    pub fn is_closed(&self) -> bool {
+        self.tx.is_closed()
+    }
+}
+
+impl<M> Clone for InboxSender<M> {
+    fn clone(&self) -> Self {
+        self.state.senders.fetch_add(1, Ordering::SeqCst);
+        InboxSender {
+            tx: self.tx.clone(),
+            state: Arc::clone(&self.state),
+        }
+    }
+}
+
+impl<M> Drop for InboxSender<M> {
+    fn drop(&mut self) {
+        self.state.senders.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Queue length and number of senders of a weighted inbox.
+#[derive(Debug)]
+pub(crate) struct InboxState {
+    queued: AtomicUsize,
+    senders: AtomicUsize,
+}
+
+impl InboxState {
+    pub(crate) fn queued(&self) -> usize {
+        self.queued.load(Ordering::SeqCst)
+    }
+
+    /// All senders were dropped.
+    pub(crate) fn closed(&self) -> bool {
+        self.senders.load(Ordering::SeqCst) == 0
+    }
+}
+
+/// The receiving half of a weighted inbox, owned by the inbox driver.
+pub(crate) struct InboxReceiver<M> {
+    rx: mpsc::UnboundedReceiver<M>,
+    state: Arc<InboxState>,
+}
+
+impl<M> InboxReceiver<M> {
+    pub(crate) fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<M>> {
+        let msg = ready!(self.rx.poll_recv(cx));
+        if msg.is_some() {
+            self.state.queued.fetch_sub(1, Ordering::SeqCst);
+        }
+        Poll::Ready(msg)
+    }
+}
+
+impl<M> Drop for InboxReceiver<M> {
+    fn drop(&mut self) {
+        // messages left behind by a stopped actor are not queued anymore
+        self.rx.close();
+        let waker = futures_task::noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        while let Poll::Ready(Some(_)) = self.poll_recv(&mut cx) {}
+    }
+}
+
+pub(crate) fn inbox_channel<M>() -> (InboxSender<M>, InboxReceiver<M>, Arc<InboxState>) {
+    let (tx, rx) = mpsc::unbounded_channel();
+    let state = Arc::new(InboxState {
+        queued: AtomicUsize::new(0),
+        senders: AtomicUsize::new(1),
+    });
+    let sender = InboxSender {
+        tx,
+        state: Arc::clone(&state),
+    };
+    let receiver = InboxReceiver {
+        rx,
+        state: Arc::clone(&state),
+    };
+    (sender, receiver, state)
+}
+
+pub(crate) fn output_channel<T>() -> (OutputSender<T>, OutputStream<T>) {
+    let (tx, rx) = mpsc::unbounded_channel();
+    (OutputSender { tx }, OutputStream { rx })
+}
+
+/// Identifier of an actor in an [`ActorSet`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ActorId(usize);
+
+/// A set of actors driven together as a single stream.
+///
+/// Actors added to the set are not spawned, they run as part of the task
+/// polling the set. The stream yields the id of each actor once it stops,
+/// and ends when the set is empty.
+///
+/// ```
+/// # use actix::prelude::*;
+/// use actix::utils::ActorSet;
+/// use futures_util::stream::StreamExt;
+///
+/// struct Child;
+///
+/// impl Actor for Child {
+///     type Context = Context<Self>;
+///
+///     fn started(&mut self, ctx: &mut Self::Context) {
+///         ctx.stop();
+///     }
+/// }
+///
+/// # #[actix::main]
+/// # async fn main() {
+/// let mut set = ActorSet::new();
+/// let (first, _) = set.add(Context::new(), Child);
+/// let (second, _) = set.add(Context::new(), Child);
+///
+/// let stopped = set.collect::<Vec<_>>().await;
+/// assert_eq!(stopped, vec![first, second]);
+/// # }
+/// ```
+#[allow(clippy::type_complexity)]
+#[derive(Default)]
+pub struct ActorSet {
+    next: usize,
+    actors: Vec<(ActorId, Pin<Box<dyn Future<Output = ()>>>)>,
+}
+
+impl ActorSet {
+    /// Creates an empty set.
+    pub fn new() -> Self {
+        ActorSet::default()
+    }
+
+    /// Adds an actor to the set, returning its id and address.
+    pub fn add<A>(&mut self, ctx: crate::Context<A>, act: A) -> (ActorId, Addr<A>)
+    where
+        A: Actor<Context = crate::Context<A>>,
+    {
+        let fut = ctx.into_future(act);
+        let addr = fut.address();
+        let id = ActorId(self.next);
+        self.next += 1;
+        self.actors.push((id, Box::pin(fut)));
+        (id, addr)
+    }
+
+    /// Returns the number of running actors in the set.
+    pub fn len(&self) -> usize {
+        self.actors.len()
+    }
+
+    /// Returns `true` if the set contains no running actors.
+    pub fn is_empty(&self) -> bool {
+        self.actors.is_empty()
+    }
+}
+
+impl Stream for ActorSet {
+    type Item = ActorId;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<ActorId>> {
+        if self.actors.is_empty() {
+            return Poll::Ready(None);
+        }
+        let idx = self
+            .actors
+            .iter_mut()
+            .position(|(_, fut)| fut.as_mut().poll(cx).is_ready());
+        match idx {
+            Some(idx) => Poll::Ready(Some(self.actors.remove(idx).0)),
+            None => Poll::Pending,
+        }
+    }
+}
+
+/// Stops actors in phases, in ascending phase order.
+///
+/// Actors join with [`Context::join_shutdown`](crate::Context::join_shutdown).
+/// [`shutdown`](Self::shutdown) drains and stops all actors of the lowest
+/// phase concurrently and waits for them to stop before moving on to the
+/// next phase, e.g. to stop accepting connections before the database pool
+/// is closed. The coordinator can be cloned and sent to other threads.
+///
+/// ```
+/// # use std::time::Duration;
+/// # use actix::prelude::*;
+/// use actix::utils::ShutdownCoordinator;
+///
+/// struct Service;
+///
+/// impl Actor for Service {
+///     type Context = Context<Self>;
+/// }
+///
+/// # #[actix::main]
+/// # async fn main() {
+/// let coordinator = ShutdownCoordinator::new();
+/// let addr = Service::create(|ctx| {
+///     ctx.join_shutdown(&coordinator, 0, Duration::from_secs(5));
+///     Service
+/// });
+///
+/// coordinator.shutdown().await;
+/// assert!(!addr.connected());
+/// # }
+/// ```
+#[derive(Clone, Default)]
+pub struct ShutdownCoordinator {
+    phases: Arc<Mutex<BTreeMap<u8, Vec<ShutdownMember>>>>,
+}
+
+struct ShutdownMember {
+    begin: oneshot::Sender<()>,
+    // closed once the actor's context is dropped
+    done: oneshot::Receiver<()>,
+}
+
+/// Membership of an actor in a shutdown, kept by its context.
+///
+/// It is polled by the context instead of being spawned, so it neither keeps
+/// the actor alive nor gets cancelled by restarts or transitions.
+pub(crate) struct ShutdownListener {
+    begin: Option<oneshot::Receiver<()>>,
+    begun: bool,
+    timeout: Duration,
+    // dropped along with the context once the actor has stopped
+    _done: oneshot::Sender<()>,
+}
+
+impl ShutdownListener {
+    /// Returns the drain timeout once the shutdown of this member begins.
+    pub(crate) fn poll_begin(&mut self, cx: &mut Context<'_>) -> Option<Duration> {
+        let res = Pin::new(self.begin.as_mut()?).poll(cx);
+        match res {
+            Poll::Ready(res) => {
+                self.begin = None;
+                self.begun = res.is_ok();
+                res.ok().map(|_| self.timeout)
+            }
+            Poll::Pending => None,
+        }
+    }
+
+    /// Whether the shutdown of this member has begun.
+    pub(crate) fn begun(&self) -> bool {
+        self.begun
+    }
+}
+
+impl ShutdownCoordinator {
+    /// Creates a coordinator without members.
+    pub fn new() -> Self {
+        ShutdownCoordinator::default()
+    }
+
+    /// Registers a member in `phase`, which is drained for at most
+    /// `timeout` once its shutdown begins.
+    pub(crate) fn join(&self, phase: u8, timeout: Duration) -> ShutdownListener {
+        let (begin, begin_rx) = oneshot::channel();
+        let (done_tx, done) = oneshot::channel();
+        self.phases
+            .lock()
+            .entry(phase)
+            .or_default()
+            .push(ShutdownMember { begin, done });
+        ShutdownListener {
+            begin: Some(begin_rx),
+            begun: false,
+            timeout,
+            _done: done_tx,
+        }
+    }
+
+    /// Shuts down all members, one phase after another.
+    ///
+    /// Resolves once every member has stopped. Members that join a later
+    /// phase while the shutdown is in progress are stopped as well.
+    pub async fn shutdown(&self) {
+        loop {
+            let members = {
+                let mut phases = self.phases.lock();
+                let phase = match phases.keys().next() {
+                    Some(phase) => *phase,
+                    None => return,
+                };
+                phases.remove(&phase).unwrap()
+            };
+
+            let mut done = Vec::with_capacity(members.len());
+            for member in members {
+                let _ = member.begin.send(());
+                done.push(member.done);
+            }
+            for rx in done {
+                let _ = rx.await;
+            }
+        }
+    }
+}
+
+/// State of a [`CircuitBreaker`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BreakerState {
+    /// Attempts are allowed.
+    Closed,
+    /// Too many attempts failed, new attempts are rejected until the
+    /// cooldown has passed.
+    Open,
+    /// The cooldown has passed, a single probe attempt is allowed.
+    HalfOpen,
+}
+
+#[derive(Debug)]
+struct Breaker {
+    threshold: usize,
+    cooldown: Duration,
+    failures: usize,
+    // set while the breaker is open
+    opened: Option<Instant>,
+    probing: bool,
+}
+
+impl Breaker {
+    fn state(&self, now: Instant) -> BreakerState {
+        match self.opened {
+            None => BreakerState::Closed,
+            Some(at) if now < at + self.cooldown => BreakerState::Open,
+            Some(_) => BreakerState::HalfOpen,
+        }
+    }
+}
+
+/// Rejects attempts to spawn futures after too many of them failed.
+///
+/// Used with [`Context::spawn_with_breaker`](crate::Context::spawn_with_breaker).
+/// After `threshold` consecutive failures the breaker opens and new
+/// attempts fail fast, protecting a struggling dependency. Once `cooldown`
+/// has passed it lets one probe attempt through; the breaker closes again if
+/// the probe succeeds and reopens if it fails. Clones share their state, so
+/// all calls to the same downstream can use one breaker.
+#[derive(Clone, Debug)]
+pub struct CircuitBreaker {
+    inner: Arc<Mutex<Breaker>>,
+}
+
+impl CircuitBreaker {
+    /// Creates a closed breaker that opens after `threshold` consecutive
+    /// failures and stays open for `cooldown`.
+    pub fn new(threshold: usize, cooldown: Duration) -> Self {
+        CircuitBreaker {
+            inner: Arc::new(Mutex::new(Breaker {
+                threshold: threshold.max(1),
+                cooldown,
+                failures: 0,
+                opened: None,
+                probing: false,
+            })),
+        }
+    }
+
+    /// Returns the current state of the breaker.
+    pub fn state(&self) -> BreakerState {
+        self.inner.lock().state(Instant::now())
+    }
+
+    /// Starts an attempt, returns `None` if the breaker rejects it.
+    pub(crate) fn attempt(&self) -> Option<BreakerAttempt> {
+        let mut inner = self.inner.lock();
+        let probe = match inner.state(Instant::now()) {
+            BreakerState::Closed => false,
+            BreakerState::Open => return None,
+            BreakerState::HalfOpen if inner.probing => return None,
+            BreakerState::HalfOpen => true,
+        };
+        inner.probing |= probe;
+        Some(BreakerAttempt {
+            breaker: self.clone(),
+            probe,
+        })
+    }
+}
+
+/// An attempt let through by a [`CircuitBreaker`]. A probe that is dropped
+/// before it completes lets the next attempt probe instead.
+pub(crate) struct BreakerAttempt {
+    breaker: CircuitBreaker,
+    probe: bool,
+}
+
+impl BreakerAttempt {
+    /// Records the outcome of the attempt.
+    pub(crate) fn finish(mut self, ok: bool) {
+        let mut inner = self.breaker.inner.lock();
+        if self.probe {
+            inner.probing = false;
+            self.probe = false;
+        }
+        if ok {
+            inner.failures = 0;
+            inner.opened = None;
+        } else {
+            inner.failures += 1;
+            if inner.opened.is_some() || inner.failures >= inner.threshold {
+                inner.opened = Some(Instant::now());
+            }
+        }
+    }
+}
+
+impl Drop for BreakerAttempt {
+    fn drop(&mut self) {
+        if self.probe {
+            self.breaker.inner.lock().probing = false;
+        }
+    }
+}
+
 pin_project! {
     /// An `ActorFuture` that runs a function in the actor's context after a specified amount of time.
     ///
@@ -18,6 +18,7 @@ impl Message for Num {
     type Result = ();
 }
 
+#[allow(dead_code)]
 struct MyActor(Arc<AtomicUsize>, Arc<AtomicBool>, Running);
 
 impl Actor for MyActor {
@@ -59,6 +60,23 @@ async fn test_stream() {
     assert!(err.load(Ordering::Relaxed));
 }
 
+#[actix::test]
+async fn test_start_with_stream() {
+    let count = Arc::new(AtomicUsize::new(0));
+    let finished = Arc::new(AtomicBool::new(false));
+
+    let act = MyActor(Arc::clone(&count), Arc::clone(&finished), Running::Stop);
+    act.start_with_stream(|act| {
+        let base = act.0.load(Ordering::Relaxed);
+        futures_util::stream::iter((1..=3).map(move |n| Num(base + n)))
+    });
+
+    sleep(Duration::new(0, 1_000_000)).await;
+
+    assert_eq!(count.load(Ordering::Relaxed), 6);
+    assert!(finished.load(Ordering::Relaxed));
+}
+
 #[derive(Message)]
 #[rtype(result = "()")]
 struct Stop;
@@ -252,3 +270,177 @@ fn test_run_interval() {
     // We wait 10 intervals by ~100ms
     assert_eq!(result.elapsed().as_secs(), 1);
 }
+
+struct Splitter(actix::utils::OutputSender<String>);
+
+impl Actor for Splitter {
+    type Context = Context<Self>;
+}
+
+struct Line(&'static str);
+
+impl Message for Line {
+    type Result = ();
+}
+
+impl Handler<Line> for Splitter {
+    type Result = ();
+
+    fn handle(&mut self, msg: Line, _: &mut Self::Context) {
+        for word in msg.0.split_whitespace() {
+            self.0.send(word.to_owned()).unwrap();
+        }
+    }
+}
+
+#[actix::test]
+async fn test_create_with_output() {
+    use futures_util::stream::StreamExt;
+
+    let (addr, output) = Splitter::create_with_output(|_, tx| Splitter(tx));
+    addr.do_send(Line("hello actor"));
+    addr.do_send(Line("world"));
+    drop(addr);
+
+    let words = output.collect::<Vec<_>>().await;
+    assert_eq!(words, vec!["hello", "actor", "world"]);
+}
+
+struct Restored {
+    resumed: bool,
+    started: Arc<AtomicBool>,
+    msgs: Arc<AtomicUsize>,
+}
+
+impl Actor for Restored {
+    type Context = Context<Self>;
+
+    fn should_start(&self) -> bool {
+        !self.resumed
+    }
+
+    fn started(&mut self, _: &mut Self::Context) {
+        self.started.store(true, Ordering::Relaxed);
+    }
+}
+
+impl Handler<Num> for Restored {
+    type Result = ();
+
+    fn handle(&mut self, msg: Num, _: &mut Self::Context) {
+        self.msgs.fetch_add(msg.0, Ordering::Relaxed);
+    }
+}
+
+#[actix::test]
+async fn test_should_start() {
+    for resumed in [false, true].iter() {
+        let started = Arc::new(AtomicBool::new(false));
+        let msgs = Arc::new(AtomicUsize::new(0));
+
+        let addr = Restored {
+            resumed: *resumed,
+            started: Arc::clone(&started),
+            msgs: Arc::clone(&msgs),
+        }
+        .start();
+        addr.send(Num(3)).await.unwrap();
+
+        assert_eq!(started.load(Ordering::Relaxed), !resumed);
+        assert_eq!(msgs.load(Ordering::Relaxed), 3);
+    }
+}
+
+struct Teardown(Arc<AtomicBool>, Arc<AtomicBool>);
+
+impl Actor for Teardown {
+    type Context = Context<Self>;
+
+    fn stopping(&mut self, _: &mut Self::Context) -> Running {
+        self.0.store(true, Ordering::Relaxed);
+        Running::Stop
+    }
+
+    fn stopped(&mut self, _: &mut Self::Context) {
+        self.1.store(true, Ordering::Relaxed);
+    }
+}
+
+#[test]
+fn test_stopped_on_system_shutdown() {
+    let stopping = Arc::new(AtomicBool::new(false));
+    let stopped = Arc::new(AtomicBool::new(false));
+
+    let act = Teardown(Arc::clone(&stopping), Arc::clone(&stopped));
+    let sys = System::new();
+    sys.block_on(async move {
+        // keep the address alive, the actor is only stopped by the shutdown
+        std::mem::forget(act.start());
+        System::current().stop();
+    });
+    sys.run().unwrap();
+
+    assert!(stopping.load(Ordering::Relaxed));
+    assert!(stopped.load(Ordering::Relaxed));
+}
+
+struct Child;
+
+impl Actor for Child {
+    type Context = Context<Self>;
+}
+
+impl Handler<Num> for Child {
+    type Result = ();
+
+    fn handle(&mut self, msg: Num, ctx: &mut Self::Context) {
+        ctx.run_later(Duration::from_millis(msg.0 as u64), |_, ctx| ctx.stop());
+    }
+}
+
+#[actix::test]
+async fn test_actor_set() {
+    use actix::utils::ActorSet;
+    use futures_util::stream::StreamExt;
+
+    let mut set = ActorSet::new();
+    let (slow, slow_addr) = set.add(Context::new(), Child);
+    let (fast, fast_addr) = set.add(Context::new(), Child);
+    slow_addr.do_send(Num(20));
+    fast_addr.do_send(Num(5));
+    assert_eq!(set.len(), 2);
+
+    assert_eq!(set.next().await, Some(fast));
+    assert_eq!(set.len(), 1);
+    assert_eq!(set.next().await, Some(slow));
+    assert!(set.is_empty());
+    assert_eq!(set.next().await, None);
+}
+
+struct Batch(mpsc::Sender<Result<usize, &'static str>>);
+
+impl Actor for Batch {
+    type Context = Context<Self>;
+}
+
+impl StreamHandler<Result<usize, &'static str>> for Batch {
+    fn handle(&mut self, item: Result<usize, &'static str>, _: &mut Self::Context) {
+        self.0.send(item).unwrap();
+    }
+}
+
+#[actix::test]
+async fn test_start_with_items() {
+    for stop_on_error in [true, false].iter() {
+        let (tx, rx) = mpsc::channel();
+        Batch(tx).start_with_items(vec![Ok(1), Err("bad"), Ok(2)], *stop_on_error);
+        sleep(Duration::from_millis(10)).await;
+
+        let handled = rx.try_iter().collect::<Vec<_>>();
+        if *stop_on_error {
+            assert_eq!(handled, vec![Ok(1), Err("bad")]);
+        } else {
+            assert_eq!(handled, vec![Ok(1), Err("bad"), Ok(2)]);
+        }
+    }
+}
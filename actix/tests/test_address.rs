@@ -7,6 +7,7 @@ use actix::prelude::*;
 use actix_rt::time::sleep;
 
 #[derive(Debug)]
+#[allow(dead_code)]
 struct Ping(usize);
 
 impl Message for Ping {
@@ -301,6 +302,7 @@ fn test_address_eq() {
 }
 
 #[test]
+#[allow(clippy::mutable_key_type)]
 fn test_address_hash() {
     let count0 = Arc::new(AtomicUsize::new(0));
     let count1 = Arc::clone(&count0);
@@ -391,3 +393,121 @@ fn test_recipient_hash() {
         System::current().stop();
     });
 }
+
+#[test]
+fn test_address_sink() {
+    use std::pin::Pin;
+    use std::task::Context as StdContext;
+
+    use futures_sink::Sink;
+    use futures_util::future::poll_fn;
+
+    let count = Arc::new(AtomicUsize::new(0));
+    let count2 = Arc::clone(&count);
+
+    System::new().block_on(async move {
+        let addr = MyActor::create(move |ctx| {
+            ctx.set_mailbox_capacity(2);
+            MyActor(count2)
+        });
+        let mut sink = addr.clone().into_sink();
+
+        // the sink is not ready while the mailbox is full
+        let waker = futures_util::task::noop_waker();
+        let mut cx = StdContext::from_waker(&waker);
+        for n in 0..2 {
+            assert!(Pin::new(&mut sink).poll_ready(&mut cx).is_ready());
+            Pin::new(&mut sink).start_send(Ping(n)).unwrap();
+        }
+        assert!(Pin::new(&mut sink).poll_ready(&mut cx).is_pending());
+
+        for n in 2..10 {
+            poll_fn(|cx| Pin::new(&mut sink).poll_ready(cx))
+                .await
+                .unwrap();
+            Pin::new(&mut sink).start_send(Ping(n)).unwrap();
+        }
+        sleep(Duration::from_millis(10)).await;
+        assert_eq!(count.load(Ordering::Relaxed), 10);
+    });
+}
+
+#[test]
+#[should_panic(expected = "without readiness")]
+fn test_address_sink_send_without_readiness() {
+    use std::pin::Pin;
+
+    use futures_sink::Sink;
+
+    System::new().block_on(async move {
+        let addr = MyActor::create(|ctx| {
+            ctx.set_mailbox_capacity(1);
+            MyActor(Arc::new(AtomicUsize::new(0)))
+        });
+        let mut sink = addr.into_sink();
+
+        Pin::new(&mut sink).start_send(Ping(0)).unwrap();
+        let _ = Pin::new(&mut sink).start_send(Ping(1));
+    });
+}
+
+struct Backlogged {
+    handled: Arc<AtomicUsize>,
+    expired: Arc<AtomicUsize>,
+}
+
+impl Actor for Backlogged {
+    type Context = actix::Context<Self>;
+}
+
+struct Stall(u64);
+
+impl Message for Stall {
+    type Result = ();
+}
+
+impl Handler<Stall> for Backlogged {
+    type Result = ();
+
+    fn handle(&mut self, msg: Stall, _: &mut Self::Context) {
+        std::thread::sleep(Duration::from_millis(msg.0));
+    }
+}
+
+impl Handler<Ping> for Backlogged {
+    type Result = ();
+
+    fn handle(&mut self, _: Ping, _: &mut Self::Context) {
+        self.handled.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn expired(&mut self, msg: Ping, _: &mut Self::Context) {
+        assert_eq!(msg.0, 0);
+        self.expired.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[actix::test]
+async fn test_send_with_deadline() {
+    let handled = Arc::new(AtomicUsize::new(0));
+    let expired = Arc::new(AtomicUsize::new(0));
+    let addr = Backlogged {
+        handled: Arc::clone(&handled),
+        expired: Arc::clone(&expired),
+    }
+    .start();
+
+    // the deadline passes while the actor is busy
+    addr.do_send(Stall(50));
+    let deadline = actix_rt::time::Instant::now() + Duration::from_millis(10);
+    let res = addr.send_with_deadline(Ping(0), deadline).await;
+    assert!(matches!(res, Err(MailboxError::Timeout)));
+
+    sleep(Duration::from_millis(60)).await;
+    assert_eq!(handled.load(Ordering::Relaxed), 0);
+    assert_eq!(expired.load(Ordering::Relaxed), 1);
+
+    let deadline = actix_rt::time::Instant::now() + Duration::from_millis(50);
+    addr.send_with_deadline(Ping(1), deadline).await.unwrap();
+    assert_eq!(handled.load(Ordering::Relaxed), 1);
+}
@@ -4,6 +4,7 @@ use std::sync::Arc;
 use tokio::sync::oneshot;
 
 #[derive(Debug)]
+#[allow(dead_code)]
 struct Ping(usize);
 
 impl Message for Ping {
@@ -1,16 +1,19 @@
 #![cfg(feature = "macros")]
 #![allow(clippy::let_unit_value)]
 
-use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 use std::task::{Context as StdContext, Poll};
 use std::{pin::Pin, time::Duration};
 
 use actix::prelude::*;
+use actix::{ItemInfo, ItemScheduler};
 use actix_rt::time::{interval_at, sleep, Instant};
 use futures_core::stream::Stream;
 use futures_util::stream::once;
+use tokio::io::DuplexStream;
 use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+use tokio_util::codec::{LinesCodec, LinesCodecError};
 
 #[derive(Debug, PartialEq)]
 enum Op {
@@ -557,3 +560,3061 @@ mod scheduled_task_is_cancelled_properly {
         sys.run().unwrap();
     }
 }
+
+struct OrderedItems(Arc<std::sync::Mutex<Vec<usize>>>);
+
+impl Actor for OrderedItems {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        ctx.set_preserve_item_order(true);
+        for i in 0..4 {
+            ctx.spawn(
+                tokio::task::yield_now()
+                    .into_actor(self)
+                    .map(move |_, act, _| act.0.lock().unwrap().push(i)),
+            );
+        }
+    }
+}
+
+#[test]
+fn test_preserve_item_order() {
+    let order = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let order2 = Arc::clone(&order);
+
+    let sys = System::new();
+    sys.block_on(async move {
+        let _addr = OrderedItems(order2).start();
+        sleep(Duration::from_millis(50)).await;
+    });
+
+    assert_eq!(*order.lock().unwrap(), vec![0, 1, 2, 3]);
+}
+
+struct Quiesce(Arc<std::sync::Mutex<Vec<&'static str>>>);
+
+impl Actor for Quiesce {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        ctx.spawn(
+            sleep(Duration::from_millis(20))
+                .into_actor(self)
+                .map(|_, act, _| act.0.lock().unwrap().push("future")),
+        );
+        ctx.quiesce(|act, _| act.0.lock().unwrap().push("quiesced"));
+    }
+}
+
+impl Handler<Ping> for Quiesce {
+    type Result = ();
+
+    fn handle(&mut self, _: Ping, _: &mut Self::Context) {
+        self.0.lock().unwrap().push("message");
+    }
+}
+
+#[test]
+fn test_quiesce() {
+    let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let events2 = Arc::clone(&events);
+
+    let sys = System::new();
+    sys.block_on(async move {
+        let addr = Quiesce(events2).start();
+        addr.send(Ping).await.unwrap();
+    });
+
+    assert_eq!(
+        *events.lock().unwrap(),
+        vec!["future", "quiesced", "message"]
+    );
+}
+
+struct Write(usize);
+
+impl Message for Write {
+    type Result = ();
+}
+
+struct OrderedFutures(Arc<std::sync::Mutex<Vec<usize>>>);
+
+impl Actor for OrderedFutures {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        for (i, delay) in [30, 10, 20].iter().enumerate() {
+            let delay = Duration::from_millis(*delay);
+            ctx.add_ordered_future(async move {
+                sleep(delay).await;
+                Write(i)
+            });
+        }
+    }
+}
+
+impl Handler<Write> for OrderedFutures {
+    type Result = ();
+
+    fn handle(&mut self, msg: Write, _: &mut Self::Context) {
+        self.0.lock().unwrap().push(msg.0);
+    }
+}
+
+#[test]
+fn test_add_ordered_future() {
+    let writes = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let writes2 = Arc::clone(&writes);
+
+    let sys = System::new();
+    sys.block_on(async move {
+        let _addr = OrderedFutures(writes2).start();
+        sleep(Duration::from_millis(100)).await;
+    });
+
+    assert_eq!(*writes.lock().unwrap(), vec![0, 1, 2]);
+}
+
+struct Drain {
+    delay: Duration,
+    completed: Arc<AtomicUsize>,
+    stopped: Arc<AtomicUsize>,
+}
+
+impl Actor for Drain {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        ctx.spawn(sleep(self.delay).into_actor(self).map(|_, act, _| {
+            act.completed.fetch_add(1, Ordering::SeqCst);
+        }));
+        ctx.drain_and_stop(Duration::from_millis(50));
+    }
+
+    fn stopped(&mut self, _: &mut Self::Context) {
+        self.stopped.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[test]
+fn test_drain_and_stop() {
+    for (delay, completed) in [(10, 1), (1000, 0)].iter() {
+        let completed_count = Arc::new(AtomicUsize::new(0));
+        let stopped = Arc::new(AtomicUsize::new(0));
+        let act = Drain {
+            delay: Duration::from_millis(*delay),
+            completed: Arc::clone(&completed_count),
+            stopped: Arc::clone(&stopped),
+        };
+
+        let sys = System::new();
+        sys.block_on(async move {
+            let _addr = act.start();
+            sleep(Duration::from_millis(100)).await;
+        });
+
+        assert_eq!(completed_count.load(Ordering::SeqCst), *completed);
+        assert_eq!(stopped.load(Ordering::SeqCst), 1);
+    }
+}
+
+struct HighWater(Arc<std::sync::Mutex<Vec<usize>>>);
+
+impl Actor for HighWater {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        let crossings = Arc::clone(&self.0);
+        ctx.on_items_high_water(2, move |len| crossings.lock().unwrap().push(len));
+    }
+}
+
+struct SpawnItems(usize);
+
+impl Message for SpawnItems {
+    type Result = ();
+}
+
+impl Handler<SpawnItems> for HighWater {
+    type Result = ();
+
+    fn handle(&mut self, msg: SpawnItems, ctx: &mut Self::Context) {
+        for _ in 0..msg.0 {
+            ctx.spawn(sleep(Duration::from_millis(20)).into_actor(self));
+        }
+    }
+}
+
+#[test]
+fn test_items_high_water() {
+    let crossings = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let crossings2 = Arc::clone(&crossings);
+
+    let sys = System::new();
+    sys.block_on(async move {
+        let addr = HighWater(crossings2).start();
+        addr.send(SpawnItems(3)).await.unwrap();
+        addr.send(SpawnItems(1)).await.unwrap();
+        sleep(Duration::from_millis(50)).await;
+        addr.send(SpawnItems(5)).await.unwrap();
+        sleep(Duration::from_millis(50)).await;
+    });
+
+    assert_eq!(*crossings.lock().unwrap(), vec![3, 5]);
+}
+
+struct Repeating {
+    restarts: Arc<AtomicUsize>,
+    items: Vec<usize>,
+}
+
+impl Actor for Repeating {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        let restarts = Arc::clone(&self.restarts);
+        ctx.add_stream_repeating(
+            move || {
+                restarts.fetch_add(1, Ordering::SeqCst);
+                futures_util::stream::iter(vec![1, 2])
+            },
+            Some(Duration::from_millis(5)),
+        );
+    }
+}
+
+impl StreamHandler<usize> for Repeating {
+    fn handle(&mut self, item: usize, ctx: &mut Self::Context) {
+        self.items.push(item);
+        if self.items.len() == 6 {
+            assert_eq!(self.items, vec![1, 2, 1, 2, 1, 2]);
+            ctx.stop();
+        }
+    }
+}
+
+#[test]
+fn test_add_stream_repeating() {
+    let restarts = Arc::new(AtomicUsize::new(0));
+    let restarts2 = Arc::clone(&restarts);
+
+    let sys = System::new();
+    sys.block_on(async move {
+        let _addr = Repeating {
+            restarts: restarts2,
+            items: Vec::new(),
+        }
+        .start();
+        sleep(Duration::from_millis(100)).await;
+    });
+
+    assert_eq!(restarts.load(Ordering::SeqCst), 3);
+}
+
+struct Greeter(&'static str);
+
+impl Actor for Greeter {
+    type Context = Context<Self>;
+}
+
+struct Greet;
+
+impl Message for Greet {
+    type Result = &'static str;
+}
+
+impl Handler<Greet> for Greeter {
+    type Result = MessageResult<Greet>;
+
+    fn handle(&mut self, _: Greet, _: &mut Self::Context) -> Self::Result {
+        MessageResult(self.0)
+    }
+}
+
+#[actix::test]
+async fn test_replace_actor() {
+    let mut fut = Context::new().into_future(Greeter("hello"));
+    assert_eq!(fut.actor().0, "hello");
+    fut.actor_mut().0 = "hi";
+
+    let old = fut.replace_actor(Greeter("bonjour"));
+    assert_eq!(old.0, "hi");
+
+    let addr = fut.address();
+    actix_rt::spawn(fut);
+    assert_eq!(addr.send(Greet).await.unwrap(), "bonjour");
+}
+
+#[derive(Default)]
+struct Labeled {
+    done: Option<SpawnHandle>,
+    pending: Option<SpawnHandle>,
+}
+
+impl Actor for Labeled {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        self.done = Some(ctx.spawn_labeled("timeout:quick", async {}.into_actor(self)));
+        self.pending = Some(ctx.spawn_labeled(
+            "subscription:user42",
+            sleep(Duration::from_secs(10)).into_actor(self),
+        ));
+        assert_eq!(ctx.label_of(self.done.unwrap()), Some("timeout:quick"));
+    }
+}
+
+struct Labels;
+
+impl Message for Labels {
+    type Result = (Option<String>, Option<String>);
+}
+
+impl Handler<Labels> for Labeled {
+    type Result = MessageResult<Labels>;
+
+    fn handle(&mut self, _: Labels, ctx: &mut Self::Context) -> Self::Result {
+        let label = |h: Option<SpawnHandle>| ctx.label_of(h.unwrap()).map(str::to_owned);
+        MessageResult((label(self.done), label(self.pending)))
+    }
+}
+
+#[actix::test]
+async fn test_spawn_labeled() {
+    let addr = Labeled::default().start();
+    sleep(Duration::from_millis(10)).await;
+    let (done, pending) = addr.send(Labels).await.unwrap();
+    assert_eq!(done, None);
+    assert_eq!(pending.as_deref(), Some("subscription:user42"));
+}
+
+struct Reconnecting {
+    connects: Arc<AtomicUsize>,
+    reconnects: usize,
+    items: Vec<usize>,
+}
+
+impl Actor for Reconnecting {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        let connects = Arc::clone(&self.connects);
+        ctx.add_stream_reconnecting(
+            move || {
+                let n = connects.fetch_add(1, Ordering::SeqCst);
+                async move {
+                    sleep(Duration::from_millis(5)).await;
+                    futures_util::stream::iter(vec![n])
+                }
+            },
+            None,
+        );
+    }
+}
+
+impl StreamHandler<usize> for Reconnecting {
+    fn handle(&mut self, item: usize, ctx: &mut Self::Context) {
+        self.items.push(item);
+        if self.items.len() == 3 {
+            assert_eq!(self.items, vec![0, 1, 2]);
+            assert_eq!(self.reconnects, 2);
+            ctx.stop();
+        }
+    }
+
+    fn reconnected(&mut self, _: &mut Self::Context) {
+        self.reconnects += 1;
+    }
+}
+
+#[test]
+fn test_add_stream_reconnecting() {
+    let connects = Arc::new(AtomicUsize::new(0));
+    let connects2 = Arc::clone(&connects);
+
+    let sys = System::new();
+    sys.block_on(async move {
+        let _addr = Reconnecting {
+            connects: connects2,
+            reconnects: 0,
+            items: Vec::new(),
+        }
+        .start();
+        sleep(Duration::from_millis(100)).await;
+    });
+
+    assert_eq!(connects.load(Ordering::SeqCst), 3);
+}
+
+struct ExternalEvent {
+    fired: Arc<std::sync::atomic::AtomicBool>,
+    waker: std::sync::mpsc::Sender<std::task::Waker>,
+}
+
+impl Actor for ExternalEvent {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        self.waker.send(ctx.waker().unwrap()).unwrap();
+
+        // does not register a waker, relies on the context being woken
+        let fired = Arc::clone(&self.fired);
+        let event = futures_util::future::poll_fn(move |_| {
+            if fired.load(Ordering::SeqCst) {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        });
+        ctx.spawn(event.into_actor(self).map(|_, _, ctx| ctx.stop()));
+    }
+
+    fn stopped(&mut self, _: &mut Self::Context) {
+        System::current().stop();
+    }
+}
+
+#[test]
+fn test_context_waker() {
+    let fired = Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let fired2 = Arc::clone(&fired);
+    let (tx, rx) = std::sync::mpsc::channel();
+
+    std::thread::spawn(move || {
+        let waker: std::task::Waker = rx.recv().unwrap();
+        std::thread::sleep(Duration::from_millis(20));
+        fired2.store(true, Ordering::SeqCst);
+        waker.wake();
+    });
+
+    let sys = System::new();
+    sys.block_on(async move {
+        let _addr = ExternalEvent { fired, waker: tx }.start();
+    });
+    sys.run().unwrap();
+}
+
+struct Suspendable {
+    ticks: Arc<AtomicUsize>,
+}
+
+impl Actor for Suspendable {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        ctx.run_interval(Duration::from_millis(2), |act, _| {
+            act.ticks.fetch_add(1, Ordering::SeqCst);
+        });
+    }
+}
+
+struct Suspend;
+
+impl Message for Suspend {
+    type Result = actix::ResumeHandle;
+}
+
+impl Handler<Suspend> for Suspendable {
+    type Result = MessageResult<Suspend>;
+
+    fn handle(&mut self, _: Suspend, ctx: &mut Self::Context) -> Self::Result {
+        let handle = ctx.suspend();
+        // a suspension is not a wait future
+        assert!(!ctx.waiting());
+        MessageResult(handle)
+    }
+}
+
+struct Ticks;
+
+impl Message for Ticks {
+    type Result = usize;
+}
+
+impl Handler<Ticks> for Suspendable {
+    type Result = usize;
+
+    fn handle(&mut self, _: Ticks, _: &mut Self::Context) -> usize {
+        self.ticks.load(Ordering::SeqCst)
+    }
+}
+
+#[actix::test]
+async fn test_suspend_resume() {
+    let ticks = Arc::new(AtomicUsize::new(0));
+    let addr = Suspendable {
+        ticks: Arc::clone(&ticks),
+    }
+    .start();
+
+    sleep(Duration::from_millis(20)).await;
+    let handle = addr.send(Suspend).await.unwrap();
+    let suspended_at = ticks.load(Ordering::SeqCst);
+    let pending = addr.send(Ticks);
+
+    sleep(Duration::from_millis(20)).await;
+    assert_eq!(ticks.load(Ordering::SeqCst), suspended_at);
+
+    handle.resume();
+    assert_eq!(pending.await.unwrap(), suspended_at);
+    sleep(Duration::from_millis(20)).await;
+    assert!(ticks.load(Ordering::SeqCst) > suspended_at);
+}
+
+struct Pending;
+
+impl Actor for Pending {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        ctx.spawn_labeled(
+            "stream:ticks",
+            sleep(Duration::from_secs(10)).into_actor(self),
+        );
+        ctx.spawn(sleep(Duration::from_secs(10)).into_actor(self));
+    }
+}
+
+struct GetPending;
+
+impl Message for GetPending {
+    type Result = actix::PendingItems;
+}
+
+impl Handler<GetPending> for Pending {
+    type Result = MessageResult<GetPending>;
+
+    fn handle(&mut self, _: GetPending, ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(ctx.pending())
+    }
+}
+
+#[actix::test]
+async fn test_pending_items() {
+    let addr = Pending.start();
+    let pending = addr.send(GetPending).await.unwrap();
+
+    assert_eq!(pending.spawned, 2);
+    assert_eq!(pending.waiting, 0);
+    assert_eq!(pending.messages, 0);
+    assert_eq!(pending.labels, vec!["stream:ticks".to_owned()]);
+}
+
+struct Prefetch(Arc<std::sync::Mutex<Vec<&'static str>>>);
+
+impl Actor for Prefetch {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        for (name, delay) in [("fast", 5), ("slow", 100)].iter() {
+            let name = *name;
+            ctx.spawn_with_ttl(
+                sleep(Duration::from_millis(*delay))
+                    .into_actor(self)
+                    .map(move |_, act, _| act.0.lock().unwrap().push(name)),
+                Duration::from_millis(20),
+                move |act, _| act.0.lock().unwrap().push("expired"),
+            );
+        }
+    }
+}
+
+#[test]
+fn test_spawn_with_ttl() {
+    let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let events2 = Arc::clone(&events);
+
+    let sys = System::new();
+    sys.block_on(async move {
+        let _addr = Prefetch(events2).start();
+        sleep(Duration::from_millis(150)).await;
+    });
+
+    assert_eq!(*events.lock().unwrap(), vec!["fast", "expired"]);
+}
+
+struct Firehose {
+    priority: actix::MailboxPriority,
+    from_stream: bool,
+    handled: usize,
+    control_at: Arc<AtomicUsize>,
+}
+
+impl Actor for Firehose {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        ctx.set_mailbox_priority(self.priority);
+        ctx.add_stream(futures_util::stream::iter(0..100));
+        ctx.add_stream(futures_util::stream::iter(0..100));
+    }
+}
+
+impl StreamHandler<i32> for Firehose {
+    fn handle(&mut self, _: i32, ctx: &mut Self::Context) {
+        self.handled += 1;
+        if self.handled == 1 && self.from_stream {
+            ctx.address().do_send(Ping);
+        }
+    }
+
+    fn finished(&mut self, _: &mut Self::Context) {}
+}
+
+impl Handler<Ping> for Firehose {
+    type Result = ();
+
+    fn handle(&mut self, _: Ping, _: &mut Self::Context) {
+        self.control_at.store(self.handled, Ordering::SeqCst);
+    }
+}
+
+#[test]
+fn test_mailbox_priority() {
+    use actix::MailboxPriority::*;
+
+    // streams yield 16 items per poll
+    let cases = [
+        (High, false, 0),
+        (Normal, false, 0),
+        (Low, false, 32),
+        (High, true, 16),
+        (Normal, true, 32),
+        (Low, true, 32),
+    ];
+
+    for (priority, from_stream, expected) in cases.iter() {
+        let control_at = Arc::new(AtomicUsize::new(0));
+        let act = Firehose {
+            priority: *priority,
+            from_stream: *from_stream,
+            handled: 0,
+            control_at: Arc::clone(&control_at),
+        };
+
+        let sys = System::new();
+        sys.block_on(async move {
+            let addr = act.start();
+            if !from_stream {
+                addr.do_send(Ping);
+            }
+            sleep(Duration::from_millis(20)).await;
+        });
+        assert_eq!(
+            control_at.load(Ordering::SeqCst),
+            *expected,
+            "{:?}",
+            priority
+        );
+    }
+}
+
+struct RequestId(u64);
+
+struct Tag;
+
+impl Message for Tag {
+    type Result = Option<u64>;
+}
+
+struct Ambient;
+
+impl Actor for Ambient {
+    type Context = Context<Self>;
+}
+
+impl Handler<Tag> for Ambient {
+    type Result = ResponseActFuture<Self, Option<u64>>;
+
+    fn handle(&mut self, _: Tag, ctx: &mut Self::Context) -> Self::Result {
+        assert!(ctx.insert(RequestId(7)).is_none());
+        ctx.get_mut::<RequestId>().unwrap().0 += 1;
+
+        Box::pin(
+            async { sleep(Duration::from_millis(1)).await }
+                .into_actor(self)
+                .map(|_, _, ctx: &mut Context<Self>| {
+                    let id = ctx.get::<RequestId>().map(|id| id.0);
+                    assert_eq!(ctx.remove::<RequestId>().map(|id| id.0), id);
+                    assert!(ctx.get::<RequestId>().is_none());
+                    id
+                }),
+        )
+    }
+}
+
+#[actix::test]
+async fn test_context_local_values() {
+    let addr = Ambient.start();
+    assert_eq!(addr.send(Tag).await.unwrap(), Some(8));
+}
+
+struct WriteFailed(&'static str);
+
+impl Message for WriteFailed {
+    type Result = ();
+}
+
+struct Writer(Arc<Mutex<Vec<&'static str>>>);
+
+impl Actor for Writer {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        ctx.spawn_discard(async { Ok::<_, WriteFailed>(()) });
+        ctx.spawn_discard(async { Err(WriteFailed("disk full")) });
+    }
+}
+
+impl Handler<WriteFailed> for Writer {
+    type Result = ();
+
+    fn handle(&mut self, msg: WriteFailed, _: &mut Self::Context) {
+        self.0.lock().unwrap().push(msg.0);
+    }
+}
+
+#[actix::test]
+async fn test_spawn_discard() {
+    let errors = Arc::new(Mutex::new(Vec::new()));
+    let _addr = Writer(Arc::clone(&errors)).start();
+    sleep(Duration::from_millis(10)).await;
+    assert_eq!(*errors.lock().unwrap(), vec!["disk full"]);
+}
+
+struct Request(usize);
+
+impl Message for Request {
+    type Result = ();
+}
+
+struct Connect;
+
+impl Message for Connect {
+    type Result = ();
+}
+
+struct Gate {
+    connected: bool,
+    handled: Arc<Mutex<Vec<usize>>>,
+}
+
+impl Actor for Gate {
+    type Context = Context<Self>;
+}
+
+impl Handler<Request> for Gate {
+    type Result = ();
+
+    fn handle(&mut self, msg: Request, ctx: &mut Self::Context) {
+        if self.connected {
+            self.handled.lock().unwrap().push(msg.0);
+        } else {
+            ctx.stash(msg);
+        }
+    }
+}
+
+impl Handler<Connect> for Gate {
+    type Result = ();
+
+    fn handle(&mut self, _: Connect, ctx: &mut Self::Context) {
+        assert_eq!(ctx.stashed(), 2);
+        self.connected = true;
+        ctx.unstash();
+    }
+}
+
+#[actix::test]
+async fn test_stash() {
+    let handled = Arc::new(Mutex::new(Vec::new()));
+    let addr = Gate {
+        connected: false,
+        handled: Arc::clone(&handled),
+    }
+    .start();
+
+    addr.do_send(Request(1));
+    addr.do_send(Request(2));
+    addr.do_send(Connect);
+    addr.do_send(Request(3));
+    sleep(Duration::from_millis(10)).await;
+
+    assert_eq!(*handled.lock().unwrap(), vec![1, 2, 3]);
+}
+
+struct ShortLived(Arc<AtomicUsize>, u64);
+
+impl Actor for ShortLived {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        ctx.run_later(Duration::from_millis(self.1), |_, ctx| ctx.stop());
+    }
+
+    fn stopped(&mut self, _: &mut Self::Context) {
+        self.0.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[actix::test]
+async fn test_run_with_handle() {
+    let stopped = Arc::new(AtomicUsize::new(0));
+
+    let handles = (1..=3).map(|n| {
+        let (_addr, handle) =
+            Context::new().run_with_handle(ShortLived(Arc::clone(&stopped), n * 5));
+        handle
+    });
+
+    let results = futures_util::future::join_all(handles).await;
+    assert!(results.iter().all(Result::is_ok));
+    assert_eq!(stopped.load(Ordering::SeqCst), 3);
+}
+
+struct Urgent;
+
+impl ItemScheduler for Urgent {
+    fn priority(&mut self, item: &ItemInfo<'_>) -> i64 {
+        (item.label == Some("urgent")) as i64
+    }
+}
+
+struct Scheduled<S> {
+    scheduler: Option<S>,
+    log: Arc<Mutex<Vec<&'static str>>>,
+}
+
+impl<S: ItemScheduler + Unpin + 'static> Actor for Scheduled<S> {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        ctx.set_item_scheduler(self.scheduler.take().unwrap());
+        for label in ["a", "urgent", "c"].iter() {
+            let log = Arc::clone(&self.log);
+            ctx.spawn_labeled(
+                *label,
+                fut::wrap_future(async move {
+                    log.lock().unwrap().push(*label);
+                }),
+            );
+        }
+    }
+}
+
+#[actix::test]
+async fn test_item_scheduler() {
+    async fn run<S: ItemScheduler + Unpin + 'static>(scheduler: S) -> Vec<&'static str> {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let _addr = Scheduled {
+            scheduler: Some(scheduler),
+            log: Arc::clone(&log),
+        }
+        .start();
+        sleep(Duration::from_millis(5)).await;
+        let log = log.lock().unwrap();
+        log.clone()
+    }
+
+    assert_eq!(run(actix::OldestFirst).await, vec!["a", "urgent", "c"]);
+    assert_eq!(run(actix::NewestFirst).await, vec!["c", "urgent", "a"]);
+    assert_eq!(run(Urgent).await, vec!["urgent", "a", "c"]);
+}
+
+struct Tally(Vec<usize>);
+
+impl Actor for Tally {
+    type Context = Context<Self>;
+
+    fn started(&mut self, _: &mut Self::Context) {
+        self.0.push(0);
+    }
+}
+
+impl Handler<Request> for Tally {
+    type Result = ();
+
+    fn handle(&mut self, msg: Request, ctx: &mut Self::Context) {
+        self.0.push(msg.0);
+        if msg.0 == 0 {
+            ctx.stop();
+        }
+    }
+}
+
+#[test]
+fn test_poll_without_system() {
+    use std::future::Future;
+
+    let waker = futures_util::task::noop_waker();
+    let mut cx = StdContext::from_waker(&waker);
+
+    let ctx = Context::new();
+    let addr = ctx.address();
+    let mut fut = ctx.into_future(Tally(Vec::new()));
+
+    addr.do_send(Request(1));
+    assert!(Pin::new(&mut fut).poll(&mut cx).is_pending());
+    assert_eq!(fut.actor().0, vec![0, 1]);
+
+    addr.do_send(Request(2));
+    addr.do_send(Request(0));
+    assert!(Pin::new(&mut fut).poll(&mut cx).is_ready());
+    assert_eq!(fut.actor().0, vec![0, 1, 2, 0]);
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "polled after the actor has stopped")]
+fn test_poll_after_stop() {
+    use std::future::Future;
+
+    let waker = futures_util::task::noop_waker();
+    let mut cx = StdContext::from_waker(&waker);
+
+    let ctx = Context::new();
+    ctx.address().do_send(Request(0));
+    let mut fut = ctx.into_future(Tally(Vec::new()));
+    assert!(Pin::new(&mut fut).poll(&mut cx).is_ready());
+
+    // must not run the lifecycle again
+    let _ = Pin::new(&mut fut).poll(&mut cx);
+}
+
+struct Throttled(Arc<Mutex<Vec<usize>>>);
+
+impl Actor for Throttled {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        ctx.set_mailbox_rate_limit(2, Duration::from_millis(50));
+    }
+}
+
+impl Handler<Request> for Throttled {
+    type Result = ();
+
+    fn handle(&mut self, msg: Request, _: &mut Self::Context) {
+        self.0.lock().unwrap().push(msg.0);
+    }
+}
+
+#[actix::test]
+async fn test_mailbox_rate_limit() {
+    let handled = Arc::new(Mutex::new(Vec::new()));
+    let addr = Throttled(Arc::clone(&handled)).start();
+    for n in 0..5 {
+        addr.do_send(Request(n));
+    }
+
+    sleep(Duration::from_millis(25)).await;
+    assert_eq!(*handled.lock().unwrap(), vec![0, 1]);
+
+    sleep(Duration::from_millis(50)).await;
+    assert_eq!(*handled.lock().unwrap(), vec![0, 1, 2, 3]);
+
+    sleep(Duration::from_millis(50)).await;
+    assert_eq!(*handled.lock().unwrap(), vec![0, 1, 2, 3, 4]);
+}
+
+struct Backlog {
+    queue: Vec<usize>,
+    log: Arc<Mutex<Vec<usize>>>,
+}
+
+impl Backlog {
+    fn drain(&mut self, ctx: &mut Context<Self>) {
+        // one item per turn, mailbox messages get handled in between
+        if let Some(n) = self.queue.pop() {
+            self.log.lock().unwrap().push(n);
+            ctx.run_soon(Self::drain);
+        }
+    }
+}
+
+impl Actor for Backlog {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        self.drain(ctx);
+    }
+}
+
+impl Handler<Request> for Backlog {
+    type Result = ();
+
+    fn handle(&mut self, msg: Request, _: &mut Self::Context) {
+        self.log.lock().unwrap().push(msg.0);
+    }
+}
+
+#[actix::test]
+async fn test_run_soon() {
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let addr = Backlog {
+        queue: vec![3, 2, 1],
+        log: Arc::clone(&log),
+    }
+    .start();
+    addr.do_send(Request(100));
+
+    sleep(Duration::from_millis(5)).await;
+    assert_eq!(*log.lock().unwrap(), vec![1, 100, 2, 3]);
+}
+
+struct Observed(Arc<Mutex<Vec<String>>>);
+
+impl Actor for Observed {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        let log = Arc::clone(&self.0);
+        ctx.observe_messages(move |msg| {
+            let entry = match msg.downcast_ref::<Request>() {
+                Some(req) => format!("observed {}", req.0),
+                None => "observed other".to_owned(),
+            };
+            log.lock().unwrap().push(entry);
+        });
+    }
+}
+
+impl Handler<Request> for Observed {
+    type Result = ();
+
+    fn handle(&mut self, msg: Request, _: &mut Self::Context) {
+        self.0.lock().unwrap().push(format!("handled {}", msg.0));
+    }
+}
+
+impl Handler<Connect> for Observed {
+    type Result = ();
+
+    fn handle(&mut self, _: Connect, _: &mut Self::Context) {}
+}
+
+#[actix::test]
+async fn test_observe_messages() {
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let addr = Observed(Arc::clone(&log)).start();
+    addr.do_send(Request(1));
+    addr.send(Connect).await.unwrap();
+
+    assert_eq!(
+        *log.lock().unwrap(),
+        vec!["observed 1", "handled 1", "observed other"]
+    );
+}
+
+struct Joiner(Arc<Mutex<Vec<u64>>>);
+
+impl Actor for Joiner {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        let futs = [15, 5, 10].iter().map(|ms| {
+            let ms = *ms;
+            fut::wrap_future(async move {
+                sleep(Duration::from_millis(ms)).await;
+                ms
+            })
+        });
+        ctx.spawn_join(futs, |act, _, results| {
+            *act.0.lock().unwrap() = results;
+        });
+    }
+}
+
+#[actix::test]
+async fn test_spawn_join() {
+    let results = Arc::new(Mutex::new(Vec::new()));
+    let _addr = Joiner(Arc::clone(&results)).start();
+
+    sleep(Duration::from_millis(10)).await;
+    assert!(results.lock().unwrap().is_empty());
+
+    sleep(Duration::from_millis(20)).await;
+    assert_eq!(*results.lock().unwrap(), vec![5, 10, 15]);
+}
+
+struct Handshake {
+    version: Option<&'static str>,
+    frames: Arc<Mutex<Vec<String>>>,
+}
+
+impl Actor for Handshake {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        let stream = futures_util::stream::iter(vec!["v2", "a", "b"]);
+        ctx.add_stream_after_first(stream, |act, _, first| {
+            act.version = first;
+        });
+    }
+}
+
+impl StreamHandler<&'static str> for Handshake {
+    fn handle(&mut self, frame: &'static str, _: &mut Self::Context) {
+        let version = self.version.expect("handshake first");
+        self.frames
+            .lock()
+            .unwrap()
+            .push(format!("{}:{}", version, frame));
+    }
+}
+
+#[actix::test]
+async fn test_add_stream_after_first() {
+    let frames = Arc::new(Mutex::new(Vec::new()));
+    let _addr = Handshake {
+        version: None,
+        frames: Arc::clone(&frames),
+    }
+    .start();
+
+    sleep(Duration::from_millis(5)).await;
+    assert_eq!(*frames.lock().unwrap(), vec!["v2:a", "v2:b"]);
+}
+
+struct Member(Arc<Mutex<Vec<&'static str>>>);
+
+impl Member {
+    fn tick(ctx: &mut Context<Self>, role: &'static str) {
+        ctx.run_interval(Duration::from_millis(10), move |act, _| {
+            act.0.lock().unwrap().push(role);
+        });
+    }
+}
+
+impl Actor for Member {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        Self::tick(ctx, "leader");
+    }
+}
+
+struct Demote;
+
+impl Message for Demote {
+    type Result = ();
+}
+
+impl Handler<Demote> for Member {
+    type Result = ();
+
+    fn handle(&mut self, _: Demote, ctx: &mut Self::Context) {
+        ctx.transition(|ctx| Self::tick(ctx, "follower"));
+    }
+}
+
+struct Promote;
+
+impl Message for Promote {
+    type Result = ();
+}
+
+impl Handler<Promote> for Member {
+    type Result = ();
+
+    fn handle(&mut self, _: Promote, ctx: &mut Self::Context) {
+        let log = Arc::clone(&self.0);
+        let flush = ctx.spawn(fut::wrap_future(async move {
+            sleep(Duration::from_millis(25)).await;
+            log.lock().unwrap().push("flushed");
+        }));
+        ctx.transition_flushing(&[flush], Duration::from_secs(1), |ctx| {
+            Self::tick(ctx, "leader")
+        });
+    }
+}
+
+#[actix::test]
+async fn test_transition() {
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let addr = Member(Arc::clone(&log)).start();
+
+    sleep(Duration::from_millis(25)).await;
+    addr.send(Demote).await.unwrap();
+    sleep(Duration::from_millis(25)).await;
+    addr.send(Promote).await.unwrap();
+    sleep(Duration::from_millis(50)).await;
+
+    let mut log = log.lock().unwrap().clone();
+    log.dedup();
+    // nothing ticks while the sink flushes
+    assert_eq!(log, vec!["leader", "follower", "flushed", "leader"]);
+}
+
+struct Reassigned(Arc<Mutex<String>>);
+
+impl Actor for Reassigned {
+    type Context = Context<Self>;
+}
+
+impl Handler<Job> for Reassigned {
+    type Result = ();
+
+    fn handle(&mut self, job: Job, _: &mut Self::Context) {
+        self.0.lock().unwrap().push(job.0);
+    }
+}
+
+impl Handler<Demote> for Reassigned {
+    type Result = ();
+
+    fn handle(&mut self, _: Demote, ctx: &mut Self::Context) {
+        ctx.add_ordered_future(async {
+            sleep(Duration::from_millis(10)).await;
+            Job('a')
+        });
+        ctx.transition(|_| {});
+        ctx.add_ordered_future(async { Job('b') });
+    }
+}
+
+#[actix::test]
+async fn test_transition_keeps_context_features() {
+    let log = Arc::new(Mutex::new(String::new()));
+    let coordinator = actix::utils::ShutdownCoordinator::new();
+    let mut inbox = None;
+    let addr = Reassigned::create(|ctx| {
+        inbox = Some(ctx.weighted_inbox::<Job>(1));
+        ctx.join_shutdown(&coordinator, 0, Duration::from_secs(1));
+        Reassigned(Arc::clone(&log))
+    });
+    let inbox = inbox.unwrap();
+
+    addr.send(Demote).await.unwrap();
+    sleep(Duration::from_millis(20)).await;
+    inbox.send(Job('i')).unwrap();
+    sleep(Duration::from_millis(5)).await;
+    assert_eq!(*log.lock().unwrap(), "abi");
+
+    // draining waits for the open inbox
+    drop(inbox);
+    actix_rt::time::timeout(Duration::from_millis(100), coordinator.shutdown())
+        .await
+        .unwrap();
+    assert!(!addr.connected());
+}
+
+struct Timers(Arc<Mutex<Vec<usize>>>);
+
+impl Actor for Timers {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        let count = ctx.timer_count();
+        self.0.lock().unwrap().push(count);
+
+        ctx.run_later(Duration::from_millis(10), |act, ctx| {
+            act.0.lock().unwrap().push(ctx.timer_count());
+        });
+        let interval = ctx.run_interval(Duration::from_millis(100), |_, _| {});
+        ctx.notify_later(Ping, Duration::from_millis(20));
+        ctx.spawn_with_ttl(fut::ready(()), Duration::from_secs(1), |_, _| {});
+        ctx.set_idle_timeout(Duration::from_secs(1));
+        // the stash age timer is not armed while nothing is stashed
+        ctx.set_stash_age_limit(Duration::from_secs(1), |_, _, _| true);
+        let count = ctx.timer_count();
+        self.0.lock().unwrap().push(count);
+
+        ctx.cancel_future(interval);
+    }
+}
+
+impl Handler<Ping> for Timers {
+    type Result = ();
+
+    fn handle(&mut self, _: Ping, ctx: &mut Self::Context) {
+        self.0.lock().unwrap().push(ctx.timer_count());
+    }
+}
+
+#[actix::test]
+async fn test_timer_count() {
+    let counts = Arc::new(Mutex::new(Vec::new()));
+    let _addr = Timers(Arc::clone(&counts)).start();
+
+    sleep(Duration::from_millis(40)).await;
+    // the timer of a running item is still armed while it runs
+    assert_eq!(*counts.lock().unwrap(), vec![0, 5, 3, 2]);
+}
+
+struct Presence(Arc<Mutex<Vec<usize>>>);
+
+impl Actor for Presence {
+    type Context = Context<Self>;
+
+    fn started(&mut self, _: &mut Self::Context) {
+        self.0.lock().unwrap().push(0);
+    }
+}
+
+impl Handler<Request> for Presence {
+    type Result = ();
+
+    fn handle(&mut self, msg: Request, _: &mut Self::Context) {
+        self.0.lock().unwrap().push(msg.0);
+    }
+}
+
+#[actix::test]
+async fn test_run_lazy() {
+    let constructed = Arc::new(AtomicUsize::new(0));
+    let log = Arc::new(Mutex::new(Vec::new()));
+
+    let lazy = |constructed: &Arc<AtomicUsize>| {
+        let (constructed, log) = (Arc::clone(constructed), Arc::clone(&log));
+        Presence::create_lazy(move |_| {
+            constructed.fetch_add(1, Ordering::SeqCst);
+            Presence(log)
+        })
+    };
+
+    // never constructed without a message
+    drop(lazy(&constructed));
+    let addr = lazy(&constructed);
+    sleep(Duration::from_millis(10)).await;
+    assert_eq!(constructed.load(Ordering::SeqCst), 0);
+
+    addr.do_send(Request(1));
+    addr.send(Request(2)).await.unwrap();
+    assert_eq!(constructed.load(Ordering::SeqCst), 1);
+    assert_eq!(*log.lock().unwrap(), vec![0, 1, 2]);
+}
+
+struct Bursty {
+    fair: bool,
+    budget: Option<usize>,
+    handled: usize,
+    control_at: Arc<Mutex<Vec<usize>>>,
+}
+
+impl Actor for Bursty {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        ctx.set_fair_polling(self.fair);
+        if let Some(budget) = self.budget {
+            ctx.set_poll_budget(budget);
+        }
+        ctx.add_stream(futures_util::stream::iter(0..100));
+    }
+}
+
+impl StreamHandler<i32> for Bursty {
+    fn handle(&mut self, _: i32, _: &mut Self::Context) {
+        self.handled += 1;
+    }
+
+    fn finished(&mut self, _: &mut Self::Context) {}
+}
+
+impl Handler<Ping> for Bursty {
+    type Result = ();
+
+    fn handle(&mut self, _: Ping, _: &mut Self::Context) {
+        self.control_at.lock().unwrap().push(self.handled);
+    }
+}
+
+#[test]
+fn test_poll_budget_and_fair_polling() {
+    // streams yield 16 items per poll
+    let cases = [
+        (false, None, vec![0, 0, 0]),
+        (true, None, vec![0, 16, 16]),
+        (false, Some(1), vec![0, 16, 32]),
+    ];
+
+    for (fair, budget, expected) in cases.iter() {
+        let control_at = Arc::new(Mutex::new(Vec::new()));
+        let act = Bursty {
+            fair: *fair,
+            budget: *budget,
+            handled: 0,
+            control_at: Arc::clone(&control_at),
+        };
+
+        let sys = System::new();
+        sys.block_on(async move {
+            let addr = act.start();
+            for _ in 0..3 {
+                addr.do_send(Ping);
+            }
+            sleep(Duration::from_millis(20)).await;
+        });
+
+        assert_eq!(*control_at.lock().unwrap(), *expected, "fair: {}", fair);
+    }
+}
+
+struct Aggregator {
+    batches: Arc<Mutex<Vec<Vec<usize>>>>,
+    done: Arc<AtomicBool>,
+}
+
+impl Actor for Aggregator {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        use futures_util::stream::StreamExt;
+
+        let samples = vec![(0, 1), (0, 2), (35, 3), (0, 4), (60, 5)];
+        let stream = futures_util::stream::iter(samples).then(|(delay, n)| async move {
+            sleep(Duration::from_millis(delay)).await;
+            n
+        });
+        ctx.add_stream_windowed(stream, Duration::from_millis(20));
+    }
+}
+
+impl StreamHandler<Vec<usize>> for Aggregator {
+    fn handle(&mut self, batch: Vec<usize>, _: &mut Self::Context) {
+        self.batches.lock().unwrap().push(batch);
+    }
+
+    fn finished(&mut self, ctx: &mut Self::Context) {
+        self.done.store(true, Ordering::SeqCst);
+        ctx.stop();
+    }
+}
+
+#[actix::test]
+async fn test_add_stream_windowed() {
+    let batches = Arc::new(Mutex::new(Vec::new()));
+    let done = Arc::new(AtomicBool::new(false));
+    let _addr = Aggregator {
+        batches: Arc::clone(&batches),
+        done: Arc::clone(&done),
+    }
+    .start();
+
+    sleep(Duration::from_millis(150)).await;
+    // empty windows are skipped, the last partial one is flushed at the end
+    assert_eq!(
+        *batches.lock().unwrap(),
+        vec![vec![1, 2], vec![3, 4], vec![5]]
+    );
+    assert!(done.load(Ordering::SeqCst));
+}
+
+#[derive(Default)]
+struct Concurrency {
+    running: usize,
+    peak: usize,
+    started: Vec<usize>,
+}
+
+struct Bounded(Arc<Mutex<Concurrency>>);
+
+impl Actor for Bounded {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        ctx.set_max_concurrent(2);
+        for n in 0..5 {
+            let stats = Arc::clone(&self.0);
+            let handle = ctx.spawn_limited(fut::wrap_future(async move {
+                {
+                    let mut stats = stats.lock().unwrap();
+                    stats.running += 1;
+                    stats.peak = stats.peak.max(stats.running);
+                    stats.started.push(n);
+                }
+                sleep(Duration::from_millis(5)).await;
+                stats.lock().unwrap().running -= 1;
+            }));
+            if n == 3 {
+                ctx.cancel_future(handle);
+            }
+        }
+    }
+}
+
+#[actix::test]
+async fn test_spawn_limited() {
+    let stats = Arc::new(Mutex::new(Concurrency::default()));
+    let _addr = Bounded(Arc::clone(&stats)).start();
+
+    sleep(Duration::from_millis(50)).await;
+    let stats = stats.lock().unwrap();
+    assert_eq!(stats.peak, 2);
+    assert_eq!(stats.running, 0);
+    assert_eq!(stats.started, vec![0, 1, 2, 4]);
+}
+
+struct Connections {
+    opening: Arc<AtomicUsize>,
+    peak: Arc<AtomicUsize>,
+    received: Arc<Mutex<Vec<usize>>>,
+}
+
+impl Actor for Connections {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        use futures_util::stream::StreamExt;
+
+        ctx.set_max_pending_streams(1);
+        for id in 0..3 {
+            let (opening, peak) = (Arc::clone(&self.opening), Arc::clone(&self.peak));
+            ctx.add_fut_stream(async move {
+                let now = opening.fetch_add(1, Ordering::SeqCst) + 1;
+                peak.fetch_max(now, Ordering::SeqCst);
+                sleep(Duration::from_millis(5)).await;
+                opening.fetch_sub(1, Ordering::SeqCst);
+                // the opened connection stays up
+                once(async move { id }).chain(futures_util::stream::pending())
+            });
+        }
+    }
+}
+
+impl StreamHandler<usize> for Connections {
+    fn handle(&mut self, id: usize, _: &mut Self::Context) {
+        self.received.lock().unwrap().push(id);
+    }
+}
+
+#[actix::test]
+async fn test_add_fut_stream_limited() {
+    let peak = Arc::new(AtomicUsize::new(0));
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let _addr = Connections {
+        opening: Arc::default(),
+        peak: Arc::clone(&peak),
+        received: Arc::clone(&received),
+    }
+    .start();
+
+    // the slot is freed once a stream is added, not when it finishes
+    sleep(Duration::from_millis(40)).await;
+    assert_eq!(peak.load(Ordering::SeqCst), 1);
+    assert_eq!(*received.lock().unwrap(), vec![0, 1, 2]);
+}
+
+#[derive(Debug, PartialEq)]
+struct Reconnected(usize);
+
+struct Flaky;
+
+impl Actor for Flaky {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        assert_eq!(ctx.report_error(Reconnected(0)), Err(Reconnected(0)));
+    }
+}
+
+impl Handler<Request> for Flaky {
+    type Result = ();
+
+    fn handle(&mut self, msg: Request, ctx: &mut Self::Context) {
+        if msg.0 == 0 {
+            ctx.stop();
+        } else {
+            ctx.report_error(Reconnected(msg.0)).unwrap();
+        }
+    }
+}
+
+struct Subscribe;
+
+impl Message for Subscribe {
+    type Result = actix::utils::OutputStream<Reconnected>;
+}
+
+impl Handler<Subscribe> for Flaky {
+    type Result = MessageResult<Subscribe>;
+
+    fn handle(&mut self, _: Subscribe, ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(ctx.error_stream())
+    }
+}
+
+#[actix::test]
+async fn test_error_stream() {
+    use futures_util::stream::StreamExt;
+
+    let addr = Flaky.start();
+    let errors = addr.send(Subscribe).await.unwrap();
+    addr.do_send(Request(1));
+    addr.do_send(Request(2));
+    addr.do_send(Request(0));
+
+    let errors = errors.collect::<Vec<_>>().await;
+    assert_eq!(errors, vec![Reconnected(1), Reconnected(2)]);
+}
+
+#[test]
+fn test_poll_from_external_loop() {
+    use futures_util::task::{waker, ArcWake};
+    use std::future::Future;
+
+    struct Wakes(AtomicUsize);
+
+    impl ArcWake for Wakes {
+        fn wake_by_ref(arc_self: &Arc<Self>) {
+            arc_self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    let wakes = Arc::new(Wakes(AtomicUsize::new(0)));
+    let waker = waker(Arc::clone(&wakes));
+    let mut cx = StdContext::from_waker(&waker);
+
+    let ctx = Context::new();
+    let addr = ctx.address();
+    let mut fut = ctx.into_future(Tally(Vec::new()));
+    assert!(Pin::new(&mut fut).poll(&mut cx).is_pending());
+    assert_eq!(wakes.0.load(Ordering::SeqCst), 0);
+
+    // a new message wakes the external loop
+    addr.do_send(Request(1));
+    assert_eq!(wakes.0.load(Ordering::SeqCst), 1);
+    assert!(Pin::new(&mut fut).poll(&mut cx).is_pending());
+    assert_eq!(fut.actor().0, vec![0, 1]);
+}
+
+struct Inspected;
+
+impl Actor for Inspected {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        ctx.set_debug_typenames(true);
+        ctx.run_later(Duration::from_secs(10), |_, _| {});
+        ctx.spawn(fut::ready(()));
+    }
+}
+
+impl Handler<Tag> for Inspected {
+    type Result = MessageResult<Tag>;
+
+    fn handle(&mut self, _: Tag, ctx: &mut Self::Context) -> Self::Result {
+        let typenames = ctx.item_typenames();
+        if cfg!(debug_assertions) {
+            // completed futures are no longer listed
+            assert_eq!(typenames.len(), 1);
+            assert!(typenames[0].contains("TimerFunc"), "{:?}", typenames);
+        } else {
+            assert!(typenames.is_empty());
+        }
+        MessageResult(None)
+    }
+}
+
+#[actix::test]
+async fn test_item_typenames() {
+    let addr = Inspected.start();
+    sleep(Duration::from_millis(5)).await;
+    addr.send(Tag).await.unwrap();
+}
+
+#[derive(Debug)]
+struct Job(char);
+
+impl Message for Job {
+    type Result = ();
+}
+
+struct Tenants(Arc<Mutex<String>>);
+
+impl Actor for Tenants {
+    type Context = Context<Self>;
+}
+
+impl Handler<Job> for Tenants {
+    type Result = ();
+
+    fn handle(&mut self, job: Job, ctx: &mut Self::Context) {
+        self.0.lock().unwrap().push(job.0);
+        if job.0 == 'w' {
+            ctx.wait(sleep(Duration::from_millis(1)).into_actor(self));
+        }
+    }
+}
+
+#[actix::test]
+async fn test_weighted_inbox() {
+    let log = Arc::new(Mutex::new(String::new()));
+    let mut inboxes = None;
+    let _addr = Tenants::create(|ctx| {
+        inboxes = Some((ctx.weighted_inbox::<Job>(3), ctx.weighted_inbox::<Job>(1)));
+        Tenants(Arc::clone(&log))
+    });
+    let (noisy, quiet) = inboxes.unwrap();
+
+    for _ in 0..9 {
+        noisy.send(Job('n')).unwrap();
+    }
+    for _ in 0..4 {
+        quiet.send(Job('q')).unwrap();
+    }
+    drop(noisy);
+
+    sleep(Duration::from_millis(5)).await;
+    assert_eq!(*log.lock().unwrap(), "nnnqnnnqnnnqq");
+    assert!(!quiet.is_closed());
+}
+
+#[actix::test]
+async fn test_weighted_inbox_keeps_credit() {
+    let log = Arc::new(Mutex::new(String::new()));
+    let mut inboxes = None;
+    let _addr = Tenants::create(|ctx| {
+        inboxes = Some((ctx.weighted_inbox::<Job>(3), ctx.weighted_inbox::<Job>(1)));
+        Tenants(Arc::clone(&log))
+    });
+    let (noisy, quiet) = inboxes.unwrap();
+
+    // the round resumes after the wait with the credit left
+    for c in "nwnnnn".chars() {
+        noisy.send(Job(c)).unwrap();
+    }
+    for _ in 0..2 {
+        quiet.send(Job('q')).unwrap();
+    }
+
+    sleep(Duration::from_millis(20)).await;
+    assert_eq!(*log.lock().unwrap(), "nwnqnnnq");
+}
+
+struct Connection(&'static str);
+
+struct Prepared(Arc<Mutex<Vec<&'static str>>>);
+
+impl Actor for Prepared {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        let conn = ctx.get::<Connection>().unwrap();
+        self.0.lock().unwrap().push(conn.0);
+    }
+
+    fn stopped(&mut self, _: &mut Self::Context) {
+        self.0.lock().unwrap().push("stopped");
+    }
+}
+
+impl Handler<Ping> for Prepared {
+    type Result = ();
+
+    fn handle(&mut self, _: Ping, _: &mut Self::Context) {
+        self.0.lock().unwrap().push("ping");
+    }
+}
+
+#[actix::test]
+async fn test_prepare() {
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let addr = Prepared::create(|ctx| {
+        ctx.prepare(async {
+            sleep(Duration::from_millis(10)).await;
+            Ok::<_, ()>(Connection("connected"))
+        });
+        Prepared(Arc::clone(&log))
+    });
+    addr.send(Ping).await.unwrap();
+    assert_eq!(*log.lock().unwrap(), vec!["connected", "ping"]);
+
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let addr = Prepared::create(|ctx| {
+        ctx.prepare(async { Ok::<_, &str>(Connection("connected")) });
+        ctx.prepare(async { Err::<(), _>("refused") });
+        Prepared(Arc::clone(&log))
+    });
+    assert!(addr.send(Ping).await.is_err());
+    assert!(log.lock().unwrap().is_empty());
+}
+
+struct Requests(Arc<Mutex<Vec<usize>>>);
+
+impl Actor for Requests {
+    type Context = Context<Self>;
+}
+
+impl Handler<Request> for Requests {
+    type Result = ();
+
+    fn handle(&mut self, msg: Request, ctx: &mut Self::Context) {
+        let n = msg.0;
+        ctx.spawn_labeled(
+            format!("request-{}", n % 2),
+            sleep(Duration::from_millis(10))
+                .into_actor(self)
+                .map(move |_, act, _| act.0.lock().unwrap().push(n)),
+        );
+    }
+}
+
+struct CancelRequest(&'static str);
+
+impl Message for CancelRequest {
+    type Result = usize;
+}
+
+impl Handler<CancelRequest> for Requests {
+    type Result = usize;
+
+    fn handle(&mut self, msg: CancelRequest, ctx: &mut Self::Context) -> usize {
+        ctx.cancel_labeled(msg.0)
+    }
+}
+
+#[actix::test]
+async fn test_cancel_labeled() {
+    let done = Arc::new(Mutex::new(Vec::new()));
+    let addr = Requests(Arc::clone(&done)).start();
+    for n in 0..5 {
+        addr.do_send(Request(n));
+    }
+
+    assert_eq!(addr.send(CancelRequest("request-1")).await.unwrap(), 2);
+    assert_eq!(addr.send(CancelRequest("request-1")).await.unwrap(), 0);
+    assert_eq!(addr.send(CancelRequest("request-2")).await.unwrap(), 0);
+
+    sleep(Duration::from_millis(30)).await;
+    let mut done = done.lock().unwrap().clone();
+    done.sort_unstable();
+    assert_eq!(done, vec![0, 2, 4]);
+}
+
+struct KeepAlive(Arc<AtomicBool>);
+
+impl Actor for KeepAlive {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        ctx.set_idle_timeout(Duration::from_millis(30));
+        // a running future keeps the actor busy
+        ctx.spawn(sleep(Duration::from_millis(50)).into_actor(self));
+    }
+
+    fn stopped(&mut self, _: &mut Self::Context) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+}
+
+impl Handler<Ping> for KeepAlive {
+    type Result = ();
+
+    fn handle(&mut self, _: Ping, _: &mut Self::Context) {}
+}
+
+#[actix::test]
+async fn test_idle_timeout() {
+    let stopped = Arc::new(AtomicBool::new(false));
+    let addr = KeepAlive(Arc::clone(&stopped)).start();
+
+    sleep(Duration::from_millis(60)).await;
+    for _ in 0..4 {
+        addr.send(Ping).await.unwrap();
+        sleep(Duration::from_millis(15)).await;
+    }
+    assert!(!stopped.load(Ordering::SeqCst));
+
+    sleep(Duration::from_millis(60)).await;
+    assert!(stopped.load(Ordering::SeqCst));
+    assert!(!addr.connected());
+}
+
+#[actix::test]
+async fn test_idle_timeout_weighted_inbox() {
+    let log = Arc::new(Mutex::new(String::new()));
+    let mut inbox = None;
+    let addr = Tenants::create(|ctx| {
+        ctx.set_idle_timeout(Duration::from_millis(30));
+        inbox = Some(ctx.weighted_inbox::<Job>(1));
+        Tenants(Arc::clone(&log))
+    });
+    let inbox = inbox.unwrap();
+
+    // messages of the inbox are activity
+    for _ in 0..4 {
+        inbox.send(Job('i')).unwrap();
+        sleep(Duration::from_millis(15)).await;
+    }
+    assert!(addr.connected());
+
+    // an open inbox without messages does not keep the actor busy
+    sleep(Duration::from_millis(60)).await;
+    assert_eq!(*log.lock().unwrap(), "iiii");
+    assert!(!addr.connected());
+    assert!(inbox.is_closed());
+}
+
+struct Echo(actix::io::FramedWrite<String, tokio::io::WriteHalf<DuplexStream>, LinesCodec>);
+
+impl Actor for Echo {
+    type Context = Context<Self>;
+}
+
+impl actix::io::WriteHandler<LinesCodecError> for Echo {}
+
+impl StreamHandler<Result<String, LinesCodecError>> for Echo {
+    fn handle(&mut self, line: Result<String, LinesCodecError>, _: &mut Self::Context) {
+        self.0.write(line.unwrap().to_uppercase());
+    }
+}
+
+#[actix::test]
+async fn test_add_framed() {
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    let (client, server) = tokio::io::duplex(64);
+    let _addr = Echo::create(|ctx| {
+        let (_, writer) = ctx.add_framed(server, LinesCodec::new());
+        Echo(writer)
+    });
+
+    let (read, mut write) = tokio::io::split(client);
+    write.write_all(b"hello\nframed\n").await.unwrap();
+    let mut lines = BufReader::new(read).lines();
+    assert_eq!(lines.next_line().await.unwrap().unwrap(), "HELLO");
+    assert_eq!(lines.next_line().await.unwrap().unwrap(), "FRAMED");
+}
+
+struct Counter(usize);
+
+impl Actor for Counter {
+    type Context = Context<Self>;
+}
+
+impl Handler<Request> for Counter {
+    type Result = ();
+
+    fn handle(&mut self, msg: Request, ctx: &mut Self::Context) {
+        if msg.0 == 0 {
+            // bypasses the callback
+            self.0 = 0;
+        } else {
+            ctx.mutate_state(self, |act| act.0 += msg.0);
+        }
+    }
+}
+
+#[actix::test]
+async fn test_on_state_change() {
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let addr = Counter::create(|ctx| {
+        let seen = Arc::clone(&seen);
+        ctx.on_state_change(move |act| seen.lock().unwrap().push(act.0));
+        Counter(0)
+    });
+
+    for n in [1, 2, 0, 3].iter() {
+        addr.send(Request(*n)).await.unwrap();
+    }
+    assert_eq!(*seen.lock().unwrap(), vec![1, 3, 3]);
+}
+
+struct BoundedGate {
+    policy: actix::StashOverflow,
+    connected: bool,
+    handled: Vec<usize>,
+    dropped: Arc<Mutex<Vec<usize>>>,
+}
+
+impl Actor for BoundedGate {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        ctx.set_stash_limit(2, self.policy, |act: &mut Self, msg: Request, _| {
+            act.dropped.lock().unwrap().push(msg.0);
+        });
+    }
+}
+
+impl Handler<Request> for BoundedGate {
+    type Result = ();
+
+    fn handle(&mut self, msg: Request, ctx: &mut Self::Context) {
+        if self.connected {
+            self.handled.push(msg.0);
+        } else {
+            ctx.stash(msg);
+        }
+    }
+}
+
+impl Handler<Connect> for BoundedGate {
+    type Result = ();
+
+    fn handle(&mut self, _: Connect, ctx: &mut Self::Context) {
+        self.connected = true;
+        ctx.unstash();
+    }
+}
+
+struct Handled;
+
+impl Message for Handled {
+    type Result = Vec<usize>;
+}
+
+impl Handler<Handled> for BoundedGate {
+    type Result = MessageResult<Handled>;
+
+    fn handle(&mut self, _: Handled, _: &mut Self::Context) -> Self::Result {
+        MessageResult(self.handled.clone())
+    }
+}
+
+#[actix::test]
+async fn test_stash_limit() {
+    use actix::StashOverflow::*;
+
+    let cases = [
+        (DropOldest, vec![3, 4], vec![1, 2]),
+        (DropNewest, vec![1, 4], vec![2, 3]),
+        (Reject, vec![1, 2], vec![3, 4]),
+    ];
+    for (policy, handled, dropped) in cases.iter() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let addr = BoundedGate {
+            policy: *policy,
+            connected: false,
+            handled: Vec::new(),
+            dropped: Arc::clone(&log),
+        }
+        .start();
+
+        for n in 1..=4 {
+            addr.do_send(Request(n));
+        }
+        addr.do_send(Connect);
+
+        assert_eq!(addr.send(Handled).await.unwrap(), *handled);
+        assert_eq!(*log.lock().unwrap(), *dropped);
+    }
+}
+
+struct Pipeline(Arc<Mutex<Vec<String>>>);
+
+impl Actor for Pipeline {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        ctx.add_stream(futures_util::stream::iter(0..3usize));
+        ctx.add_control_stream(futures_util::stream::iter(vec!["pause", "resume"]));
+    }
+}
+
+impl StreamHandler<usize> for Pipeline {
+    fn handle(&mut self, item: usize, _: &mut Self::Context) {
+        self.0.lock().unwrap().push(item.to_string());
+    }
+}
+
+impl StreamHandler<&'static str> for Pipeline {
+    fn handle(&mut self, cmd: &'static str, _: &mut Self::Context) {
+        self.0.lock().unwrap().push(cmd.to_owned());
+    }
+
+    fn finished(&mut self, _: &mut Self::Context) {}
+}
+
+#[actix::test]
+async fn test_control_stream() {
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let _addr = Pipeline(Arc::clone(&log)).start();
+    sleep(Duration::from_millis(10)).await;
+
+    assert_eq!(*log.lock().unwrap(), vec!["pause", "resume", "0", "1", "2"]);
+}
+
+struct Download(Arc<Mutex<Vec<&'static str>>>);
+
+impl Actor for Download {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        let mut handles = Vec::new();
+        for name in ["progressing", "stuck"].iter() {
+            let name = *name;
+            handles.push(
+                ctx.spawn_with_ttl(
+                    sleep(Duration::from_millis(40))
+                        .into_actor(self)
+                        .map(move |_, act, _| act.0.lock().unwrap().push(name)),
+                    Duration::from_millis(20),
+                    move |act, _| act.0.lock().unwrap().push("expired"),
+                ),
+            );
+        }
+        let progressing = handles[0];
+        ctx.run_later(Duration::from_millis(10), move |_, ctx| {
+            assert!(ctx.extend_deadline(progressing, Duration::from_millis(40)));
+        });
+        ctx.run_later(Duration::from_millis(60), move |act, ctx| {
+            assert!(!ctx.extend_deadline(progressing, Duration::from_millis(40)));
+            assert!(!ctx.extend_deadline(ctx.handle(), Duration::from_millis(40)));
+            act.0.lock().unwrap().push("done");
+        });
+    }
+}
+
+#[actix::test]
+async fn test_extend_deadline() {
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let _addr = Download(Arc::clone(&events)).start();
+    sleep(Duration::from_millis(80)).await;
+
+    assert_eq!(
+        *events.lock().unwrap(),
+        vec!["expired", "progressing", "done"]
+    );
+}
+
+struct Spawner(Arc<Mutex<Vec<String>>>);
+
+impl Spawner {
+    fn log(&self, event: String) {
+        self.0.lock().unwrap().push(event);
+    }
+}
+
+impl Actor for Spawner {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        ctx.spawn(async {}.into_actor(self).map(|_, act, ctx| {
+            act.log("A".to_owned());
+            ctx.spawn(
+                async {}
+                    .into_actor(act)
+                    .map(|_, act, _| act.log("B".to_owned())),
+            );
+            ctx.stash(Request(1));
+            ctx.unstash();
+        }));
+        ctx.spawn(
+            async {}
+                .into_actor(self)
+                .map(|_, act, _| act.log("X".to_owned())),
+        );
+    }
+}
+
+impl Handler<Request> for Spawner {
+    type Result = ();
+
+    fn handle(&mut self, msg: Request, _: &mut Self::Context) {
+        self.log(format!("msg {}", msg.0));
+    }
+}
+
+#[actix::test]
+async fn test_spawn_during_pass() {
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let _addr = Spawner(Arc::clone(&log)).start();
+    sleep(Duration::from_millis(5)).await;
+
+    // `B` is polled in the next pass, after the stashed message
+    assert_eq!(*log.lock().unwrap(), vec!["A", "X", "msg 1", "B"]);
+}
+
+/// Future that wakes itself on every poll.
+struct Spin(Arc<AtomicUsize>);
+
+impl ActorFuture<Coalesced> for Spin {
+    type Output = ();
+
+    fn poll(
+        self: Pin<&mut Self>,
+        _: &mut Coalesced,
+        _: &mut Context<Coalesced>,
+        task: &mut StdContext<'_>,
+    ) -> Poll<()> {
+        self.0.fetch_add(1, Ordering::SeqCst);
+        task.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+struct Coalesced(Arc<AtomicUsize>);
+
+impl Actor for Coalesced {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        ctx.coalesce_wakeups(Duration::from_millis(20));
+        ctx.spawn(Spin(Arc::clone(&self.0)));
+    }
+}
+
+#[actix::test]
+async fn test_coalesce_wakeups() {
+    let polls = Arc::new(AtomicUsize::new(0));
+    let _addr = Coalesced(Arc::clone(&polls)).start();
+
+    // one pass per window instead of one per wakeup
+    sleep(Duration::from_millis(110)).await;
+    let polls = polls.load(Ordering::SeqCst);
+    assert!((4..=7).contains(&polls), "polled {} times", polls);
+}
+
+struct Lingering(Arc<AtomicBool>);
+
+impl Actor for Lingering {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        ctx.coalesce_wakeups(Duration::from_secs(1));
+        ctx.spawn(sleep(Duration::from_secs(10)).into_actor(self));
+    }
+
+    fn stopped(&mut self, _: &mut Self::Context) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+}
+
+#[actix::test]
+async fn test_coalesce_drop_in_window() {
+    use std::future::Future;
+
+    let stopped = Arc::new(AtomicBool::new(false));
+    let mut fut = Context::new().into_future(Lingering(Arc::clone(&stopped)));
+    let waker = futures_util::task::noop_waker();
+    let mut cx = StdContext::from_waker(&waker);
+    assert!(Pin::new(&mut fut).poll(&mut cx).is_pending());
+
+    // dropped inside the window, e.g. on arbiter shutdown
+    drop(fut);
+    assert!(stopped.load(Ordering::SeqCst));
+}
+
+struct Watched(Arc<AtomicUsize>);
+
+impl Actor for Watched {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        let stalls = Arc::clone(&self.0);
+        ctx.set_watchdog(Duration::from_millis(20), move || {
+            stalls.fetch_add(1, Ordering::SeqCst);
+        });
+    }
+}
+
+impl Handler<Ping> for Watched {
+    type Result = ();
+
+    fn handle(&mut self, _: Ping, _: &mut Self::Context) {}
+}
+
+#[actix::test]
+async fn test_watchdog() {
+    let stalls = Arc::new(AtomicUsize::new(0));
+    let addr = Watched(Arc::clone(&stalls)).start();
+
+    // reported once per stall
+    sleep(Duration::from_millis(50)).await;
+    assert_eq!(stalls.load(Ordering::SeqCst), 1);
+
+    addr.send(Ping).await.unwrap();
+    assert_eq!(stalls.load(Ordering::SeqCst), 1);
+    sleep(Duration::from_millis(50)).await;
+    assert_eq!(stalls.load(Ordering::SeqCst), 2);
+}
+
+struct Timed;
+
+impl Actor for Timed {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        assert_eq!(ctx.latency_stats(), None);
+        ctx.track_latency();
+    }
+}
+
+struct Work(u64);
+
+impl Message for Work {
+    type Result = ();
+}
+
+impl Handler<Work> for Timed {
+    type Result = ();
+
+    fn handle(&mut self, msg: Work, _: &mut Self::Context) {
+        std::thread::sleep(Duration::from_millis(msg.0));
+    }
+}
+
+struct Stats;
+
+impl Message for Stats {
+    type Result = Option<actix::LatencyStats>;
+}
+
+impl Handler<Stats> for Timed {
+    type Result = Option<actix::LatencyStats>;
+
+    fn handle(&mut self, _: Stats, ctx: &mut Self::Context) -> Self::Result {
+        ctx.latency_stats()
+    }
+}
+
+#[actix::test]
+async fn test_latency_stats() {
+    let addr = Timed.start();
+    for _ in 0..9 {
+        addr.do_send(Work(1));
+    }
+    addr.send(Work(20)).await.unwrap();
+
+    let stats = addr.send(Stats).await.unwrap().unwrap();
+    assert_eq!(stats.count, 10);
+    assert!(stats.min >= Duration::from_millis(1));
+    assert!(stats.max >= Duration::from_millis(20));
+    assert!(stats.p50 < Duration::from_millis(10), "{:?}", stats);
+    assert_eq!(stats.p99, stats.max);
+    assert!(stats.mean > stats.p50 && stats.mean < stats.max);
+}
+
+struct Heartbeat(Arc<AtomicUsize>);
+
+impl Actor for Heartbeat {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        ctx.expect_within(Duration::from_millis(30), |act, ctx| {
+            act.0.fetch_add(1, Ordering::SeqCst);
+            ctx.stop();
+        });
+    }
+}
+
+impl Handler<Ping> for Heartbeat {
+    type Result = ();
+
+    fn handle(&mut self, _: Ping, _: &mut Self::Context) {}
+}
+
+#[actix::test]
+async fn test_expect_within() {
+    let timeouts = Arc::new(AtomicUsize::new(0));
+    let addr = Heartbeat(Arc::clone(&timeouts)).start();
+
+    // heartbeats keep the timer from firing
+    for _ in 0..4 {
+        sleep(Duration::from_millis(15)).await;
+        addr.send(Ping).await.unwrap();
+    }
+    assert_eq!(timeouts.load(Ordering::SeqCst), 0);
+
+    sleep(Duration::from_millis(50)).await;
+    assert_eq!(timeouts.load(Ordering::SeqCst), 1);
+    assert!(!addr.connected());
+}
+
+/// Decodes lines into requests.
+struct RequestCodec(LinesCodec);
+
+impl tokio_util::codec::Decoder for RequestCodec {
+    type Item = Request;
+    type Error = LinesCodecError;
+
+    fn decode(
+        &mut self,
+        src: &mut bytes::BytesMut,
+    ) -> Result<Option<Request>, LinesCodecError> {
+        match self.0.decode(src)? {
+            Some(line) => line.parse().map(|n| Some(Request(n))).map_err(|_| {
+                std::io::Error::new(std::io::ErrorKind::InvalidData, "not a number").into()
+            }),
+            None => Ok(None),
+        }
+    }
+}
+
+struct Ipc(Arc<Mutex<Vec<usize>>>);
+
+impl Actor for Ipc {
+    type Context = Context<Self>;
+}
+
+impl Handler<Request> for Ipc {
+    type Result = ();
+
+    fn handle(&mut self, msg: Request, _: &mut Self::Context) {
+        self.0.lock().unwrap().push(msg.0);
+    }
+}
+
+#[actix::test]
+async fn test_codec_inbox() {
+    use futures_util::stream::StreamExt;
+    use tokio::io::AsyncWriteExt;
+
+    let handled = Arc::new(Mutex::new(Vec::new()));
+    let (mut client, server) = tokio::io::duplex(64);
+    let mut errors = None;
+    let _addr = Ipc::create(|ctx| {
+        errors = Some(ctx.error_stream::<LinesCodecError>());
+        ctx.add_codec_inbox(server, RequestCodec(LinesCodec::new()));
+        Ipc(Arc::clone(&handled))
+    });
+
+    // a decode error ends the inbox
+    client.write_all(b"1\n2\nx\n3\n").await.unwrap();
+    let err = errors.unwrap().next().await.unwrap();
+    assert!(matches!(err, LinesCodecError::Io(_)));
+    sleep(Duration::from_millis(10)).await;
+    assert_eq!(*handled.lock().unwrap(), vec![1, 2]);
+}
+
+struct Inboxes;
+
+impl Message for Inboxes {
+    type Result = Vec<actix::InboxStat>;
+}
+
+impl Handler<Inboxes> for Tenants {
+    type Result = Vec<actix::InboxStat>;
+
+    fn handle(&mut self, _: Inboxes, ctx: &mut Self::Context) -> Self::Result {
+        ctx.inbox_stats()
+    }
+}
+
+#[actix::test]
+async fn test_inbox_stats() {
+    let stat = |label: &str, closed, queued| actix::InboxStat {
+        label: label.to_owned(),
+        closed,
+        queued,
+    };
+
+    let mut inboxes = None;
+    let addr = Tenants::create(|ctx| {
+        inboxes = Some((
+            ctx.labeled_inbox::<Job>("control", 1),
+            ctx.labeled_inbox::<Job>("tenant-7", 1),
+        ));
+        Tenants(Arc::new(Mutex::new(String::new())))
+    });
+    let (control, tenant) = inboxes.unwrap();
+    sleep(Duration::from_millis(5)).await;
+
+    // the mailbox is handled before the inboxes are drained
+    for _ in 0..4 {
+        tenant.send(Job('t')).unwrap();
+    }
+    let stats = addr.send(Inboxes).await.unwrap();
+    assert_eq!(
+        stats,
+        vec![
+            stat("mailbox", false, 0),
+            stat("control", false, 0),
+            stat("tenant-7", false, 4),
+        ]
+    );
+
+    drop(control);
+    let stats = addr.send(Inboxes).await.unwrap();
+    assert_eq!(stats[1], stat("control", true, 0));
+    assert_eq!(stats[2], stat("tenant-7", false, 0));
+}
+
+struct Abandoned(Arc<Mutex<Vec<actix::InboxStat>>>);
+
+impl Actor for Abandoned {
+    type Context = Context<Self>;
+
+    fn stopped(&mut self, ctx: &mut Self::Context) {
+        *self.0.lock().unwrap() = ctx.inbox_stats();
+    }
+}
+
+impl Handler<Job> for Abandoned {
+    type Result = ();
+
+    fn handle(&mut self, _: Job, ctx: &mut Self::Context) {
+        // the inbox is dropped while the actor waits
+        ctx.drain_and_stop(Duration::from_millis(5));
+        ctx.wait(sleep(Duration::from_millis(50)).into_actor(self));
+    }
+}
+
+#[actix::test]
+async fn test_inbox_stats_dropped_messages() {
+    let stats = Arc::new(Mutex::new(Vec::new()));
+    let mut inbox = None;
+    let _addr = Abandoned::create(|ctx| {
+        inbox = Some(ctx.labeled_inbox::<Job>("jobs", 1));
+        Abandoned(Arc::clone(&stats))
+    });
+    let inbox = inbox.unwrap();
+    for _ in 0..3 {
+        inbox.send(Job('j')).unwrap();
+    }
+
+    sleep(Duration::from_millis(20)).await;
+    let stats = stats.lock().unwrap();
+    assert_eq!(stats[1].label, "jobs");
+    assert_eq!(stats[1].queued, 0);
+    assert!(inbox.is_closed());
+}
+
+struct Completions(Arc<Mutex<Vec<&'static str>>>);
+
+impl Actor for Completions {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        let job = ctx.spawn(sleep(Duration::from_millis(10)).into_actor(self));
+        let cancelled = ctx.spawn(sleep(Duration::from_millis(10)).into_actor(self));
+        let done = ctx.spawn(fut::ready(()));
+
+        assert!(ctx.on_complete(job, |act, _| act.0.lock().unwrap().push("job")));
+        assert!(ctx.on_complete(cancelled, |act, _| {
+            act.0.lock().unwrap().push("cancelled")
+        }));
+        ctx.cancel_future(cancelled);
+
+        // completed in the first pass
+        ctx.run_later(Duration::from_millis(1), move |act, ctx| {
+            assert!(!ctx.on_complete(done, |_, _| unreachable!()));
+            act.0.lock().unwrap().push("later");
+        });
+    }
+}
+
+#[actix::test]
+async fn test_on_complete() {
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let _addr = Completions(Arc::clone(&log)).start();
+
+    sleep(Duration::from_millis(30)).await;
+    assert_eq!(*log.lock().unwrap(), vec!["later", "job"]);
+}
+
+struct Adaptive;
+
+impl Actor for Adaptive {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        ctx.set_adaptive_capacity(2, 16);
+    }
+}
+
+impl Handler<Ping> for Adaptive {
+    type Result = ();
+
+    fn handle(&mut self, _: Ping, _: &mut Self::Context) {}
+}
+
+struct Capacity;
+
+impl Message for Capacity {
+    type Result = usize;
+}
+
+impl Handler<Capacity> for Adaptive {
+    type Result = usize;
+
+    fn handle(&mut self, _: Capacity, ctx: &mut Self::Context) -> usize {
+        use actix::dev::AsyncContextParts;
+        ctx.parts().capacity()
+    }
+}
+
+#[actix::test]
+async fn test_adaptive_capacity() {
+    let addr = Adaptive.start();
+    assert_eq!(addr.send(Capacity).await.unwrap(), 16);
+
+    // a backlog shrinks the capacity, an empty mailbox grows it again
+    for _ in 0..14 {
+        addr.do_send(Ping);
+    }
+    assert_eq!(addr.send(Capacity).await.unwrap(), 8);
+    assert_eq!(addr.send(Capacity).await.unwrap(), 16);
+}
+
+#[derive(Default)]
+struct Starved {
+    forced: bool,
+    handled: Vec<usize>,
+    aged: Arc<Mutex<Vec<usize>>>,
+}
+
+impl Actor for Starved {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        ctx.set_stash_age_limit(Duration::from_millis(20), |act, msg, ctx| {
+            let msg = msg.downcast_ref::<Request>().unwrap();
+            act.aged.lock().unwrap().push(msg.0);
+            // force the first message, drop the others
+            if msg.0 == 1 {
+                act.forced = true;
+                ctx.unstash();
+            }
+            msg.0 == 1
+        });
+    }
+}
+
+impl Handler<Request> for Starved {
+    type Result = ();
+
+    fn handle(&mut self, msg: Request, ctx: &mut Self::Context) {
+        if self.forced {
+            self.handled.push(msg.0);
+        } else {
+            ctx.stash(msg);
+        }
+    }
+}
+
+impl Handler<Handled> for Starved {
+    type Result = MessageResult<Handled>;
+
+    fn handle(&mut self, _: Handled, ctx: &mut Self::Context) -> Self::Result {
+        assert_eq!(ctx.stashed(), 0);
+        MessageResult(self.handled.clone())
+    }
+}
+
+#[actix::test]
+async fn test_stash_age_limit() {
+    let aged = Arc::new(Mutex::new(Vec::new()));
+    let addr = Starved {
+        aged: Arc::clone(&aged),
+        ..Default::default()
+    }
+    .start();
+
+    addr.do_send(Request(1));
+    addr.do_send(Request(2));
+    sleep(Duration::from_millis(10)).await;
+    assert!(aged.lock().unwrap().is_empty());
+
+    sleep(Duration::from_millis(40)).await;
+    assert_eq!(*aged.lock().unwrap(), vec![1, 2]);
+    assert_eq!(addr.send(Handled).await.unwrap(), vec![1]);
+}
+
+struct Eager(Arc<AtomicBool>);
+
+impl Actor for Eager {
+    type Context = Context<Self>;
+
+    fn started(&mut self, _: &mut Self::Context) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+}
+
+impl Handler<Ping> for Eager {
+    type Result = ();
+
+    fn handle(&mut self, _: Ping, _: &mut Self::Context) {}
+}
+
+#[actix::test]
+async fn test_run_eager() {
+    let started = Arc::new(AtomicBool::new(false));
+    let addr = Context::new().run(Eager(Arc::clone(&started)));
+    assert!(!started.load(Ordering::SeqCst));
+    addr.send(Ping).await.unwrap();
+    assert!(started.load(Ordering::SeqCst));
+
+    let started = Arc::new(AtomicBool::new(false));
+    let addr = Context::new().run_eager(Eager(Arc::clone(&started)));
+    assert!(started.load(Ordering::SeqCst));
+    addr.send(Ping).await.unwrap();
+}
+
+struct Phased {
+    name: &'static str,
+    phase: u8,
+    log: Arc<Mutex<Vec<&'static str>>>,
+    coordinator: actix::utils::ShutdownCoordinator,
+}
+
+impl Actor for Phased {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        ctx.join_shutdown(&self.coordinator, self.phase, Duration::from_secs(1));
+        // phase 0 drains a pending request before stopping
+        if self.phase == 0 {
+            sleep(Duration::from_millis(20)).into_actor(self).spawn(ctx);
+        }
+    }
+
+    fn stopped(&mut self, _: &mut Self::Context) {
+        self.log.lock().unwrap().push(self.name);
+    }
+}
+
+#[actix::test]
+async fn test_shutdown_coordinator() {
+    let coordinator = actix::utils::ShutdownCoordinator::new();
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let mut addrs = Vec::new();
+    for &(name, phase) in &[("pool", 1), ("listener", 0), ("cache", 1)] {
+        let phased = Phased {
+            name,
+            phase,
+            log: Arc::clone(&log),
+            coordinator: coordinator.clone(),
+        };
+        addrs.push(phased.start());
+    }
+    sleep(Duration::from_millis(5)).await;
+
+    coordinator.shutdown().await;
+    assert!(addrs.iter().all(|addr| !addr.connected()));
+    let mut log = log.lock().unwrap().clone();
+    assert_eq!(log.remove(0), "listener");
+    log.sort_unstable();
+    assert_eq!(log, vec!["cache", "pool"]);
+}
+
+struct Rejoined {
+    restarts: Arc<AtomicUsize>,
+    stopped: Arc<AtomicUsize>,
+}
+
+impl Actor for Rejoined {
+    type Context = Context<Self>;
+
+    fn stopped(&mut self, _: &mut Self::Context) {
+        self.stopped.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+impl actix::Supervised for Rejoined {
+    fn restarting(&mut self, _: &mut Self::Context) {
+        self.restarts.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+impl Handler<Ping> for Rejoined {
+    type Result = ();
+
+    fn handle(&mut self, _: Ping, ctx: &mut Self::Context) {
+        ctx.stop();
+    }
+}
+
+#[actix::test]
+async fn test_shutdown_after_restart() {
+    let coordinator = actix::utils::ShutdownCoordinator::new();
+    let (restarts, stopped) = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)));
+
+    let addr = {
+        let (restarts, stopped) = (Arc::clone(&restarts), Arc::clone(&stopped));
+        let coordinator = coordinator.clone();
+        Supervisor::start(move |ctx: &mut Context<Rejoined>| {
+            ctx.join_shutdown(&coordinator, 0, Duration::from_secs(1));
+            Rejoined { restarts, stopped }
+        })
+    };
+    addr.send(Ping).await.unwrap();
+    sleep(Duration::from_millis(5)).await;
+    assert_eq!(restarts.load(Ordering::SeqCst), 1);
+
+    // the membership survives the restart, and the actor stays stopped
+    actix_rt::time::timeout(Duration::from_millis(100), coordinator.shutdown())
+        .await
+        .unwrap();
+    assert_eq!(restarts.load(Ordering::SeqCst), 1);
+    assert_eq!(stopped.load(Ordering::SeqCst), 2);
+    assert!(!addr.connected());
+}
+
+#[actix::test]
+async fn test_joined_shutdown_does_not_keep_alive() {
+    let coordinator = actix::utils::ShutdownCoordinator::new();
+    let stopped = Arc::new(AtomicUsize::new(0));
+
+    let addr = Rejoined::create(|ctx| {
+        ctx.join_shutdown(&coordinator, 0, Duration::from_secs(1));
+        Rejoined {
+            restarts: Arc::default(),
+            stopped: Arc::clone(&stopped),
+        }
+    });
+    drop(addr);
+    sleep(Duration::from_millis(5)).await;
+    assert_eq!(stopped.load(Ordering::SeqCst), 1);
+
+    // nothing left to wait for
+    actix_rt::time::timeout(Duration::from_millis(100), coordinator.shutdown())
+        .await
+        .unwrap();
+}
+
+struct Visited;
+
+impl Actor for Visited {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        ctx.spawn_labeled("sub:a", sleep(Duration::from_secs(10)).into_actor(self));
+        ctx.spawn(async {}.into_actor(self));
+        ctx.spawn(sleep(Duration::from_secs(10)).into_actor(self));
+    }
+}
+
+struct Items;
+
+impl Message for Items {
+    type Result = Vec<Option<String>>;
+}
+
+impl Handler<Items> for Visited {
+    type Result = MessageResult<Items>;
+
+    fn handle(&mut self, _: Items, ctx: &mut Self::Context) -> Self::Result {
+        let mut items = Vec::new();
+        ctx.for_each_item(|item| items.push(item.label.map(str::to_owned)));
+        MessageResult(items)
+    }
+}
+
+#[actix::test]
+async fn test_for_each_item() {
+    let addr = Visited.start();
+    sleep(Duration::from_millis(5)).await;
+    let items = addr.send(Items).await.unwrap();
+    assert_eq!(items, vec![Some("sub:a".to_owned()), None]);
+}
+
+struct Downstream(actix::utils::CircuitBreaker);
+
+impl Actor for Downstream {
+    type Context = Context<Self>;
+}
+
+struct Call {
+    ok: bool,
+    delay: u64,
+}
+
+impl Message for Call {
+    type Result = bool;
+}
+
+impl Handler<Call> for Downstream {
+    type Result = bool;
+
+    fn handle(&mut self, msg: Call, ctx: &mut Self::Context) -> bool {
+        let Call { ok, delay } = msg;
+        let mk = || {
+            async move {
+                sleep(Duration::from_millis(delay)).await;
+                if ok {
+                    Ok(())
+                } else {
+                    Err("unavailable")
+                }
+            }
+            .into_actor(self)
+        };
+        ctx.spawn_with_breaker(mk, &self.0.clone()).is_some()
+    }
+}
+
+#[actix::test]
+async fn test_spawn_with_breaker() {
+    use actix::utils::{BreakerState, CircuitBreaker};
+
+    let breaker = CircuitBreaker::new(2, Duration::from_millis(30));
+    let addr = Downstream(breaker.clone()).start();
+    let call = |ok, delay| addr.send(Call { ok, delay });
+
+    assert!(call(false, 0).await.unwrap());
+    assert!(call(false, 0).await.unwrap());
+    sleep(Duration::from_millis(5)).await;
+    assert_eq!(breaker.state(), BreakerState::Open);
+    assert!(!call(true, 0).await.unwrap());
+
+    // a single probe is let through after the cooldown
+    sleep(Duration::from_millis(30)).await;
+    assert_eq!(breaker.state(), BreakerState::HalfOpen);
+    assert!(call(true, 10).await.unwrap());
+    assert!(!call(true, 0).await.unwrap());
+    sleep(Duration::from_millis(20)).await;
+    assert_eq!(breaker.state(), BreakerState::Closed);
+    assert!(call(true, 0).await.unwrap());
+}
+
+struct Daemon(Arc<AtomicBool>);
+
+impl Actor for Daemon {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        ctx.set_perpetual();
+    }
+
+    fn stopped(&mut self, _: &mut Self::Context) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+}
+
+#[actix::test]
+async fn test_perpetual() {
+    // losing the last address is reported, the actor still stops
+    let stopped = Arc::new(AtomicBool::new(false));
+    drop(Daemon(Arc::clone(&stopped)).start());
+    sleep(Duration::from_millis(10)).await;
+    assert!(stopped.load(Ordering::SeqCst));
+}
+
+struct Child(&'static str, Arc<Mutex<Vec<&'static str>>>);
+
+impl Actor for Child {
+    type Context = Context<Self>;
+
+    fn stopped(&mut self, _: &mut Self::Context) {
+        self.1.lock().unwrap().push(self.0);
+    }
+}
+
+impl Handler<Ping> for Child {
+    type Result = ();
+
+    fn handle(&mut self, _: Ping, _: &mut Self::Context) {}
+}
+
+struct Parent {
+    log: Arc<Mutex<Vec<&'static str>>>,
+    children: Vec<(SpawnHandle, Addr<Child>)>,
+}
+
+impl Actor for Parent {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        for name in &["first", "second"] {
+            let child = Child(name, Arc::clone(&self.log));
+            self.children.push(ctx.spawn_child(Context::new(), child));
+        }
+    }
+}
+
+struct Children;
+
+impl Message for Children {
+    type Result = Vec<Addr<Child>>;
+}
+
+impl Handler<Children> for Parent {
+    type Result = MessageResult<Children>;
+
+    fn handle(&mut self, _: Children, _: &mut Self::Context) -> Self::Result {
+        MessageResult(self.children.iter().map(|child| child.1.clone()).collect())
+    }
+}
+
+struct Release(bool);
+
+impl Message for Release {
+    type Result = ();
+}
+
+impl Handler<Release> for Parent {
+    type Result = ();
+
+    // cancel the first child, or stop the parent
+    fn handle(&mut self, msg: Release, ctx: &mut Self::Context) {
+        if msg.0 {
+            ctx.cancel_future(self.children[0].0);
+        } else {
+            ctx.stop();
+        }
+    }
+}
+
+#[actix::test]
+async fn test_spawn_child() {
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let parent = Parent {
+        log: Arc::clone(&log),
+        children: Vec::new(),
+    }
+    .start();
+    let children = parent.send(Children).await.unwrap();
+    for child in &children {
+        child.send(Ping).await.unwrap();
+    }
+
+    parent.send(Release(true)).await.unwrap();
+    sleep(Duration::from_millis(5)).await;
+    assert_eq!(*log.lock().unwrap(), vec!["first"]);
+    assert!(!children[0].connected());
+    assert!(children[1].connected());
+
+    // stopping the parent tears down the remaining child
+    parent.send(Release(false)).await.unwrap();
+    sleep(Duration::from_millis(5)).await;
+    assert_eq!(*log.lock().unwrap(), vec!["first", "second"]);
+    assert!(!children[1].connected());
+}
+
+#[cfg(feature = "metrics")]
+#[derive(Clone, Default)]
+struct Registry(Arc<Mutex<std::collections::HashMap<String, f64>>>);
+
+#[cfg(feature = "metrics")]
+impl actix::MetricsSink for Registry {
+    fn incr(&self, name: &str, by: u64) {
+        *self.0.lock().unwrap().entry(name.to_owned()).or_default() += by as f64;
+    }
+
+    fn gauge(&self, name: &str, value: f64) {
+        self.0.lock().unwrap().insert(name.to_owned(), value);
+    }
+}
+
+#[cfg(feature = "metrics")]
+struct Measured(Registry);
+
+#[cfg(feature = "metrics")]
+impl Actor for Measured {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        ctx.set_metrics_sink(self.0.clone());
+        ctx.spawn(async {}.into_actor(self));
+        ctx.spawn(sleep(Duration::from_secs(10)).into_actor(self));
+    }
+}
+
+#[cfg(feature = "metrics")]
+impl Handler<Ping> for Measured {
+    type Result = ();
+
+    fn handle(&mut self, _: Ping, _: &mut Self::Context) {}
+}
+
+#[cfg(feature = "metrics")]
+#[actix::test]
+async fn test_metrics_sink() {
+    let registry = Registry::default();
+    let addr = Measured(registry.clone()).start();
+    for _ in 0..3 {
+        addr.send(Ping).await.unwrap();
+    }
+    sleep(Duration::from_millis(5)).await;
+
+    let metrics = registry.0.lock().unwrap();
+    assert_eq!(metrics["messages_handled"], 3.0);
+    assert_eq!(metrics["items_completed"], 1.0);
+    assert_eq!(metrics["items_pending"], 1.0);
+    assert_eq!(metrics["messages_queued"], 0.0);
+}
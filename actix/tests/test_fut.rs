@@ -53,8 +53,8 @@ impl Actor for MyStreamActor {
 
     fn started(&mut self, ctx: &mut Self::Context) {
         let mut s = futures_util::stream::FuturesOrdered::new();
-        s.push(sleep(Duration::from_millis(20)));
-        s.push(sleep(Duration::from_millis(20)));
+        s.push_back(sleep(Duration::from_millis(20)));
+        s.push_back(sleep(Duration::from_millis(20)));
 
         s.into_actor(self)
             .timeout(Duration::from_millis(1))
@@ -254,3 +254,140 @@ fn test_try_future() {
         assert_eq!(res.err().unwrap(), 996u32);
     })
 }
+
+struct PanicActor {
+    caught: Arc<AtomicBool>,
+}
+
+impl Actor for PanicActor {
+    type Context = actix::Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        async {
+            panic!("buggy future");
+        }
+        .into_actor(self)
+        .catch_unwind()
+        .map(|res: Result<(), _>, act, _| {
+            let err = res.unwrap_err();
+            assert_eq!(err.downcast_ref::<&str>(), Some(&"buggy future"));
+            act.caught.store(true, Ordering::Relaxed);
+            System::current().stop();
+        })
+        .spawn(ctx);
+    }
+}
+
+#[test]
+fn test_fut_catch_unwind() {
+    let caught = Arc::new(AtomicBool::new(false));
+    let caught2 = Arc::clone(&caught);
+
+    let sys = System::new();
+    sys.block_on(async {
+        let _addr = PanicActor { caught: caught2 }.start();
+    });
+    sys.run().unwrap();
+
+    assert!(caught.load(Ordering::Relaxed), "Panic not caught");
+}
+
+/// Stream that panics when polled after it finished.
+struct Strict(Option<usize>);
+
+impl futures_util::stream::Stream for Strict {
+    type Item = usize;
+
+    fn poll_next(
+        mut self: std::pin::Pin<&mut Self>,
+        _: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<usize>> {
+        let n = self.0.expect("polled after completion");
+        self.0 = n.checked_sub(1);
+        std::task::Poll::Ready(self.0.map(|_| n))
+    }
+}
+
+struct PollPastEnd<S>(S, usize);
+
+impl<S: ActorStream<MyStreamActor2, Item = usize> + Unpin> ActorFuture<MyStreamActor2>
+    for PollPastEnd<S>
+{
+    type Output = usize;
+
+    fn poll(
+        mut self: std::pin::Pin<&mut Self>,
+        act: &mut MyStreamActor2,
+        ctx: &mut Context<MyStreamActor2>,
+        task: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<usize> {
+        let this = &mut *self;
+        for _ in 0..5 {
+            if let std::task::Poll::Ready(Some(n)) =
+                std::pin::Pin::new(&mut this.0).poll_next(act, ctx, task)
+            {
+                this.1 += n;
+            }
+        }
+        std::task::Poll::Ready(this.1)
+    }
+}
+
+struct FuseMsg;
+
+impl Message for FuseMsg {
+    type Result = usize;
+}
+
+impl Handler<FuseMsg> for MyStreamActor2 {
+    type Result = ResponseActFuture<Self, usize>;
+
+    fn handle(&mut self, _: FuseMsg, _: &mut Context<Self>) -> Self::Result {
+        let stream = Strict(Some(2)).into_actor(self).fuse();
+        Box::pin(PollPastEnd(stream, 0))
+    }
+}
+
+#[test]
+fn test_stream_fuse() {
+    System::new().block_on(async {
+        let addr = MyStreamActor2 { counter: 0 }.start();
+        let res = addr.send(FuseMsg).await.unwrap();
+        assert_eq!(res, 3);
+    })
+}
+
+struct DedupMsg(usize);
+
+impl Message for DedupMsg {
+    type Result = Vec<usize>;
+}
+
+impl Handler<DedupMsg> for MyStreamActor2 {
+    type Result = ResponseActFuture<Self, Vec<usize>>;
+
+    fn handle(&mut self, msg: DedupMsg, _: &mut Context<Self>) -> Self::Result {
+        futures_util::stream::iter(vec![1, 2, 1, 3, 1, 2, 4, 2])
+            .into_actor(self)
+            .dedup(msg.0, |n| *n)
+            .collect()
+            .boxed_local()
+    }
+}
+
+#[test]
+fn test_stream_dedup() {
+    System::new().block_on(async {
+        let addr = MyStreamActor2 { counter: 0 }.start();
+
+        // a repeated key stays in the window, evicted keys pass again
+        let res = addr.send(DedupMsg(2)).await.unwrap();
+        assert_eq!(res, vec![1, 2, 3, 2, 4]);
+
+        let res = addr.send(DedupMsg(8)).await.unwrap();
+        assert_eq!(res, vec![1, 2, 3, 4]);
+
+        let res = addr.send(DedupMsg(0)).await.unwrap();
+        assert_eq!(res, vec![1, 2, 1, 3, 1, 2, 4, 2]);
+    })
+}
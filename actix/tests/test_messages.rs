@@ -5,6 +5,7 @@ use std::collections::HashSet;
 use actix::prelude::*;
 
 #[derive(Clone, Debug)]
+#[allow(dead_code)]
 struct Num(usize);
 
 impl Message for Num {
@@ -135,3 +136,57 @@ async fn test_different_message_result_types() {
         "Session with id `1` should already have been inserted"
     );
 }
+
+struct Config(Vec<u8>);
+
+impl Message for Config {
+    type Result = ();
+}
+
+struct Subscriber(std::sync::Arc<std::sync::Mutex<Vec<usize>>>);
+
+impl Actor for Subscriber {
+    type Context = Context<Self>;
+}
+
+impl Handler<std::rc::Rc<Config>> for Subscriber {
+    type Result = ();
+
+    fn handle(&mut self, msg: std::rc::Rc<Config>, _: &mut Self::Context) {
+        assert_eq!(msg.0.len(), 1024);
+        self.0
+            .lock()
+            .unwrap()
+            .push(std::rc::Rc::as_ptr(&msg) as usize);
+    }
+}
+
+#[actix::test]
+async fn test_rc_message() {
+    use std::rc::Rc;
+    use std::sync::{Arc, Mutex};
+
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let inboxes: Vec<_> = (0..3)
+        .map(|_| {
+            let mut inbox = None;
+            let seen = Arc::clone(&seen);
+            Subscriber::create(|ctx| {
+                inbox = Some(ctx.weighted_inbox::<Rc<Config>>(1));
+                Subscriber(seen)
+            });
+            inbox.unwrap()
+        })
+        .collect();
+
+    // every subscriber gets the same allocation
+    let config = Rc::new(Config(vec![0; 1024]));
+    for inbox in &inboxes {
+        inbox.send(Rc::clone(&config)).ok().unwrap();
+    }
+    actix_rt::time::sleep(std::time::Duration::from_millis(10)).await;
+
+    let seen = seen.lock().unwrap();
+    assert_eq!(seen.len(), 3);
+    assert!(seen.iter().all(|&ptr| ptr == Rc::as_ptr(&config) as usize));
+}
@@ -1,10 +1,14 @@
 #![cfg(feature = "macros")]
 
 use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
 use std::task::{Context, Poll};
+use std::time::Duration;
 
 use actix::io::SinkWrite;
 use actix::prelude::*;
+use actix_rt::time::sleep;
 use bytes::{Buf, Bytes};
 use futures_sink::Sink;
 use tokio::sync::mpsc;
@@ -116,6 +120,23 @@ impl Handler<Data> for MyActor {
     }
 }
 
+struct TryData(Bytes);
+
+impl Message for TryData {
+    type Result = Result<(), Bytes>;
+}
+
+impl Handler<TryData> for MyActor {
+    type Result = Result<(), Bytes>;
+    fn handle(&mut self, data: TryData, _ctxt: &mut Self::Context) -> Self::Result {
+        self.sink.try_write(data.0.clone()).unwrap();
+        // sink is still busy with the first item
+        let res = self.sink.try_write(data.0);
+        self.sink.close();
+        res
+    }
+}
+
 #[actix::test]
 async fn test_send_1() {
     let (sender, mut receiver) = mpsc::unbounded_channel();
@@ -259,3 +280,681 @@ async fn test_send_bytes() {
     let res = receiver.recv().await.unwrap();
     assert_eq!(expected_bytes, res);
 }
+
+#[actix::test]
+async fn test_try_write() {
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+
+    let addr = MyActor::create(move |ctxt| {
+        let sink = MySink {
+            sender,
+            queue: Vec::new(),
+        };
+        MyActor {
+            sink: SinkWrite::new(sink, ctxt),
+        }
+    });
+
+    let res = addr
+        .send(TryData(Bytes::from_static(b"Hello")))
+        .await
+        .unwrap();
+    assert_eq!(res, Err(Bytes::from_static(b"Hello")));
+    drop(addr);
+
+    let mut res = Vec::new();
+    while let Some(r) = receiver.recv().await {
+        res.push(r);
+    }
+
+    assert_eq!(b"Hello", &res[..]);
+}
+
+struct Forwarder {
+    sink: SinkWrite<Bytes, SinkUnboundedSender>,
+}
+
+impl Actor for Forwarder {
+    type Context = actix::Context<Self>;
+}
+
+impl Handler<Data> for Forwarder {
+    type Result = ();
+    fn handle(&mut self, data: Data, _ctxt: &mut Self::Context) {
+        let _ = self.sink.write(data.bytes);
+        if data.last {
+            self.sink.close();
+        }
+    }
+}
+
+#[actix::test]
+async fn test_forward() {
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+
+    let addr = Forwarder::create(move |ctxt| Forwarder {
+        sink: SinkWrite::forward(SinkUnboundedSender { tx: sender }, ctxt),
+    });
+
+    addr.do_send(Data {
+        bytes: Bytes::from_static(b"Hello"),
+        last: true,
+    });
+    assert_eq!(receiver.recv().await.unwrap(), Bytes::from_static(b"Hello"));
+
+    // closing the forwarding writer does not stop the actor
+    sleep(Duration::from_millis(10)).await;
+    assert!(addr.connected());
+}
+
+struct StalledSink;
+
+impl Sink<Bytes> for StalledSink {
+    type Error = ();
+
+    fn poll_ready(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Pending
+    }
+
+    fn start_send(self: Pin<&mut Self>, _: Bytes) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Pending
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _: &mut Context) -> Poll<Result<(), Self::Error>> {
+        Poll::Pending
+    }
+}
+
+struct Piped;
+
+impl Actor for Piped {
+    type Context = actix::Context<Self>;
+}
+
+impl actix::io::WriteHandler<()> for Piped {}
+
+#[actix::test]
+async fn test_pipe() {
+    use futures_util::stream::{self, StreamExt};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    let chunks = || {
+        ["Hel", "lo", " world"]
+            .iter()
+            .map(|s| Bytes::from_static(s.as_bytes()))
+    };
+
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    let _addr = MyActor::create(move |ctxt| {
+        let sink = MySink {
+            sender,
+            queue: Vec::new(),
+        };
+        let mut sink = SinkWrite::new(sink, ctxt);
+        sink.pipe(stream::iter(chunks()), ctxt);
+        MyActor { sink }
+    });
+
+    let mut res = Vec::new();
+    while res.len() < 11 {
+        res.push(receiver.recv().await.unwrap());
+    }
+    assert_eq!(b"Hello world", &res[..]);
+
+    // a stalled sink stops reading from the stream
+    let pulled = Arc::new(AtomicUsize::new(0));
+    let pulled2 = Arc::clone(&pulled);
+    let stream = stream::iter(chunks()).inspect(move |_| {
+        pulled2.fetch_add(1, Ordering::SeqCst);
+    });
+    let _addr = Piped::create(move |ctxt| {
+        SinkWrite::new(StalledSink, ctxt).pipe(stream, ctxt);
+        Piped
+    });
+
+    sleep(Duration::from_millis(10)).await;
+    assert_eq!(pulled.load(Ordering::SeqCst), 1);
+}
+
+#[actix::test]
+async fn test_keepalive() {
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    let armed = Arc::new(AtomicUsize::new(0));
+    let armed2 = Arc::clone(&armed);
+
+    let addr = Forwarder::create(move |ctxt| {
+        let mut sink = SinkWrite::forward(SinkUnboundedSender { tx: sender }, ctxt);
+        sink.set_keepalive(Duration::from_millis(20), || Bytes::from_static(b"ping"));
+        // the keepalive timer counts as armed, replacing it does not add one
+        sink.set_keepalive(Duration::from_millis(20), || Bytes::from_static(b"ping"));
+        armed2.store(ctxt.timer_count(), Ordering::SeqCst);
+        Forwarder { sink }
+    });
+    assert_eq!(armed.load(Ordering::SeqCst), 1);
+
+    sleep(Duration::from_millis(30)).await;
+    assert_eq!(receiver.try_recv().unwrap(), Bytes::from_static(b"ping"));
+
+    // sending data restarts the timer
+    addr.do_send(Data {
+        bytes: Bytes::from_static(b"Hello"),
+        last: false,
+    });
+    assert_eq!(receiver.recv().await.unwrap(), Bytes::from_static(b"Hello"));
+    sleep(Duration::from_millis(10)).await;
+    assert!(receiver.try_recv().is_err());
+    assert_eq!(receiver.recv().await.unwrap(), Bytes::from_static(b"ping"));
+
+    // no keepalives once the writer is closed
+    addr.do_send(Data {
+        bytes: Bytes::from_static(b"bye"),
+        last: true,
+    });
+    assert_eq!(receiver.recv().await.unwrap(), Bytes::from_static(b"bye"));
+    sleep(Duration::from_millis(50)).await;
+    assert!(receiver.try_recv().is_err());
+}
+
+/// Connection which fails once it accepted an item without a sender.
+struct Conn {
+    tx: Option<mpsc::UnboundedSender<Bytes>>,
+    accepted: usize,
+}
+
+impl Sink<Bytes> for Conn {
+    type Error = ();
+
+    fn poll_ready(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        match self.tx {
+            None if self.accepted > 0 => Poll::Ready(Err(())),
+            _ => Poll::Ready(Ok(())),
+        }
+    }
+
+    fn start_send(self: Pin<&mut Self>, t: Bytes) -> Result<(), Self::Error> {
+        let this = self.get_mut();
+        this.accepted += 1;
+        if let Some(ref tx) = this.tx {
+            let _ = tx.send(t);
+        }
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(self.tx.as_ref().map(|_| ()).ok_or(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _: &mut Context) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+struct Reconnect {
+    sink: SinkWrite<Bytes, Conn>,
+    tx: Option<mpsc::UnboundedSender<Bytes>>,
+}
+
+impl Actor for Reconnect {
+    type Context = actix::Context<Self>;
+}
+
+impl actix::io::WriteHandler<()> for Reconnect {
+    fn error(&mut self, _: (), _: &mut Self::Context) -> Running {
+        let tx = self.tx.take();
+        self.sink.replace_preserving(Conn { tx, accepted: 0 });
+        Running::Continue
+    }
+}
+
+#[actix::test]
+async fn test_replace_preserving() {
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    let _addr = Reconnect::create(move |ctxt| {
+        let conn = Conn {
+            tx: None,
+            accepted: 0,
+        };
+        let mut sink = SinkWrite::new(conn, ctxt);
+        for s in &["lost", "a", "b"] {
+            sink.write(Bytes::from_static(s.as_bytes())).unwrap();
+        }
+        Reconnect {
+            sink,
+            tx: Some(sender),
+        }
+    });
+
+    // the item handed to the broken sink is gone, buffered ones are kept
+    assert_eq!(receiver.recv().await.unwrap(), Bytes::from_static(b"a"));
+    assert_eq!(receiver.recv().await.unwrap(), Bytes::from_static(b"b"));
+}
+
+/// Forwards items to a channel and counts flushes.
+struct Counted {
+    tx: mpsc::UnboundedSender<Bytes>,
+    flushes: std::rc::Rc<std::cell::Cell<usize>>,
+}
+
+impl Sink<Bytes> for Counted {
+    type Error = ();
+
+    fn poll_ready(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(self: Pin<&mut Self>, t: Bytes) -> Result<(), Self::Error> {
+        self.tx.send(t).map_err(|_| ())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.flushes.set(self.flushes.get() + 1);
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _: &mut Context) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+struct Batcher {
+    sink: SinkWrite<Bytes, Counted>,
+}
+
+impl Actor for Batcher {
+    type Context = actix::Context<Self>;
+}
+
+impl actix::io::WriteHandler<()> for Batcher {}
+
+impl Handler<Data> for Batcher {
+    type Result = ();
+
+    fn handle(&mut self, data: Data, _: &mut Self::Context) {
+        self.sink.write(data.bytes).unwrap();
+        if data.last {
+            self.sink.close();
+        }
+    }
+}
+
+struct Batch(bool);
+
+impl Message for Batch {
+    type Result = ();
+}
+
+impl Handler<Batch> for Batcher {
+    type Result = ();
+
+    fn handle(&mut self, msg: Batch, _: &mut Self::Context) {
+        if msg.0 {
+            self.sink.start_batch();
+        } else {
+            self.sink.end_batch();
+        }
+    }
+}
+
+#[actix::test]
+async fn test_batch() {
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    let flushes = std::rc::Rc::new(std::cell::Cell::new(0));
+    let counted = Counted {
+        tx: sender,
+        flushes: flushes.clone(),
+    };
+    let addr = Batcher::create(move |ctxt| Batcher {
+        sink: SinkWrite::new(counted, ctxt),
+    });
+    let data = |s: &'static str, last| Data {
+        bytes: Bytes::from_static(s.as_bytes()),
+        last,
+    };
+
+    addr.send(Batch(true)).await.unwrap();
+    let before = flushes.get();
+    addr.send(data("a", false)).await.unwrap();
+    addr.send(data("b", false)).await.unwrap();
+    sleep(Duration::from_millis(10)).await;
+    assert!(receiver.try_recv().is_err());
+
+    // the whole batch is sent with a single flush
+    addr.send(Batch(false)).await.unwrap();
+    assert_eq!(receiver.recv().await.unwrap(), Bytes::from_static(b"a"));
+    assert_eq!(receiver.recv().await.unwrap(), Bytes::from_static(b"b"));
+    assert_eq!(flushes.get(), before + 1);
+
+    // closing sends a partial batch
+    addr.send(Batch(true)).await.unwrap();
+    addr.send(data("c", true)).await.unwrap();
+    assert_eq!(receiver.recv().await.unwrap(), Bytes::from_static(b"c"));
+}
+
+struct Pipelined {
+    sink: SinkWrite<Bytes, SinkUnboundedSender>,
+}
+
+impl Actor for Pipelined {
+    type Context = actix::Context<Self>;
+}
+
+struct Response(u64, &'static str);
+
+impl Message for Response {
+    type Result = Result<(), Bytes>;
+}
+
+impl Handler<Response> for Pipelined {
+    type Result = Result<(), Bytes>;
+
+    fn handle(&mut self, msg: Response, _: &mut Self::Context) -> Self::Result {
+        self.sink
+            .write_ordered(msg.0, Bytes::from_static(msg.1.as_bytes()))
+    }
+}
+
+#[actix::test]
+async fn test_write_ordered() {
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let addr = Pipelined::create(move |ctx| Pipelined {
+        sink: SinkWrite::forward(SinkUnboundedSender { tx }, ctx),
+    });
+
+    for &(seq, item) in &[(2, "c"), (0, "a"), (3, "d"), (5, "f"), (1, "b")] {
+        addr.send(Response(seq, item)).await.unwrap().unwrap();
+    }
+    // sequence numbers are only written once
+    assert!(addr.send(Response(1, "b")).await.unwrap().is_err());
+    assert!(addr.send(Response(5, "f")).await.unwrap().is_err());
+
+    for item in &["a", "b", "c", "d"] {
+        assert_eq!(rx.recv().await.unwrap(), item.as_bytes());
+    }
+    // item 5 waits for the gap to be filled
+    sleep(Duration::from_millis(10)).await;
+    assert!(rx.try_recv().is_err());
+    addr.send(Response(4, "e")).await.unwrap().unwrap();
+    assert_eq!(rx.recv().await.unwrap(), "e".as_bytes());
+    assert_eq!(rx.recv().await.unwrap(), "f".as_bytes());
+}
+
+struct Bounded {
+    sink: SinkWrite<Bytes, SinkUnboundedSender>,
+}
+
+impl Actor for Bounded {
+    type Context = actix::Context<Self>;
+}
+
+struct Burst(actix::io::SinkOverflow);
+
+impl Message for Burst {
+    type Result = Vec<Bytes>;
+}
+
+impl Handler<Burst> for Bounded {
+    type Result = MessageResult<Burst>;
+
+    fn handle(&mut self, msg: Burst, _: &mut Self::Context) -> Self::Result {
+        // the batch keeps items in the buffer until all are written
+        self.sink.set_buffer_limit(2, msg.0);
+        self.sink.start_batch();
+        let rejected = ["a", "b", "c", "d"]
+            .iter()
+            .filter_map(|s| self.sink.write(Bytes::from_static(s.as_bytes())).err())
+            .collect();
+        self.sink.end_batch();
+        MessageResult(rejected)
+    }
+}
+
+#[actix::test]
+async fn test_buffer_limit() {
+    use actix::io::SinkOverflow::*;
+
+    let cases = [
+        (DropOldest, ["c", "d"], vec![]),
+        (DropNewest, ["a", "d"], vec![]),
+        (Reject, ["a", "b"], vec!["c", "d"]),
+    ];
+    for (policy, sent, rejected) in cases.iter() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let addr = Bounded::create(move |ctx| Bounded {
+            sink: SinkWrite::forward(SinkUnboundedSender { tx }, ctx),
+        });
+
+        let res = addr.send(Burst(*policy)).await.unwrap();
+        assert_eq!(res, rejected.to_vec(), "{:?}", policy);
+        for item in sent {
+            assert_eq!(rx.recv().await.unwrap(), item.as_bytes(), "{:?}", policy);
+        }
+        sleep(Duration::from_millis(5)).await;
+        assert!(rx.try_recv().is_err());
+    }
+}
+
+/// Sink failing in `poll_ready` or rejecting every item in `start_send`.
+struct Failing {
+    in_ready: Arc<AtomicBool>,
+}
+
+impl Sink<Bytes> for Failing {
+    type Error = ();
+
+    fn poll_ready(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        if self.in_ready.load(Ordering::SeqCst) {
+            Poll::Ready(Err(()))
+        } else {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    fn start_send(self: Pin<&mut Self>, _: Bytes) -> Result<(), Self::Error> {
+        Err(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _: &mut Context) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+struct Rejected {
+    sink: SinkWrite<Bytes, Failing>,
+    errors: Arc<AtomicUsize>,
+}
+
+impl Actor for Rejected {
+    type Context = actix::Context<Self>;
+}
+
+impl actix::io::WriteHandler<()> for Rejected {
+    fn error(&mut self, _: (), _: &mut Self::Context) -> Running {
+        self.errors.fetch_add(1, Ordering::SeqCst);
+        Running::Stop
+    }
+}
+
+impl Handler<TryData> for Rejected {
+    type Result = Result<(), Bytes>;
+
+    fn handle(&mut self, data: TryData, _: &mut Self::Context) -> Self::Result {
+        self.sink.try_write(data.0)
+    }
+}
+
+impl Handler<Data> for Rejected {
+    type Result = ();
+
+    fn handle(&mut self, data: Data, _: &mut Self::Context) {
+        let _ = self.sink.write(data.bytes);
+    }
+}
+
+#[actix::test]
+async fn test_sink_errors_reported() {
+    let cases = [(false, true), (true, false), (true, true), (false, false)];
+    for &(in_ready, try_write) in cases.iter() {
+        let errors = Arc::new(AtomicUsize::new(0));
+        let errors2 = Arc::clone(&errors);
+        let failing = Arc::new(AtomicBool::new(false));
+        let sink = Failing {
+            in_ready: Arc::clone(&failing),
+        };
+        let addr = Rejected::create(move |ctx| Rejected {
+            sink: SinkWrite::new(sink, ctx),
+            errors: errors2,
+        });
+
+        // let the writer run first, so items are sent without buffering
+        sleep(Duration::from_millis(1)).await;
+        failing.store(in_ready, Ordering::SeqCst);
+        let bytes = Bytes::from_static(b"a");
+        if try_write {
+            // a sink failing to get ready does not take the item
+            let res = addr.send(TryData(bytes.clone())).await.unwrap();
+            assert_eq!(res, if in_ready { Err(bytes) } else { Ok(()) });
+        } else {
+            // buffered items are reported by the writer, even unflushed
+            let data = Data { bytes, last: false };
+            addr.send(data).await.unwrap();
+        }
+
+        sleep(Duration::from_millis(5)).await;
+        let case = (in_ready, try_write);
+        assert_eq!(errors.load(Ordering::SeqCst), 1, "case: {:?}", case);
+        // stopping on the error ends the writer and the actor with it
+        assert!(!addr.connected());
+    }
+}
+
+/// Sink which does not accept items until it is opened, without waking.
+struct Gated {
+    open: Arc<AtomicBool>,
+    tx: mpsc::UnboundedSender<Bytes>,
+}
+
+impl Sink<Bytes> for Gated {
+    type Error = ();
+
+    fn poll_ready(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        if self.open.load(Ordering::SeqCst) {
+            Poll::Ready(Ok(()))
+        } else {
+            Poll::Pending
+        }
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Bytes) -> Result<(), Self::Error> {
+        self.tx.send(item).map_err(|_| ())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+struct Draining {
+    sink: SinkWrite<Bytes, Gated>,
+}
+
+impl Actor for Draining {
+    type Context = actix::Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        let _ = self.sink.write(Bytes::from_static(b"last words"));
+        // never completes, so the drain runs into its deadline
+        ctx.spawn(fut::wrap_future(futures_util::future::pending::<()>()));
+        ctx.drain_and_stop(Duration::from_millis(30));
+    }
+}
+
+#[actix::test]
+async fn test_drain_deadline_flushes_sink() {
+    let (tx, mut receiver) = mpsc::unbounded_channel();
+    let open = Arc::new(AtomicBool::new(false));
+    let gate = Arc::clone(&open);
+
+    let addr = Draining::create(move |ctxt| Draining {
+        sink: SinkWrite::forward(Gated { open: gate, tx }, ctxt),
+    });
+
+    sleep(Duration::from_millis(10)).await;
+    assert!(receiver.try_recv().is_err());
+    open.store(true, Ordering::SeqCst);
+
+    sleep(Duration::from_millis(40)).await;
+    assert_eq!(
+        receiver.try_recv().unwrap(),
+        Bytes::from_static(b"last words")
+    );
+    assert!(!addr.connected());
+}
+
+struct Broken {
+    sink: SinkWrite<Bytes, Failing>,
+    stopped: Arc<AtomicBool>,
+}
+
+impl Actor for Broken {
+    type Context = actix::Context<Self>;
+
+    fn stopped(&mut self, _: &mut Self::Context) {
+        self.stopped.store(true, Ordering::SeqCst);
+    }
+}
+
+struct Probe;
+
+impl Message for Probe {
+    type Result = bool;
+}
+
+impl Handler<Probe> for Broken {
+    type Result = bool;
+
+    fn handle(&mut self, _: Probe, _: &mut Self::Context) -> bool {
+        self.sink.write(Bytes::from_static(b"probe")).is_ok()
+    }
+}
+
+#[actix::test]
+async fn test_forward_rejects_writes_after_error() {
+    use futures_util::stream::{self, StreamExt};
+
+    let stopped = Arc::new(AtomicBool::new(false));
+    let addr = Broken::create({
+        let stopped = Arc::clone(&stopped);
+        move |ctxt| {
+            let mut sink = SinkWrite::forward(
+                Failing {
+                    in_ready: Arc::new(AtomicBool::new(true)),
+                },
+                ctxt,
+            );
+            let items =
+                stream::iter(vec![Bytes::from_static(b"first")]).chain(stream::pending());
+            sink.pipe(items, ctxt);
+            Broken { sink, stopped }
+        }
+    });
+
+    sleep(Duration::from_millis(10)).await;
+    // the failed writer is closed, and the pipe does not stall on it
+    assert!(!addr.send(Probe).await.unwrap());
+    drop(addr);
+    sleep(Duration::from_millis(10)).await;
+    assert!(stopped.load(Ordering::SeqCst));
+}
@@ -68,3 +68,67 @@ fn test_supervisor_restart() {
     assert_eq!(restarts.load(Ordering::Relaxed), 2);
     assert_eq!(messages.load(Ordering::Relaxed), 2);
 }
+
+struct Ticker {
+    started: bool,
+    preserved: Arc<AtomicUsize>,
+    dropped: Arc<AtomicUsize>,
+}
+
+impl Actor for Ticker {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Context<Self>) {
+        if self.started {
+            return;
+        }
+        self.started = true;
+
+        let handle = ctx.run_interval(Duration::from_millis(5), |act, _| {
+            act.preserved.fetch_add(1, Ordering::SeqCst);
+        });
+        ctx.preserve_on_restart(handle);
+        ctx.run_interval(Duration::from_millis(5), |act, _| {
+            act.dropped.fetch_add(1, Ordering::SeqCst);
+        });
+    }
+}
+
+impl actix::Supervised for Ticker {}
+
+impl actix::Handler<Die> for Ticker {
+    type Result = ();
+
+    fn handle(&mut self, _: Die, ctx: &mut Context<Self>) {
+        ctx.stop();
+    }
+}
+
+#[test]
+fn test_supervisor_preserve_on_restart() {
+    let preserved = Arc::new(AtomicUsize::new(0));
+    let dropped = Arc::new(AtomicUsize::new(0));
+    let preserved2 = Arc::clone(&preserved);
+    let dropped2 = Arc::clone(&dropped);
+
+    let sys = System::new();
+    sys.block_on(async move {
+        let addr = actix::Supervisor::start(move |_| Ticker {
+            started: false,
+            preserved: preserved2,
+            dropped: dropped2,
+        });
+        sleep(Duration::from_millis(25)).await;
+        addr.send(Die).await.unwrap();
+        sleep(Duration::from_millis(10)).await;
+
+        let preserved_before = preserved.load(Ordering::SeqCst);
+        let dropped_before = dropped.load(Ordering::SeqCst);
+        assert!(preserved_before > 0);
+        assert!(dropped_before > 0);
+
+        sleep(Duration::from_millis(25)).await;
+        assert!(preserved.load(Ordering::SeqCst) > preserved_before);
+        assert_eq!(dropped.load(Ordering::SeqCst), dropped_before);
+    });
+}